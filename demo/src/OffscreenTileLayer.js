@@ -7,7 +7,7 @@ export class OffscreenTileLayer extends Tile {
   constructor(opt_options) {
     super(opt_options)
     this.queue = []
-    this.shared_state = new SharedArrayBuffer(7 * Uint32Array.BYTES_PER_ELEMENT)
+    this.shared_state = new SharedArrayBuffer(8 * Uint32Array.BYTES_PER_ELEMENT)
   }
 
   createContainer() {
@@ -127,7 +127,7 @@ export class OffscreenTileLayer extends Tile {
     }
   }
 
-  setFrameState({ size, viewState: { center, resolution, rotation } }) {
+  setFrameState({ size, pixelRatio, viewState: { center, resolution, rotation } }) {
     const slice = new Uint32Array(this.shared_state)
 
     const buffer = new ArrayBuffer(this.shared_state.byteLength)
@@ -141,6 +141,7 @@ export class OffscreenTileLayer extends Tile {
     f32_buffer[3] = center[1]
     f32_buffer[4] = resolution
     f32_buffer[5] = rotation
+    f32_buffer[7] = pixelRatio
 
     for (let i = 0; i < uint32_buffer.length; i++) {
       Atomics.store(slice, i, uint32_buffer[i])
@@ -1,5 +1,4 @@
-import init, { initThreadPool, startWithOffscreenCanvas, render, addPbfTileData } from 'wgpu-layers'
-import { create, makeInverse } from 'ol/transform'
+import init, { initThreadPool, startWithOffscreenCanvas, renderWithCamera, addPbfTileData } from 'wgpu-layers'
 
 import { READY, STARTED, CANVAS, SHARED_ARRAY_BUFFER, PBF_DATA } from './types'
 
@@ -19,7 +18,11 @@ self.onmessage = async ({ data: { type, payload } }) => {
     case PBF_DATA:
       const { data, tileCoord, extent } = payload
       if (ready) {
-        await addPbfTileData(new Uint8Array(data), tileCoord, extent)
+        try {
+          await addPbfTileData(new Uint8Array(data), tileCoord, extent)
+        } catch (error) {
+          console.error(error) // skip corrupt tiles
+        }
       }
       break
   }
@@ -28,12 +31,15 @@ self.onmessage = async ({ data: { type, payload } }) => {
 function loop() {
   Atomics.wait(new Int32Array(shared_state), 6, 0) // wait until notify
 
-  const { size, viewState } = getFrameState()
+  const { size, pixelRatio, viewState } = getFrameState()
 
-  const [width, height] = size
-  render(
-    getViewMatrix(viewState, width, height),
-    size
+  renderWithCamera(
+    viewState.center,
+    getZoom(viewState.resolution),
+    viewState.rotation,
+    0.0, // pitch
+    size.map(value => Math.round(value * pixelRatio)),
+    pixelRatio
   )
 
   setTimeout(loop)
@@ -52,6 +58,7 @@ function getFrameState() {
 
   return {
     size: [uint32_buffer[0], uint32_buffer[1]],
+    pixelRatio: f32_buffer[7],
     viewState: {
       center: [f32_buffer[2], f32_buffer[3]],
       resolution: f32_buffer[4],
@@ -60,36 +67,10 @@ function getFrameState() {
   }
 }
 
-function getViewMatrix(viewState, width, height) {
-  const halfWidth = width * .5
-  const halfHeight = height * .5
+const RESOLUTION_ZOOM_0 = 156543.03392804097
 
-  const scaleX = viewState.resolution * halfWidth
-  const scaleY = viewState.resolution * halfHeight
-
-  const viewTransform = create()
-
-  const sin = Math.sin(viewState.rotation)
-  const cos = Math.cos(viewState.rotation)
-
-  viewTransform[0] = scaleX * cos
-  viewTransform[1] = scaleX * sin
-  viewTransform[2] = scaleY * -sin
-  viewTransform[3] = scaleY * cos
-  viewTransform[4] = viewState.center[0]
-  viewTransform[5] = viewState.center[1]
-
-  const viewMatrix = makeInverse(
-    create(),
-    viewTransform
-  )
-
-  return [
-    viewMatrix[0], viewMatrix[1], 0.0, 0.0,
-    viewMatrix[2], viewMatrix[3], 0.0, 0.0,
-    0.0, 0.0, 1.0, 0.0,
-    viewMatrix[4], viewMatrix[5], 0.0, 1.0
-  ]
+function getZoom(resolution) {
+  return Math.log2(RESOLUTION_ZOOM_0 / resolution)
 }
 
 async function run() {
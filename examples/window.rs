@@ -1,19 +1,34 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod example {
-  use log::info;
+  use log::{error, info};
   use pollster::FutureExt;
   use std::sync::Arc;
 
+  const BEARING_STEP: f32 = std::f32::consts::PI / 16.0;
+  const PITCH_STEP: f32 = std::f32::consts::PI / 36.0;
+
+  const SOURCE: &str = "osm";
+
   struct Application {
     window: Option<Arc<winit::window::Window>>,
+    instance: Option<wgpu_layers::Instance>,
     size: winit::dpi::PhysicalSize<u32>,
+    center: [f32; 2],
+    zoom: f32,
+    bearing: f32,
+    pitch: f32,
   }
 
   impl Application {
     fn new() -> Self {
       Self {
         window: None,
+        instance: None,
         size: winit::dpi::PhysicalSize::new(512, 512),
+        center: [2_402_790.2, 6_916_642.0],
+        zoom: 2.2,
+        bearing: 0.0,
+        pitch: 0.0,
       }
     }
     fn create_window(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
@@ -31,6 +46,34 @@ mod example {
 
       self.window = Some(Arc::new(window));
     }
+
+    /// same tile as the browser test, so native and web output can be compared. read from
+    /// the MBTiles file given as first argument instead, if there is one
+    fn add_tile(&self) -> Result<(), wgpu_layers::error::Error> {
+      let Some(instance) = self.instance.as_ref() else {
+        return Ok(());
+      };
+      instance.add_source(SOURCE.to_owned());
+      instance.add_layer(SOURCE.to_owned(), SOURCE.to_owned(), None)?;
+
+      let tile_coord = vec![4, 8, 5];
+      let extent = vec![0.0, 5009377.085697312, 2_504_688.5, 7_514_065.5];
+      if let Some(path) = std::env::args().nth(1) {
+        let metadata = instance.add_mbtiles(SOURCE.to_owned(), path)?;
+        info!("MBTiles with source layers {:?}", metadata.vector_layers);
+        return instance
+          .add_mbtiles_tile(SOURCE.to_owned(), tile_coord, extent)
+          .block_on();
+      }
+      instance
+        .add_pbf_tile_data(
+          SOURCE.to_owned(),
+          include_bytes!("../tests/pbf/osm_4_8_5.pbf").to_vec(),
+          tile_coord,
+          extent,
+        )
+        .block_on()
+    }
   }
 
   impl winit::application::ApplicationHandler for Application {
@@ -39,7 +82,19 @@ mod example {
 
       self.create_window(event_loop);
 
-      wgpu_layers::init(self, (self.size.width, self.size.height)).block_on();
+      let size = (self.size.width, self.size.height);
+      match wgpu_layers::Instance::new(self, size, None).block_on() {
+        Ok(instance) => self.instance = Some(instance),
+        Err(err) => {
+          error!("{}", err);
+          event_loop.exit();
+          return;
+        }
+      }
+
+      if let Err(err) = self.add_tile() {
+        error!("{}", err);
+      }
     }
 
     fn window_event(
@@ -58,28 +113,46 @@ mod example {
               ..
             },
           ..
-        } => match logical_key {
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::Enter) => event_loop.exit(),
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowUp) => {}
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowDown) => {}
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowLeft) => {}
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowRight) => {}
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::PageUp) => {}
-          winit::keyboard::Key::Named(winit::keyboard::NamedKey::PageDown) => {}
-          _ => (),
-        },
+        } => {
+          match logical_key {
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::Enter) => event_loop.exit(),
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowUp) => {
+              self.pitch += PITCH_STEP
+            }
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowDown) => {
+              self.pitch = (self.pitch - PITCH_STEP).max(0.0)
+            }
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowLeft) => {
+              self.bearing -= BEARING_STEP
+            }
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowRight) => {
+              self.bearing += BEARING_STEP
+            }
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::PageUp) => self.zoom += 0.5,
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::PageDown) => self.zoom -= 0.5,
+            _ => (),
+          }
+          if let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+          }
+        }
         winit::event::WindowEvent::Resized(size) => {
           self.size = size;
         }
         winit::event::WindowEvent::RedrawRequested => {
-          #[rustfmt::skip]
-            let view_matrix = vec![
-              1.15142285e-7, -0.0, 0.0, 0.0,
-              0.0, 1.15142285e-7, 0.0, 0.0,
-              -0.27666306, -0.7963807, 1.0, 0.0,
-              0.0, 0.0, 0.0, 1.0,
-            ];
-          wgpu_layers::render(view_matrix, vec![self.size.width, self.size.height]);
+          let Some(instance) = self.instance.as_ref() else {
+            return;
+          };
+          if let Err(err) = instance.render_with_camera(
+            self.center.to_vec(),
+            self.zoom,
+            self.bearing,
+            self.pitch,
+            vec![self.size.width, self.size.height],
+            self.window.as_ref().unwrap().scale_factor() as f32,
+          ) {
+            error!("{}", err);
+          }
         }
         _ => (),
       }
@@ -0,0 +1,83 @@
+/// bigger outputs are rejected, so a small corrupt or malicious input can't exhaust the
+/// memory
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 << 20;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// gzip or zlib compressed data is detected by its header, other data is returned as is
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, String> {
+  if data.starts_with(&GZIP_MAGIC) {
+    gunzip(&data)
+  } else if is_zlib(&data) {
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&data, MAX_DECOMPRESSED_SIZE)
+      .map_err(|status| format!("zlib decompression failed: {status:?}"))
+  } else {
+    Ok(data)
+  }
+}
+
+/// deflate with a window of at most 32k and a valid header checksum, a protobuf message
+/// can't start like this as field 15 isn't used by vector tiles
+fn is_zlib(data: &[u8]) -> bool {
+  match data {
+    [cmf, flg, ..] => {
+      cmf & 0x0f == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0
+    }
+    _ => false,
+  }
+}
+
+/// gzip member as written by gzip or zlib's gzip mode, the checksum isn't verified
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+  const FHCRC: u8 = 1 << 1;
+  const FEXTRA: u8 = 1 << 2;
+  const FNAME: u8 = 1 << 3;
+  const FCOMMENT: u8 = 1 << 4;
+
+  let invalid = || "invalid gzip header".to_owned();
+  if data.len() < 18 || data[..2] != GZIP_MAGIC || data[2] != 8 {
+    return Err(invalid());
+  }
+  let flags = data[3];
+  let mut offset = 10;
+
+  if flags & FEXTRA != 0 {
+    let length = u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+    offset += 2 + length;
+  }
+  for flag in [FNAME, FCOMMENT] {
+    if flags & flag != 0 {
+      let end = data
+        .get(offset..)
+        .and_then(|rest| rest.iter().position(|byte| *byte == 0))
+        .ok_or_else(invalid)?;
+      offset += end + 1;
+    }
+  }
+  if flags & FHCRC != 0 {
+    offset += 2;
+  }
+
+  // deflate stream is followed by crc32 and size
+  let deflated = data.get(offset..data.len() - 8).ok_or_else(invalid)?;
+  miniz_oxide::inflate::decompress_to_vec_with_limit(deflated, MAX_DECOMPRESSED_SIZE)
+    .map_err(|status| format!("gzip decompression failed: {status:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::gzip;
+
+  #[test]
+  fn detect_compression() {
+    let pbf = b"\x1a\x05layer".to_vec();
+    let gzip = gzip(&pbf, Some("tile.pbf"));
+    let zlib = miniz_oxide::deflate::compress_to_vec_zlib(&pbf, 6);
+
+    assert_eq!(decompress(gzip).unwrap(), pbf);
+    assert_eq!(decompress(zlib).unwrap(), pbf);
+    assert_eq!(decompress(pbf.clone()).unwrap(), pbf);
+    assert!(decompress(vec![0x1f, 0x8b, 8, 0]).is_err());
+  }
+}
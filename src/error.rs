@@ -0,0 +1,111 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+  /// surface can't be created or has no usable configuration
+  Surface(String),
+
+  /// no suitable adapter found
+  Adapter(wgpu::RequestAdapterError),
+
+  /// device can't be requested from the adapter
+  Device(wgpu::RequestDeviceError),
+
+  /// tile data can't be parsed
+  TileDecode(String),
+
+  /// tile format is neither "mvt" nor "mlt"
+  UnknownTileFormat(String),
+
+  /// GeoJSON can't be parsed or the source has none
+  GeoJson(String),
+
+  /// tile archive can't be read or the source has none
+  Archive(String),
+
+  /// tile coordinate needs exactly three values [z, x, y]
+  InvalidTileCoord(Vec<u32>),
+
+  /// size needs exactly two values [width, height]
+  InvalidSize(Vec<u32>),
+
+  /// raster pixels don't match their size or the size isn't supported
+  InvalidImage(String),
+
+  /// line geometry can't be tessellated
+  Tessellation(String),
+
+  /// geometry doesn't fit into a gpu buffer of this many bytes, the limit of the device
+  BufferLimit(u64),
+
+  /// parsed buckets can't be received from the tile parsers
+  ParserQueue(std::sync::mpsc::TryRecvError),
+
+  /// surface texture can't be acquired
+  SurfaceLost(wgpu::SurfaceError),
+
+  /// extent needs exactly four values [min_x, min_y, max_x, max_y]
+  InvalidExtent(Vec<f32>),
+
+  /// resolutions need to be positive and descending
+  InvalidResolution(Vec<f32>),
+
+  /// renderer is used before it was initialized
+  NotInitialized,
+
+  /// no source registered with this name
+  UnknownSource(String),
+
+  /// no layer registered with this name
+  UnknownLayer(String),
+
+  /// device was lost and couldn't be recreated
+  DeviceLost,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Surface(err) => write!(f, "surface error: {err}"),
+      Error::Adapter(err) => write!(f, "adapter error: {err}"),
+      Error::Device(err) => write!(f, "device error: {err}"),
+      Error::TileDecode(err) => write!(f, "tile decode error: {err}"),
+      Error::UnknownTileFormat(format) => write!(f, "unknown tile format: {format}"),
+      Error::GeoJson(err) => write!(f, "geojson error: {err}"),
+      Error::Archive(err) => write!(f, "archive error: {err}"),
+      Error::InvalidTileCoord(tile_coord) => write!(f, "invalid tile coordinate: {tile_coord:?}"),
+      Error::InvalidSize(size) => write!(f, "invalid size: {size:?}"),
+      Error::InvalidImage(err) => write!(f, "invalid image: {err}"),
+      Error::Tessellation(err) => write!(f, "tessellation error: {err}"),
+      Error::BufferLimit(limit) => write!(f, "buffer limit of {limit} bytes exceeded"),
+      Error::ParserQueue(err) => write!(f, "parser queue error: {err}"),
+      Error::SurfaceLost(err) => write!(f, "surface lost: {err}"),
+      Error::InvalidExtent(extent) => write!(f, "invalid extent: {extent:?}"),
+      Error::InvalidResolution(resolutions) => write!(f, "invalid resolution: {resolutions:?}"),
+      Error::NotInitialized => write!(f, "renderer not initialized"),
+      Error::UnknownSource(name) => write!(f, "unknown source: {name}"),
+      Error::UnknownLayer(name) => write!(f, "unknown layer: {name}"),
+      Error::DeviceLost => write!(f, "device lost"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Adapter(err) => Some(err),
+      Error::Device(err) => Some(err),
+      Error::SurfaceLost(err) => Some(err),
+      Error::ParserQueue(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+/// errors reject the promise of async functions or are thrown by sync functions
+#[cfg(target_arch = "wasm32")]
+impl From<Error> for wasm_bindgen::JsValue {
+  fn from(err: Error) -> Self {
+    wasm_bindgen::JsError::from(err).into()
+  }
+}
@@ -0,0 +1,154 @@
+/// table of a flatbuffer, fields are looked up through its vtable. every access is
+/// bounds checked, so corrupt data results in an error instead of a panic
+#[derive(Clone, Copy)]
+pub struct Table<'a> {
+  bytes: &'a [u8],
+
+  position: usize,
+
+  vtable: usize,
+}
+
+impl<'a> Table<'a> {
+  /// table referenced by the offset at the start of the buffer
+  pub fn root(bytes: &'a [u8]) -> Result<Self, String> {
+    Self::at(bytes, read_reference(bytes, 0)?)
+  }
+
+  fn at(bytes: &'a [u8], position: usize) -> Result<Self, String> {
+    let soffset = i32::from_le_bytes(read_array(bytes, position)?);
+    let vtable = (position as i64 - soffset as i64)
+      .try_into()
+      .map_err(|_| invalid())?;
+    Ok(Self {
+      bytes,
+      position,
+      vtable,
+    })
+  }
+
+  /// none if the field isn't set
+  fn get_field_position(&self, field: usize) -> Result<Option<usize>, String> {
+    let vtable_size = u16::from_le_bytes(read_array(self.bytes, self.vtable)?) as usize;
+    let entry = 4 + 2 * field;
+    if entry + 2 > vtable_size {
+      return Ok(None);
+    }
+    let offset = u16::from_le_bytes(read_array(self.bytes, self.vtable + entry)?) as usize;
+    Ok((offset != 0).then_some(self.position + offset))
+  }
+
+  pub fn get_u8(&self, field: usize, default: u8) -> Result<u8, String> {
+    match self.get_field_position(field)? {
+      Some(position) => Ok(read_array::<1>(self.bytes, position)?[0]),
+      None => Ok(default),
+    }
+  }
+
+  pub fn get_u16(&self, field: usize, default: u16) -> Result<u16, String> {
+    match self.get_field_position(field)? {
+      Some(position) => Ok(u16::from_le_bytes(read_array(self.bytes, position)?)),
+      None => Ok(default),
+    }
+  }
+
+  pub fn get_i32(&self, field: usize, default: i32) -> Result<i32, String> {
+    match self.get_field_position(field)? {
+      Some(position) => Ok(i32::from_le_bytes(read_array(self.bytes, position)?)),
+      None => Ok(default),
+    }
+  }
+
+  pub fn get_u64(&self, field: usize, default: u64) -> Result<u64, String> {
+    match self.get_field_position(field)? {
+      Some(position) => Ok(u64::from_le_bytes(read_array(self.bytes, position)?)),
+      None => Ok(default),
+    }
+  }
+
+  pub fn get_table(&self, field: usize) -> Result<Option<Table<'a>>, String> {
+    let Some(position) = self.get_field_position(field)? else {
+      return Ok(None);
+    };
+    Self::at(self.bytes, read_reference(self.bytes, position)?).map(Some)
+  }
+
+  /// bytes of a vector with elements of the given size
+  fn get_vector(&self, field: usize, size: usize) -> Result<&'a [u8], String> {
+    let Some(position) = self.get_field_position(field)? else {
+      return Ok(&[]);
+    };
+    let start = read_reference(self.bytes, position)?;
+    let length = u32::from_le_bytes(read_array(self.bytes, start)?) as usize;
+    length
+      .checked_mul(size)
+      .and_then(|length| self.bytes.get(start + 4..start + 4 + length))
+      .ok_or_else(invalid)
+  }
+
+  pub fn get_bytes(&self, field: usize) -> Result<&'a [u8], String> {
+    self.get_vector(field, 1)
+  }
+
+  pub fn get_u32s(&self, field: usize) -> Result<Vec<u32>, String> {
+    Ok(
+      self
+        .get_vector(field, 4)?
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect(),
+    )
+  }
+
+  pub fn get_f64s(&self, field: usize) -> Result<Vec<f64>, String> {
+    Ok(
+      self
+        .get_vector(field, 8)?
+        .chunks_exact(8)
+        .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+        .collect(),
+    )
+  }
+
+  pub fn get_string(&self, field: usize) -> Result<Option<String>, String> {
+    if self.get_field_position(field)?.is_none() {
+      return Ok(None);
+    }
+    let bytes = self.get_vector(field, 1)?;
+    String::from_utf8(bytes.to_vec())
+      .map(Some)
+      .map_err(|err| err.to_string())
+  }
+
+  pub fn get_tables(&self, field: usize) -> Result<Vec<Table<'a>>, String> {
+    let Some(position) = self.get_field_position(field)? else {
+      return Ok(Vec::new());
+    };
+    let start = read_reference(self.bytes, position)?;
+    let count = self.get_vector(field, 4)?.len() / 4;
+    (0..count)
+      .map(|i| {
+        let position = start + 4 + i * 4;
+        Self::at(self.bytes, read_reference(self.bytes, position)?)
+      })
+      .collect()
+  }
+}
+
+fn invalid() -> String {
+  "invalid flatbuffer".to_owned()
+}
+
+fn read_array<const N: usize>(bytes: &[u8], position: usize) -> Result<[u8; N], String> {
+  position
+    .checked_add(N)
+    .and_then(|end| bytes.get(position..end))
+    .map(|bytes| bytes.try_into().unwrap())
+    .ok_or_else(invalid)
+}
+
+/// offsets point forward from their own position
+fn read_reference(bytes: &[u8], position: usize) -> Result<usize, String> {
+  let offset = u32::from_le_bytes(read_array(bytes, position)?) as usize;
+  position.checked_add(offset).ok_or_else(invalid)
+}
@@ -0,0 +1,59 @@
+use std::ops::Range;
+
+/// bounding box as four f64 followed by the u64 offset
+pub const NODE_SIZE: u64 = 40;
+
+/// node of the packed Hilbert R-tree. inner nodes point to the index of their first
+/// child node, leaves to the byte offset of their feature in the feature section
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Node {
+  pub bbox: [f64; 4],
+
+  pub offset: u64,
+}
+
+impl Node {
+  pub fn parse(bytes: &[u8]) -> Self {
+    let get_f64 = |i: usize| f64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap());
+    Self {
+      bbox: [get_f64(0), get_f64(1), get_f64(2), get_f64(3)],
+      offset: u64::from_le_bytes(bytes[32..40].try_into().unwrap()),
+    }
+  }
+
+  pub fn intersects(&self, bbox: &[f64; 4]) -> bool {
+    self.bbox[0] <= bbox[2]
+      && self.bbox[2] >= bbox[0]
+      && self.bbox[1] <= bbox[3]
+      && self.bbox[3] >= bbox[1]
+  }
+}
+
+/// node indices of the levels from the leaves up to the root. the root is stored first,
+/// so the leaves are at the end of the index
+pub fn get_level_bounds(num_items: u64, node_size: u16) -> Vec<Range<u64>> {
+  if num_items == 0 {
+    return Vec::new();
+  }
+
+  let mut level_num_nodes = vec![num_items];
+  let mut n = num_items;
+  let mut num_nodes = n;
+  loop {
+    n = n.div_ceil(node_size as u64);
+    num_nodes += n;
+    level_num_nodes.push(n);
+    if n == 1 {
+      break;
+    }
+  }
+
+  let mut end = num_nodes;
+  level_num_nodes
+    .into_iter()
+    .map(|count| {
+      end -= count;
+      end..end + count
+    })
+    .collect()
+}
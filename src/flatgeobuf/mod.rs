@@ -0,0 +1,748 @@
+use std::{
+  cell::RefCell,
+  collections::{HashMap, VecDeque},
+  f64::consts::PI,
+  ops::Range,
+  rc::Rc,
+};
+
+use geo_types::{
+  Coord, Geometry, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon,
+  Point, Polygon,
+};
+use mvt_reader::feature::Value;
+
+use crate::{
+  geojson::{self, GeoJsonIndex},
+  pmtiles::RangeProvider,
+};
+use flatbuffer::Table;
+use index::{NODE_SIZE, Node};
+
+mod flatbuffer;
+mod index;
+
+/// layer name of all tiles read from FlatGeobuf
+pub const SOURCE_LAYER: &str = "flatgeobuf";
+
+const MAGIC: &[u8; 3] = b"fgb";
+
+const VERSION: u8 = 3;
+
+/// magic bytes followed by the header size
+const PREFIX_SIZE: u64 = 12;
+
+const MAX_HEADER_SIZE: u64 = 10 << 20;
+
+const MAX_FEATURE_SIZE: u64 = 64 << 20;
+
+/// consecutive features are read with one request up to this size
+const MAX_REQUEST_SIZE: u64 = 1 << 20;
+
+/// radius of the sphere of web mercator
+const EARTH_RADIUS: f64 = 6_378_137.0;
+
+/// fields of the header table
+mod header {
+  pub const GEOMETRY_TYPE: usize = 2;
+  pub const COLUMNS: usize = 7;
+  pub const FEATURES_COUNT: usize = 8;
+  pub const INDEX_NODE_SIZE: usize = 9;
+  pub const CRS: usize = 10;
+}
+
+/// fields of the feature and geometry tables
+mod feature {
+  pub const GEOMETRY: usize = 0;
+  pub const PROPERTIES: usize = 1;
+
+  pub const ENDS: usize = 0;
+  pub const XY: usize = 1;
+  pub const TYPE: usize = 6;
+  pub const PARTS: usize = 7;
+}
+
+mod geometry_type {
+  pub const POINT: u8 = 1;
+  pub const LINE_STRING: u8 = 2;
+  pub const POLYGON: u8 = 3;
+  pub const MULTI_POINT: u8 = 4;
+  pub const MULTI_LINE_STRING: u8 = 5;
+  pub const MULTI_POLYGON: u8 = 6;
+  pub const GEOMETRY_COLLECTION: u8 = 7;
+}
+
+mod column_type {
+  pub const BYTE: u8 = 0;
+  pub const UBYTE: u8 = 1;
+  pub const BOOL: u8 = 2;
+  pub const SHORT: u8 = 3;
+  pub const USHORT: u8 = 4;
+  pub const INT: u8 = 5;
+  pub const UINT: u8 = 6;
+  pub const LONG: u8 = 7;
+  pub const ULONG: u8 = 8;
+  pub const FLOAT: u8 = 9;
+  pub const DOUBLE: u8 = 10;
+  pub const STRING: u8 = 11;
+  pub const JSON: u8 = 12;
+  pub const DATE_TIME: u8 = 13;
+  pub const BINARY: u8 = 14;
+}
+
+/// coordinate reference systems the features can be tiled from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Crs {
+  Wgs84,
+  WebMercator,
+}
+
+impl Crs {
+  /// files without crs are treated as EPSG:4326
+  fn new(header: &Table) -> Result<Self, String> {
+    let Some(crs) = header.get_table(header::CRS)? else {
+      return Ok(Crs::Wgs84);
+    };
+    match crs.get_i32(1, 0)? {
+      0 | 4326 => Ok(Crs::Wgs84),
+      3857 | 900913 => Ok(Crs::WebMercator),
+      code => Err(format!("unsupported crs EPSG:{code}")),
+    }
+  }
+
+  /// to web mercator from 0 to 1, north is at 0
+  fn project(self, x: f64, y: f64) -> Coord<f64> {
+    match self {
+      Crs::Wgs84 => geojson::project(x, y),
+      Crs::WebMercator => Coord {
+        x: x / (2.0 * PI * EARTH_RADIUS) + 0.5,
+        y: 0.5 - y / (2.0 * PI * EARTH_RADIUS),
+      },
+    }
+  }
+
+  fn unproject(self, coord: Coord<f64>) -> Coord<f64> {
+    match self {
+      Crs::Wgs84 => {
+        let (lon, lat) = geojson::unproject(coord);
+        Coord { x: lon, y: lat }
+      }
+      Crs::WebMercator => Coord {
+        x: (coord.x - 0.5) * 2.0 * PI * EARTH_RADIUS,
+        y: (0.5 - coord.y) * 2.0 * PI * EARTH_RADIUS,
+      },
+    }
+  }
+}
+
+/// leaf of the index found by a search
+#[derive(Debug, PartialEq)]
+struct Hit {
+  /// position of the feature in the file, used as feature id
+  index: u64,
+
+  /// byte offset in the feature section
+  offset: u64,
+
+  /// offset of the next feature, if it is known from the index
+  end: Option<u64>,
+}
+
+/// FlatGeobuf file with a spatial index, read with range requests through the provider.
+/// only the index nodes and features intersecting a tile are read
+pub struct FlatGeobuf {
+  provider: Rc<dyn RangeProvider>,
+
+  crs: Crs,
+
+  /// type of all geometries, 0 if it is given per feature
+  geometry_type: u8,
+
+  /// name and type of the property columns
+  columns: Vec<(String, u8)>,
+
+  node_size: u16,
+
+  /// node indices of the index levels from the leaves up to the root
+  level_bounds: Vec<Range<u64>>,
+
+  index_offset: u64,
+
+  features_offset: u64,
+
+  /// blocks of inner nodes by their first node index, every search starts with them
+  inner_nodes: RefCell<HashMap<u64, Rc<Vec<Node>>>>,
+}
+
+impl FlatGeobuf {
+  pub async fn new(provider: Rc<dyn RangeProvider>) -> Result<Self, String> {
+    let prefix = provider.get_range(0, PREFIX_SIZE).await?;
+    if prefix.len() < PREFIX_SIZE as usize || &prefix[..3] != MAGIC || &prefix[4..7] != MAGIC {
+      return Err("not a FlatGeobuf file".to_owned());
+    }
+    if prefix[3] != VERSION {
+      return Err(format!("unsupported FlatGeobuf version {}", prefix[3]));
+    }
+    let header_size = u32::from_le_bytes(prefix[8..12].try_into().unwrap()) as u64;
+    if header_size > MAX_HEADER_SIZE {
+      return Err("header too large".to_owned());
+    }
+
+    let bytes = provider.get_range(PREFIX_SIZE, header_size).await?;
+    let header = Table::root(&bytes)?;
+    let columns = header
+      .get_tables(header::COLUMNS)?
+      .into_iter()
+      .map(|column| {
+        Ok((
+          column.get_string(0)?.unwrap_or_default(),
+          column.get_u8(1, 0)?,
+        ))
+      })
+      .collect::<Result<_, String>>()?;
+
+    let features_count = header.get_u64(header::FEATURES_COUNT, 0)?;
+    let node_size = header.get_u16(header::INDEX_NODE_SIZE, 16)?;
+    if node_size < 2 {
+      return Err("FlatGeobuf without spatial index".to_owned());
+    }
+    // the index of more features wouldn't fit into a file
+    if features_count >= 1 << 48 {
+      return Err("invalid feature count".to_owned());
+    }
+    let level_bounds = index::get_level_bounds(features_count, node_size);
+    let num_nodes = level_bounds.first().map_or(0, |leaves| leaves.end);
+
+    let index_offset = PREFIX_SIZE + header_size;
+    Ok(Self {
+      provider,
+      crs: Crs::new(&header)?,
+      geometry_type: header.get_u8(header::GEOMETRY_TYPE, 0)?,
+      columns,
+      node_size,
+      level_bounds,
+      index_offset,
+      features_offset: index_offset + num_nodes * NODE_SIZE,
+      inner_nodes: RefCell::new(HashMap::new()),
+    })
+  }
+
+  /// features intersecting the tile with its buffer, the tile is sliced from the
+  /// returned index
+  pub async fn get_tile_index(&self, z: u32, x: u32, y: u32) -> Result<GeoJsonIndex, String> {
+    let [min_x, min_y, max_x, max_y] = geojson::get_tile_bbox(z, x, y);
+    let min = self.crs.unproject(Coord { x: min_x, y: max_y });
+    let max = self.crs.unproject(Coord { x: max_x, y: min_y });
+    let hits = self.search([min.x, min.y, max.x, max.y]).await?;
+
+    let mut features = Vec::new();
+    let mut first = 0;
+    while first < hits.len() {
+      let start = hits[first].offset;
+      let mut last = first;
+      while last + 1 < hits.len()
+        && hits[last].end == Some(hits[last + 1].offset)
+        && hits[last + 1]
+          .end
+          .is_some_and(|end| end.saturating_sub(start) <= MAX_REQUEST_SIZE)
+      {
+        last += 1;
+      }
+      let end = match hits[last].end {
+        Some(end) => end,
+        None => self.get_feature_end(hits[last].offset).await?,
+      };
+      if end < start || end - start > MAX_REQUEST_SIZE.max(MAX_FEATURE_SIZE) {
+        return Err("invalid feature offset".to_owned());
+      }
+
+      let bytes = self
+        .provider
+        .get_range(self.features_offset + start, end - start)
+        .await?;
+      for hit in &hits[first..=last] {
+        let bytes = get_feature_bytes(&bytes, (hit.offset - start) as usize)?;
+        if let Some((geometry, properties)) = self.parse_feature(bytes)? {
+          features.push((geometry, Some(hit.index), properties));
+        }
+      }
+      first = last + 1;
+    }
+
+    Ok(GeoJsonIndex::from_features(features))
+  }
+
+  /// leaves intersecting the bbox in the crs of the file, ordered by their offset
+  async fn search(&self, bbox: [f64; 4]) -> Result<Vec<Hit>, String> {
+    let Some(root_level) = self.level_bounds.len().checked_sub(1) else {
+      return Ok(Vec::new());
+    };
+    let leaves_start = self.level_bounds[0].start;
+
+    let mut hits = Vec::new();
+    let mut queue = VecDeque::from([(root_level, 0..1)]);
+    while let Some((level, range)) = queue.pop_front() {
+      let nodes = self.get_nodes(level, range.clone()).await?;
+
+      for (i, node) in nodes.iter().enumerate() {
+        if !node.intersects(&bbox) {
+          continue;
+        }
+        let position = range.start + i as u64;
+        if level == 0 {
+          hits.push(Hit {
+            index: position - leaves_start,
+            offset: node.offset,
+            end: nodes.get(i + 1).map(|next| next.offset),
+          });
+          continue;
+        }
+
+        let children = &self.level_bounds[level - 1];
+        if !children.contains(&node.offset) {
+          return Err("invalid index node".to_owned());
+        }
+        let end = (node.offset + self.node_size as u64).min(children.end);
+        queue.push_back((level - 1, node.offset..end));
+      }
+    }
+
+    hits.sort_by_key(|hit| hit.offset);
+    Ok(hits)
+  }
+
+  async fn get_nodes(&self, level: usize, range: Range<u64>) -> Result<Rc<Vec<Node>>, String> {
+    if level > 0
+      && let Some(nodes) = self.inner_nodes.borrow().get(&range.start)
+    {
+      return Ok(nodes.clone());
+    }
+
+    let length = (range.end - range.start) * NODE_SIZE;
+    let bytes = self
+      .provider
+      .get_range(self.index_offset + range.start * NODE_SIZE, length)
+      .await?;
+    if bytes.len() as u64 != length {
+      return Err("unexpected end of index".to_owned());
+    }
+    let nodes = Rc::new(
+      bytes
+        .chunks_exact(NODE_SIZE as usize)
+        .map(Node::parse)
+        .collect::<Vec<_>>(),
+    );
+
+    if level > 0 {
+      self
+        .inner_nodes
+        .borrow_mut()
+        .insert(range.start, nodes.clone());
+    }
+    Ok(nodes)
+  }
+
+  /// the last feature of a search is read with its size prefix
+  async fn get_feature_end(&self, offset: u64) -> Result<u64, String> {
+    let bytes = self
+      .provider
+      .get_range(self.features_offset + offset, 4)
+      .await?;
+    let size: [u8; 4] = bytes
+      .try_into()
+      .map_err(|_| "unexpected end of file".to_owned())?;
+    let size = u32::from_le_bytes(size) as u64;
+    if size > MAX_FEATURE_SIZE {
+      return Err("feature too large".to_owned());
+    }
+    Ok(offset + 4 + size)
+  }
+
+  /// none for features without geometry or with unsupported geometry types
+  #[allow(clippy::type_complexity)]
+  fn parse_feature(
+    &self,
+    bytes: &[u8],
+  ) -> Result<Option<(Geometry<f64>, HashMap<String, Value>)>, String> {
+    let feature = Table::root(bytes)?;
+    let Some(geometry) = feature.get_table(feature::GEOMETRY)? else {
+      return Ok(None);
+    };
+    let Some(geometry) = parse_geometry(&geometry, self.geometry_type, self.crs)? else {
+      return Ok(None);
+    };
+    let properties = parse_properties(feature.get_bytes(feature::PROPERTIES)?, &self.columns)?;
+    Ok(Some((geometry, properties)))
+  }
+}
+
+/// feature table after its size prefix
+fn get_feature_bytes(bytes: &[u8], position: usize) -> Result<&[u8], String> {
+  let invalid = || "invalid feature size".to_owned();
+  let size = bytes.get(position..position + 4).ok_or_else(invalid)?;
+  let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+  bytes
+    .get(position + 4..position + 4 + size)
+    .ok_or_else(invalid)
+}
+
+/// the geometry type of the header is used if the geometry has none
+fn parse_geometry(
+  geometry: &Table,
+  default_type: u8,
+  crs: Crs,
+) -> Result<Option<Geometry<f64>>, String> {
+  let coords: Vec<Coord<f64>> = geometry
+    .get_f64s(feature::XY)?
+    .chunks_exact(2)
+    .map(|xy| crs.project(xy[0], xy[1]))
+    .collect();
+  let ends = geometry.get_u32s(feature::ENDS)?;
+  let parts = |geometry_type: u8| -> Result<Vec<Geometry<f64>>, String> {
+    let mut geometries = Vec::new();
+    for part in geometry.get_tables(feature::PARTS)? {
+      geometries.extend(parse_geometry(&part, geometry_type, crs)?);
+    }
+    Ok(geometries)
+  };
+
+  let geometry_type = match geometry.get_u8(feature::TYPE, 0)? {
+    0 => default_type,
+    geometry_type => geometry_type,
+  };
+  Ok(Some(match geometry_type {
+    geometry_type::POINT => {
+      Geometry::Point(Point(*coords.first().ok_or("point without coordinates")?))
+    }
+    geometry_type::MULTI_POINT => {
+      Geometry::MultiPoint(MultiPoint(coords.into_iter().map(Point).collect()))
+    }
+    geometry_type::LINE_STRING => Geometry::LineString(LineString(coords)),
+    geometry_type::MULTI_LINE_STRING => {
+      Geometry::MultiLineString(MultiLineString(split_lines(coords, &ends)?))
+    }
+    geometry_type::POLYGON => {
+      let mut rings = split_lines(coords, &ends)?.into_iter();
+      let exterior = rings.next().unwrap_or(LineString(vec![]));
+      Geometry::Polygon(Polygon::new(exterior, rings.collect()))
+    }
+    geometry_type::MULTI_POLYGON => Geometry::MultiPolygon(MultiPolygon(
+      parts(geometry_type::POLYGON)?
+        .into_iter()
+        .filter_map(|part| match part {
+          Geometry::Polygon(polygon) => Some(polygon),
+          _ => None,
+        })
+        .collect(),
+    )),
+    geometry_type::GEOMETRY_COLLECTION => {
+      Geometry::GeometryCollection(GeometryCollection(parts(0)?))
+    }
+    _ => return Ok(None),
+  }))
+}
+
+/// ends are the exclusive vertex index of every part, a single part has none
+fn split_lines(coords: Vec<Coord<f64>>, ends: &[u32]) -> Result<Vec<LineString<f64>>, String> {
+  if ends.is_empty() {
+    return Ok(vec![LineString(coords)]);
+  }
+  let mut start = 0;
+  ends
+    .iter()
+    .map(|end| {
+      let end = *end as usize;
+      let line = coords.get(start..end).ok_or("invalid geometry ends")?;
+      start = end;
+      Ok(LineString(line.to_vec()))
+    })
+    .collect()
+}
+
+fn take<'a>(bytes: &'a [u8], position: &mut usize, length: usize) -> Result<&'a [u8], String> {
+  let value = position
+    .checked_add(length)
+    .and_then(|end| bytes.get(*position..end))
+    .ok_or("invalid properties")?;
+  *position += length;
+  Ok(value)
+}
+
+fn take_array<const N: usize>(bytes: &[u8], position: &mut usize) -> Result<[u8; N], String> {
+  Ok(take(bytes, position, N)?.try_into().unwrap())
+}
+
+/// column index followed by the value for every set property, binary values are dropped
+fn parse_properties(
+  bytes: &[u8],
+  columns: &[(String, u8)],
+) -> Result<HashMap<String, Value>, String> {
+  let mut properties = HashMap::new();
+  let mut position = 0;
+
+  while position < bytes.len() {
+    let column = u16::from_le_bytes(take_array(bytes, &mut position)?) as usize;
+    let (name, column_type) = columns.get(column).ok_or("invalid column index")?;
+    let position = &mut position;
+
+    let value = match *column_type {
+      column_type::BYTE => Value::SInt(i8::from_le_bytes(take_array(bytes, position)?) as i64),
+      column_type::UBYTE => Value::UInt(u8::from_le_bytes(take_array(bytes, position)?) as u64),
+      column_type::BOOL => Value::Bool(take_array::<1>(bytes, position)?[0] != 0),
+      column_type::SHORT => Value::SInt(i16::from_le_bytes(take_array(bytes, position)?) as i64),
+      column_type::USHORT => Value::UInt(u16::from_le_bytes(take_array(bytes, position)?) as u64),
+      column_type::INT => Value::SInt(i32::from_le_bytes(take_array(bytes, position)?) as i64),
+      column_type::UINT => Value::UInt(u32::from_le_bytes(take_array(bytes, position)?) as u64),
+      column_type::LONG => Value::SInt(i64::from_le_bytes(take_array(bytes, position)?)),
+      column_type::ULONG => Value::UInt(u64::from_le_bytes(take_array(bytes, position)?)),
+      column_type::FLOAT => Value::Float(f32::from_le_bytes(take_array(bytes, position)?)),
+      column_type::DOUBLE => Value::Double(f64::from_le_bytes(take_array(bytes, position)?)),
+      column_type::STRING | column_type::JSON | column_type::DATE_TIME | column_type::BINARY => {
+        let length = u32::from_le_bytes(take_array(bytes, position)?) as usize;
+        let value = take(bytes, position, length)?;
+        if *column_type == column_type::BINARY {
+          continue;
+        }
+        Value::String(String::from_utf8(value.to_vec()).map_err(|err| err.to_string())?)
+      }
+      column_type => return Err(format!("unsupported column type {column_type}")),
+    };
+    properties.insert(name.clone(), value);
+  }
+  Ok(properties)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  enum Field {
+    U8(u8),
+    U16(u16),
+    U64(u64),
+    Bytes(Vec<u8>),
+    F64s(Vec<f64>),
+    String(&'static str),
+    Table(Vec<Option<Field>>),
+    Tables(Vec<Vec<Option<Field>>>),
+  }
+
+  fn patch_reference(bytes: &mut [u8], position: usize, target: usize) {
+    bytes[position..position + 4].copy_from_slice(&((target - position) as u32).to_le_bytes());
+  }
+
+  /// vtable followed by the table and its children, so all offsets point forward
+  fn write_table(bytes: &mut Vec<u8>, fields: &[Option<Field>]) -> usize {
+    let sizes: Vec<usize> = fields
+      .iter()
+      .map(|field| match field {
+        None => 0,
+        Some(Field::U8(_)) => 1,
+        Some(Field::U16(_)) => 2,
+        Some(Field::U64(_)) => 8,
+        Some(_) => 4,
+      })
+      .collect();
+
+    let vtable = bytes.len();
+    bytes.extend((4 + 2 * fields.len() as u16).to_le_bytes());
+    bytes.extend((4 + sizes.iter().sum::<usize>() as u16).to_le_bytes());
+    let mut offset = 4;
+    for size in sizes {
+      bytes.extend((if size == 0 { 0 } else { offset as u16 }).to_le_bytes());
+      offset += size;
+    }
+
+    let table = bytes.len();
+    bytes.extend(((table - vtable) as i32).to_le_bytes());
+    let mut references = Vec::new();
+    for field in fields.iter().flatten() {
+      match field {
+        Field::U8(value) => bytes.push(*value),
+        Field::U16(value) => bytes.extend(value.to_le_bytes()),
+        Field::U64(value) => bytes.extend(value.to_le_bytes()),
+        reference => {
+          references.push((bytes.len(), reference));
+          bytes.extend([0; 4]);
+        }
+      }
+    }
+    for (position, field) in references {
+      let target = write_child(bytes, field);
+      patch_reference(bytes, position, target);
+    }
+    table
+  }
+
+  fn write_child(bytes: &mut Vec<u8>, field: &Field) -> usize {
+    let start = bytes.len();
+    match field {
+      Field::Bytes(values) => {
+        bytes.extend((values.len() as u32).to_le_bytes());
+        bytes.extend(values);
+      }
+      Field::F64s(values) => {
+        bytes.extend((values.len() as u32).to_le_bytes());
+        values
+          .iter()
+          .for_each(|value| bytes.extend(value.to_le_bytes()));
+      }
+      Field::String(value) => {
+        bytes.extend((value.len() as u32).to_le_bytes());
+        bytes.extend(value.as_bytes());
+        bytes.push(0);
+      }
+      Field::Table(fields) => return write_table(bytes, fields),
+      Field::Tables(tables) => {
+        bytes.extend((tables.len() as u32).to_le_bytes());
+        let references = bytes.len();
+        bytes.extend(vec![0; tables.len() * 4]);
+        for (i, fields) in tables.iter().enumerate() {
+          let table = write_table(bytes, fields);
+          patch_reference(bytes, references + i * 4, table);
+        }
+      }
+      _ => unreachable!(),
+    }
+    start
+  }
+
+  fn write_root(fields: Vec<Option<Field>>) -> Vec<u8> {
+    let mut bytes = vec![0; 4];
+    let table = write_table(&mut bytes, &fields);
+    patch_reference(&mut bytes, 0, table);
+    bytes
+  }
+
+  fn feature(geometry_type: u8, xy: Vec<f64>, properties: Vec<u8>) -> Vec<u8> {
+    let geometry = vec![
+      None,
+      Some(Field::F64s(xy)),
+      None,
+      None,
+      None,
+      None,
+      Some(Field::U8(geometry_type)),
+    ];
+    let mut bytes = write_root(vec![
+      Some(Field::Table(geometry)),
+      Some(Field::Bytes(properties)),
+    ]);
+    let mut feature = (bytes.len() as u32).to_le_bytes().to_vec();
+    feature.append(&mut bytes);
+    feature
+  }
+
+  fn node(bbox: [f64; 4], offset: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = bbox.iter().flat_map(|value| value.to_le_bytes()).collect();
+    bytes.extend(offset.to_le_bytes());
+    bytes
+  }
+
+  /// a point and a polygon in the north east and a line in the south west, indexed
+  /// with a node size of 2
+  fn create_file() -> Vec<u8> {
+    let mut name = 0u16.to_le_bytes().to_vec();
+    name.extend(1u32.to_le_bytes());
+    name.push(b'a');
+    name.extend(1u16.to_le_bytes());
+    name.extend(3i32.to_le_bytes());
+
+    let polygon = vec![10.0, 10.0, 20.0, 10.0, 20.0, 20.0, 10.0, 20.0, 10.0, 10.0];
+    let features = [
+      feature(geometry_type::POINT, vec![10.0, 10.0], name),
+      feature(
+        geometry_type::LINE_STRING,
+        vec![-100.0, -40.0, -90.0, -40.0],
+        vec![],
+      ),
+      feature(geometry_type::POLYGON, polygon, vec![]),
+    ];
+    let offsets = [
+      0,
+      features[0].len() as u64,
+      (features[0].len() + features[1].len()) as u64,
+    ];
+
+    let header = write_root(vec![
+      Some(Field::String("test")),
+      None,
+      Some(Field::U8(0)),
+      None,
+      None,
+      None,
+      None,
+      Some(Field::Tables(vec![
+        vec![
+          Some(Field::String("name")),
+          Some(Field::U8(column_type::STRING)),
+        ],
+        vec![
+          Some(Field::String("count")),
+          Some(Field::U8(column_type::INT)),
+        ],
+      ])),
+      Some(Field::U64(3)),
+      Some(Field::U16(2)),
+    ]);
+
+    let mut file = b"fgb\x03fgb\x00".to_vec();
+    file.extend((header.len() as u32).to_le_bytes());
+    file.extend(header);
+    // root, inner level, leaves
+    file.extend(node([-100.0, -40.0, 20.0, 20.0], 1));
+    file.extend(node([-100.0, -40.0, 10.0, 10.0], 3));
+    file.extend(node([10.0, 10.0, 20.0, 20.0], 5));
+    file.extend(node([10.0, 10.0, 10.0, 10.0], offsets[0]));
+    file.extend(node([-100.0, -40.0, -90.0, -40.0], offsets[1]));
+    file.extend(node([10.0, 10.0, 20.0, 20.0], offsets[2]));
+    for feature in features {
+      file.extend(feature);
+    }
+    file
+  }
+
+  #[test]
+  fn level_bounds() {
+    assert_eq!(index::get_level_bounds(3, 2), vec![3..6, 1..3, 0..1]);
+    assert_eq!(index::get_level_bounds(1, 16), vec![1..2, 0..1]);
+    assert!(index::get_level_bounds(0, 16).is_empty());
+  }
+
+  #[test]
+  fn read_tile_features() {
+    let provider: Rc<dyn RangeProvider> = Rc::new(create_file());
+    let file = futures::executor::block_on(FlatGeobuf::new(provider)).unwrap();
+    let get_tile = |z, x, y| {
+      futures::executor::block_on(file.get_tile_index(z, x, y))
+        .unwrap()
+        .get_tile(z, x, y)
+    };
+
+    let groups = get_tile(1, 1, 0);
+    assert_eq!(groups.len(), 2);
+    let (fills, points) = (&groups[0], &groups[1]);
+    assert!(matches!(fills[0].geometry, Geometry::Polygon(_)));
+    assert_eq!(fills[0].id, Some(2));
+    assert_eq!(points[0].id, Some(0));
+    let properties = points[0].properties.as_ref().unwrap();
+    assert_eq!(properties.get("name"), Some(&Value::String("a".to_owned())));
+    assert_eq!(properties.get("count"), Some(&Value::SInt(3)));
+
+    let groups = get_tile(1, 0, 1);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0][0].id, Some(1));
+    assert!(matches!(groups[0][0].geometry, Geometry::LineString(_)));
+
+    assert!(get_tile(1, 0, 0).is_empty());
+  }
+
+  #[test]
+  fn search_skips_other_features() {
+    let provider: Rc<dyn RangeProvider> = Rc::new(create_file());
+    let file = futures::executor::block_on(FlatGeobuf::new(provider)).unwrap();
+    let hits = futures::executor::block_on(file.search([0.0, 0.0, 30.0, 30.0])).unwrap();
+    let indices: Vec<_> = hits.iter().map(|hit| hit.index).collect();
+    assert_eq!(indices, [0, 2]);
+  }
+}
@@ -0,0 +1,162 @@
+use geo_types::{Coord, Geometry, LineString, MultiLineString, MultiPoint, MultiPolygon, Polygon};
+
+#[derive(Clone, Copy)]
+enum Axis {
+  X,
+  Y,
+}
+
+impl Axis {
+  fn get(&self, coord: &Coord<f64>) -> f64 {
+    match self {
+      Axis::X => coord.x,
+      Axis::Y => coord.y,
+    }
+  }
+
+  /// point of the segment a b at k on this axis
+  fn intersect(&self, a: &Coord<f64>, b: &Coord<f64>, k: f64) -> Coord<f64> {
+    let t = (k - self.get(a)) / (self.get(b) - self.get(a));
+    match self {
+      Axis::X => Coord {
+        x: k,
+        y: a.y + (b.y - a.y) * t,
+      },
+      Axis::Y => Coord {
+        x: a.x + (b.x - a.x) * t,
+        y: k,
+      },
+    }
+  }
+}
+
+/// geometry inside of the bbox [min_x, min_y, max_x, max_y], none if nothing is left.
+/// lines are split where they leave the bbox, polygon rings follow its border
+pub fn clip(geometry: &Geometry<f64>, bbox: [f64; 4]) -> Option<Geometry<f64>> {
+  let geometry = clip_axis(geometry, bbox[0], bbox[2], Axis::X)?;
+  clip_axis(&geometry, bbox[1], bbox[3], Axis::Y)
+}
+
+fn clip_axis(geometry: &Geometry<f64>, k1: f64, k2: f64, axis: Axis) -> Option<Geometry<f64>> {
+  let inside = |coord: &Coord<f64>| (k1..=k2).contains(&axis.get(coord));
+
+  match geometry {
+    Geometry::Point(point) => inside(&point.0).then_some(Geometry::Point(*point)),
+    Geometry::MultiPoint(multi_point) => {
+      let points: Vec<_> = multi_point
+        .iter()
+        .filter(|point| inside(&point.0))
+        .copied()
+        .collect();
+      (!points.is_empty()).then_some(Geometry::MultiPoint(MultiPoint(points)))
+    }
+    Geometry::LineString(line) => to_line_geometry(clip_line(&line.0, k1, k2, axis, false)),
+    Geometry::MultiLineString(multi_line) => to_line_geometry(
+      multi_line
+        .iter()
+        .flat_map(|line| clip_line(&line.0, k1, k2, axis, false))
+        .collect(),
+    ),
+    Geometry::Polygon(polygon) => clip_polygon(polygon, k1, k2, axis).map(Geometry::Polygon),
+    Geometry::MultiPolygon(multi_polygon) => {
+      let polygons: Vec<_> = multi_polygon
+        .iter()
+        .filter_map(|polygon| clip_polygon(polygon, k1, k2, axis))
+        .collect();
+      (!polygons.is_empty()).then_some(Geometry::MultiPolygon(MultiPolygon(polygons)))
+    }
+    _ => None,
+  }
+}
+
+fn to_line_geometry(mut lines: Vec<LineString<f64>>) -> Option<Geometry<f64>> {
+  match lines.len() {
+    0 => None,
+    1 => lines.pop().map(Geometry::LineString),
+    _ => Some(Geometry::MultiLineString(MultiLineString(lines))),
+  }
+}
+
+/// rings with less than 3 corners are dropped, a polygon without exterior ring too
+fn clip_polygon(polygon: &Polygon<f64>, k1: f64, k2: f64, axis: Axis) -> Option<Polygon<f64>> {
+  let mut rings = std::iter::once(polygon.exterior())
+    .chain(polygon.interiors())
+    .map(|ring| clip_line(&ring.0, k1, k2, axis, true).pop())
+    .map(|ring| ring.filter(|ring| ring.0.len() >= 4));
+
+  let exterior = rings.next().flatten()?;
+  Some(Polygon::new(exterior, rings.flatten().collect()))
+}
+
+/// part of the line between k1 and k2 on the axis, like the clipping of geojson-vt. a
+/// ring is never split and closed again afterwards
+fn clip_line(
+  coords: &[Coord<f64>],
+  k1: f64,
+  k2: f64,
+  axis: Axis,
+  is_ring: bool,
+) -> Vec<LineString<f64>> {
+  let mut lines = Vec::new();
+  let mut slice = Vec::new();
+
+  for segment in coords.windows(2) {
+    let (a, b) = (&segment[0], &segment[1]);
+    let (ak, bk) = (axis.get(a), axis.get(b));
+    let mut exited = false;
+
+    if ak < k1 {
+      // ---|-->  |
+      if bk > k1 {
+        slice.push(axis.intersect(a, b, k1));
+        // ---|-----|-->
+        if bk > k2 {
+          slice.push(axis.intersect(a, b, k2));
+          exited = true;
+        }
+      }
+    } else if ak > k2 {
+      // |  <--|---
+      if bk < k2 {
+        slice.push(axis.intersect(a, b, k2));
+        // <--|-----|---
+        if bk < k1 {
+          slice.push(axis.intersect(a, b, k1));
+          exited = true;
+        }
+      }
+    } else {
+      slice.push(*a);
+      // <--|---  |
+      if bk < k1 {
+        slice.push(axis.intersect(a, b, k1));
+        exited = true;
+      // |  ---|-->
+      } else if bk > k2 {
+        slice.push(axis.intersect(a, b, k2));
+        exited = true;
+      }
+    }
+
+    if !is_ring && exited {
+      lines.push(LineString(std::mem::take(&mut slice)));
+    }
+  }
+
+  if let Some(last) = coords.last() {
+    let k = axis.get(last);
+    if k >= k1 && k <= k2 {
+      slice.push(*last);
+    }
+  }
+
+  if is_ring && slice.first() != slice.last() {
+    slice.push(slice[0]);
+  }
+
+  if !slice.is_empty() {
+    lines.push(LineString(slice));
+  }
+  lines.retain(|line| line.0.len() >= 2);
+  lines
+}
@@ -0,0 +1,514 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use geo_types::{
+  Coord, Geometry, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon,
+  Point, Polygon,
+};
+use mvt_reader::feature::{Feature, Value};
+
+mod clip;
+mod simplify;
+
+/// layer name of all tiles sliced from GeoJSON
+pub const SOURCE_LAYER: &str = "geojson";
+
+/// tile coordinates range from 0 to the extent, like in most vector tiles
+const EXTENT: f64 = 4096.0;
+
+/// geometry around a tile is kept up to this distance in tile coordinates, so lines
+/// and polygons don't end visibly at the tile border
+const BUFFER: f64 = 64.0;
+
+/// simplification tolerance in tile coordinates
+const TOLERANCE: f64 = 3.0;
+
+/// feature with its geometry projected to web mercator from 0 to 1, north is at 0
+struct IndexFeature {
+  geometry: Geometry<f64>,
+
+  /// [min_x, min_y, max_x, max_y] of the geometry
+  bbox: [f64; 4],
+
+  id: Option<u64>,
+
+  properties: HashMap<String, Value>,
+}
+
+/// GeoJSON in EPSG:4326, sliced into tiles of the web mercator tile grid on demand like
+/// geojson-vt does. features crossing the antimeridian with longitudes beyond ±180 are
+/// wrapped into the tiles on the other side of the world
+pub struct GeoJsonIndex {
+  features: Vec<IndexFeature>,
+}
+
+impl GeoJsonIndex {
+  /// accepts a FeatureCollection, a single Feature or a bare geometry
+  pub fn new(geojson: &str) -> Result<Self, String> {
+    let value: serde_json::Value = serde_json::from_str(geojson).map_err(|err| err.to_string())?;
+    let mut features = Vec::new();
+    add_object(&value, &mut features)?;
+    Ok(Self { features })
+  }
+
+  /// features with their geometry already projected to web mercator from 0 to 1, see
+  /// `project`
+  pub fn from_features(
+    features: impl IntoIterator<Item = (Geometry<f64>, Option<u64>, HashMap<String, Value>)>,
+  ) -> Self {
+    let mut index_features = Vec::new();
+    for (geometry, id, properties) in features {
+      push_geometry(geometry, id, &properties, &mut index_features);
+    }
+    Self {
+      features: index_features,
+    }
+  }
+
+  /// features of the tile in tile coordinates, clipped with a buffer and simplified.
+  /// features are grouped by geometry type so every group can be built into one bucket,
+  /// empty groups are left out
+  pub fn get_tile(&self, z: u32, x: u32, y: u32) -> Vec<Vec<Feature>> {
+    let z2 = 2f64.powi(z as i32);
+    let tile_bbox = get_tile_bbox(z, x, y);
+    let tolerance = TOLERANCE / EXTENT / z2;
+
+    let mut fills = Vec::new();
+    let mut lines = Vec::new();
+    let mut points = Vec::new();
+    for feature in self.features.iter() {
+      // the tile is looked up in the worlds west and east of it as well, so parts beyond
+      // the antimeridian are wrapped
+      for offset in [-1.0, 0.0, 1.0] {
+        let bbox = [
+          tile_bbox[0] + offset,
+          tile_bbox[1],
+          tile_bbox[2] + offset,
+          tile_bbox[3],
+        ];
+        if feature.bbox[0] > bbox[2]
+          || feature.bbox[2] < bbox[0]
+          || feature.bbox[1] > bbox[3]
+          || feature.bbox[3] < bbox[1]
+        {
+          continue;
+        }
+        let Some(geometry) = clip::clip(&feature.geometry, bbox)
+          .and_then(|geometry| simplify::simplify(geometry, tolerance))
+        else {
+          continue;
+        };
+
+        let group = match geometry {
+          Geometry::Point(_) | Geometry::MultiPoint(_) => &mut points,
+          Geometry::LineString(_) | Geometry::MultiLineString(_) => &mut lines,
+          _ => &mut fills,
+        };
+        group.push(Feature {
+          geometry: to_tile_coordinates(&geometry, |coord| Coord {
+            x: (((coord.x - offset) * z2 - x as f64) * EXTENT) as f32,
+            y: ((coord.y * z2 - y as f64) * EXTENT) as f32,
+          }),
+          id: feature.id,
+          properties: Some(feature.properties.clone()),
+        });
+      }
+    }
+
+    [fills, lines, points]
+      .into_iter()
+      .filter(|group| !group.is_empty())
+      .collect()
+  }
+}
+
+/// [min_x, min_y, max_x, max_y] of the tile with its buffer in web mercator from 0 to 1
+pub fn get_tile_bbox(z: u32, x: u32, y: u32) -> [f64; 4] {
+  let z2 = 2f64.powi(z as i32);
+  let buffer = BUFFER / EXTENT;
+  [
+    (x as f64 - buffer) / z2,
+    (y as f64 - buffer) / z2,
+    (x as f64 + 1.0 + buffer) / z2,
+    (y as f64 + 1.0 + buffer) / z2,
+  ]
+}
+
+fn add_object(value: &serde_json::Value, features: &mut Vec<IndexFeature>) -> Result<(), String> {
+  match value["type"].as_str() {
+    Some("FeatureCollection") => {
+      for feature in value["features"]
+        .as_array()
+        .ok_or("FeatureCollection without features")?
+      {
+        add_object(feature, features)?;
+      }
+    }
+    Some("Feature") => {
+      // features without geometry are valid, but there is nothing to draw
+      if value["geometry"].is_null() {
+        return Ok(());
+      }
+      let properties = value["properties"]
+        .as_object()
+        .map(|properties| {
+          properties
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), to_value(value)?)))
+            .collect()
+        })
+        .unwrap_or_default();
+      add_geometry(
+        &value["geometry"],
+        value["id"].as_u64(),
+        &properties,
+        features,
+      )?;
+    }
+    _ => add_geometry(value, None, &HashMap::new(), features)?,
+  }
+  Ok(())
+}
+
+/// geometry collections are split into one feature per geometry, like geojson-vt does
+fn add_geometry(
+  value: &serde_json::Value,
+  id: Option<u64>,
+  properties: &HashMap<String, Value>,
+  features: &mut Vec<IndexFeature>,
+) -> Result<(), String> {
+  push_geometry(parse_geometry(value)?, id, properties, features);
+  Ok(())
+}
+
+fn push_geometry(
+  geometry: Geometry<f64>,
+  id: Option<u64>,
+  properties: &HashMap<String, Value>,
+  features: &mut Vec<IndexFeature>,
+) {
+  let geometries = match geometry {
+    Geometry::GeometryCollection(GeometryCollection(geometries)) => geometries,
+    geometry => vec![geometry],
+  };
+
+  for geometry in geometries {
+    let Some(bbox) = get_bbox(&geometry) else {
+      continue;
+    };
+    features.push(IndexFeature {
+      geometry,
+      bbox,
+      id,
+      properties: properties.clone(),
+    });
+  }
+}
+
+fn parse_geometry(value: &serde_json::Value) -> Result<Geometry<f64>, String> {
+  let coordinates = &value["coordinates"];
+  let geometry = match value["type"].as_str() {
+    Some("Point") => Geometry::Point(Point(project_position(coordinates)?)),
+    Some("MultiPoint") => Geometry::MultiPoint(MultiPoint(
+      parse_coords(coordinates)?.into_iter().map(Point).collect(),
+    )),
+    Some("LineString") => Geometry::LineString(LineString(parse_coords(coordinates)?)),
+    Some("MultiLineString") => {
+      Geometry::MultiLineString(MultiLineString(parse_lines(coordinates)?))
+    }
+    // an invalid polygon is an empty collection, so nothing is indexed
+    Some("Polygon") => match parse_polygon(coordinates)? {
+      Some(polygon) => Geometry::Polygon(polygon),
+      None => Geometry::GeometryCollection(GeometryCollection(vec![])),
+    },
+    Some("MultiPolygon") => {
+      let mut polygons = Vec::new();
+      for polygon in get_array(coordinates)? {
+        polygons.extend(parse_polygon(polygon)?);
+      }
+      Geometry::MultiPolygon(MultiPolygon(polygons))
+    }
+    Some("GeometryCollection") => {
+      let mut geometries = Vec::new();
+      for geometry in get_array(&value["geometries"])? {
+        match parse_geometry(geometry)? {
+          Geometry::GeometryCollection(GeometryCollection(nested)) => geometries.extend(nested),
+          geometry => geometries.push(geometry),
+        }
+      }
+      Geometry::GeometryCollection(GeometryCollection(geometries))
+    }
+    Some(geometry_type) => return Err(format!("unknown geometry type {geometry_type}")),
+    None => return Err("object without type".to_owned()),
+  };
+  Ok(geometry)
+}
+
+fn get_array(value: &serde_json::Value) -> Result<&Vec<serde_json::Value>, String> {
+  value
+    .as_array()
+    .ok_or_else(|| format!("expected array, got {value}"))
+}
+
+fn parse_coords(value: &serde_json::Value) -> Result<Vec<Coord<f64>>, String> {
+  get_array(value)?.iter().map(project_position).collect()
+}
+
+fn parse_lines(value: &serde_json::Value) -> Result<Vec<LineString<f64>>, String> {
+  get_array(value)?
+    .iter()
+    .map(|line| Ok(LineString(parse_coords(line)?)))
+    .collect()
+}
+
+/// rings with less than 4 positions are invalid. invalid holes are ignored, none if the
+/// exterior ring is invalid
+fn parse_polygon(value: &serde_json::Value) -> Result<Option<Polygon<f64>>, String> {
+  let mut rings = parse_lines(value)?.into_iter();
+  let Some(exterior) = rings.next().filter(|ring| ring.0.len() >= 4) else {
+    return Ok(None);
+  };
+  let interiors = rings.filter(|ring| ring.0.len() >= 4).collect();
+  Ok(Some(Polygon::new(exterior, interiors)))
+}
+
+fn project_position(position: &serde_json::Value) -> Result<Coord<f64>, String> {
+  let (Some(lon), Some(lat)) = (position[0].as_f64(), position[1].as_f64()) else {
+    return Err(format!("invalid position {position}"));
+  };
+  Ok(project(lon, lat))
+}
+
+/// longitude and latitude to web mercator from 0 to 1, the poles are clamped
+pub fn project(lon: f64, lat: f64) -> Coord<f64> {
+  let sin = lat.to_radians().sin();
+  let y = 0.5 - 0.25 * ((1.0 + sin) / (1.0 - sin)).ln() / PI;
+  Coord {
+    x: lon / 360.0 + 0.5,
+    y: y.clamp(0.0, 1.0),
+  }
+}
+
+/// web mercator from 0 to 1 to longitude and latitude
+pub fn unproject(coord: Coord<f64>) -> (f64, f64) {
+  let lat = (PI * (1.0 - 2.0 * coord.y)).sinh().atan().to_degrees();
+  ((coord.x - 0.5) * 360.0, lat)
+}
+
+/// none for empty geometries
+fn get_bbox(geometry: &Geometry<f64>) -> Option<[f64; 4]> {
+  let coords: Vec<&Coord<f64>> = match geometry {
+    Geometry::Point(point) => vec![&point.0],
+    Geometry::MultiPoint(multi_point) => multi_point.iter().map(|point| &point.0).collect(),
+    Geometry::LineString(line) => line.0.iter().collect(),
+    Geometry::MultiLineString(multi_line) => multi_line.iter().flat_map(|line| &line.0).collect(),
+    Geometry::Polygon(polygon) => polygon.exterior().0.iter().collect(),
+    Geometry::MultiPolygon(multi_polygon) => multi_polygon
+      .iter()
+      .flat_map(|polygon| &polygon.exterior().0)
+      .collect(),
+    _ => vec![],
+  };
+  coords.into_iter().fold(None, |bbox, coord| {
+    let [min_x, min_y, max_x, max_y] = bbox.unwrap_or([
+      f64::INFINITY,
+      f64::INFINITY,
+      f64::NEG_INFINITY,
+      f64::NEG_INFINITY,
+    ]);
+    Some([
+      min_x.min(coord.x),
+      min_y.min(coord.y),
+      max_x.max(coord.x),
+      max_y.max(coord.y),
+    ])
+  })
+}
+
+/// only the types returned by clipping are converted
+fn to_tile_coordinates(
+  geometry: &Geometry<f64>,
+  transform: impl Fn(&Coord<f64>) -> Coord<f32>,
+) -> Geometry<f32> {
+  let line = |line: &LineString<f64>| LineString(line.0.iter().map(&transform).collect());
+  let polygon = |polygon: &Polygon<f64>| {
+    Polygon::new(
+      line(polygon.exterior()),
+      polygon.interiors().iter().map(line).collect(),
+    )
+  };
+
+  match geometry {
+    Geometry::Point(point) => Geometry::Point(Point(transform(&point.0))),
+    Geometry::MultiPoint(multi_point) => Geometry::MultiPoint(MultiPoint(
+      multi_point
+        .iter()
+        .map(|point| Point(transform(&point.0)))
+        .collect(),
+    )),
+    Geometry::LineString(line_string) => Geometry::LineString(line(line_string)),
+    Geometry::MultiLineString(multi_line) => {
+      Geometry::MultiLineString(MultiLineString(multi_line.iter().map(line).collect()))
+    }
+    Geometry::Polygon(p) => Geometry::Polygon(polygon(p)),
+    Geometry::MultiPolygon(multi_polygon) => {
+      Geometry::MultiPolygon(MultiPolygon(multi_polygon.iter().map(polygon).collect()))
+    }
+    _ => Geometry::GeometryCollection(GeometryCollection(vec![])),
+  }
+}
+
+/// nested objects and arrays are kept as their JSON text, null properties are dropped
+fn to_value(value: &serde_json::Value) -> Option<Value> {
+  match value {
+    serde_json::Value::Null => None,
+    serde_json::Value::Bool(value) => Some(Value::Bool(*value)),
+    serde_json::Value::Number(number) => Some(if let Some(value) = number.as_u64() {
+      Value::UInt(value)
+    } else if let Some(value) = number.as_i64() {
+      Value::SInt(value)
+    } else {
+      Value::Double(number.as_f64().unwrap_or_default())
+    }),
+    serde_json::Value::String(value) => Some(Value::String(value.clone())),
+    value => Some(Value::String(value.to_string())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const GEOJSON: &str = r#"{
+    "type": "FeatureCollection",
+    "features": [
+      {
+        "type": "Feature",
+        "id": 1,
+        "properties": { "name": "point" },
+        "geometry": { "type": "Point", "coordinates": [10.0, 50.0] }
+      },
+      {
+        "type": "Feature",
+        "id": 2,
+        "properties": null,
+        "geometry": { "type": "LineString", "coordinates": [[-90.0, 10.0], [90.0, 10.0]] }
+      },
+      {
+        "type": "Feature",
+        "properties": { "area": 1.5 },
+        "geometry": {
+          "type": "Polygon",
+          "coordinates": [[[-10.0, 5.0], [170.0, 5.0], [170.0, 80.0], [-10.0, 80.0], [-10.0, 5.0]]]
+        }
+      },
+      { "type": "Feature", "properties": {}, "geometry": null }
+    ]
+  }"#;
+
+  fn get_coords(geometry: &Geometry<f32>) -> Vec<Coord<f32>> {
+    match geometry {
+      Geometry::Point(point) => vec![point.0],
+      Geometry::LineString(line) => line.0.clone(),
+      Geometry::MultiLineString(multi_line) => {
+        multi_line.iter().flat_map(|line| line.0.clone()).collect()
+      }
+      Geometry::Polygon(polygon) => polygon.exterior().0.clone(),
+      _ => panic!("unexpected geometry {geometry:?}"),
+    }
+  }
+
+  #[test]
+  fn parse_feature_collection() {
+    let index = GeoJsonIndex::new(GEOJSON).unwrap();
+    assert_eq!(index.features.len(), 3);
+    assert_eq!(index.features[0].id, Some(1));
+    assert_eq!(
+      index.features[0].properties.get("name"),
+      Some(&Value::String("point".to_owned()))
+    );
+
+    // the point is north east of the center of the world
+    let bbox = index.features[0].bbox;
+    assert!(bbox[0] > 0.5 && bbox[1] < 0.5);
+
+    assert!(GeoJsonIndex::new(r#"{ "type": "Point", "coordinates": [0.0] }"#).is_err());
+    assert!(GeoJsonIndex::new("[]").is_err());
+  }
+
+  #[test]
+  fn slice_tiles() {
+    let index = GeoJsonIndex::new(GEOJSON).unwrap();
+
+    // world tile contains every geometry type
+    let groups = index.get_tile(0, 0, 0);
+    assert_eq!(groups.len(), 3);
+    assert!(matches!(groups[0][0].geometry, Geometry::Polygon(_)));
+    assert!(matches!(groups[1][0].geometry, Geometry::LineString(_)));
+    assert!(matches!(groups[2][0].geometry, Geometry::Point(_)));
+
+    // north east tile at zoom 1 has the point, the polygon and the eastern half of the line
+    let groups = index.get_tile(1, 1, 0);
+    assert_eq!(groups.len(), 3);
+    let min = (-BUFFER) as f32;
+    let max = (EXTENT + BUFFER) as f32;
+    for feature in groups.iter().flatten() {
+      for coord in get_coords(&feature.geometry) {
+        assert!((min..=max).contains(&coord.x), "{coord:?}");
+        assert!((min..=max).contains(&coord.y), "{coord:?}");
+      }
+    }
+    let line = get_coords(&groups[1][0].geometry);
+    assert_eq!(line.first().unwrap().x, min);
+    assert_eq!(line.last().unwrap().x, 2048.0);
+
+    // the ring is clipped at the tile border and stays closed
+    let ring = get_coords(&groups[0][0].geometry);
+    assert_eq!(ring.first(), ring.last());
+    assert!(ring.iter().any(|coord| coord.x == min));
+
+    // south west tile is empty
+    assert!(index.get_tile(1, 0, 1).is_empty());
+  }
+
+  #[test]
+  fn invalid_rings() {
+    let polygon = |rings: &str| {
+      GeoJsonIndex::new(&format!(
+        r#"{{ "type": "Polygon", "coordinates": {rings} }}"#
+      ))
+      .unwrap()
+    };
+    let exterior = "[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 0.0]]";
+    let hole = "[[1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 1.0]]";
+    let invalid = "[[0.0, 0.0], [10.0, 0.0], [0.0, 0.0]]";
+
+    let index = polygon(&format!("[{exterior}, {invalid}, {hole}]"));
+    let Geometry::Polygon(parsed) = &index.features[0].geometry else {
+      panic!("no polygon");
+    };
+    assert_eq!(parsed.exterior().0.len(), 4);
+    assert_eq!(parsed.interiors().len(), 1);
+
+    // the hole doesn't become the exterior
+    assert!(polygon(&format!("[{invalid}, {hole}]")).features.is_empty());
+  }
+
+  #[test]
+  fn wrap_antimeridian() {
+    let index = GeoJsonIndex::new(
+      r#"{ "type": "LineString", "coordinates": [[170.0, 10.0], [190.0, 10.0]] }"#,
+    )
+    .unwrap();
+
+    // the line continues on the west side of the world
+    let groups = index.get_tile(1, 0, 0);
+    assert_eq!(groups.len(), 1);
+    let line = get_coords(&groups[0][0].geometry);
+    assert_eq!(line.first().unwrap().x, (-BUFFER) as f32);
+    assert!((line.last().unwrap().x - EXTENT as f32 / 18.0).abs() < 1e-2);
+
+    let groups = index.get_tile(1, 1, 0);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].len(), 1);
+  }
+}
@@ -0,0 +1,92 @@
+use geo_types::{Coord, Geometry, LineString, MultiLineString, MultiPolygon, Polygon};
+
+/// Douglas-Peucker simplification with the tolerance in the units of the geometry, none
+/// if a line or the exterior ring of a polygon collapses
+pub fn simplify(geometry: Geometry<f64>, tolerance: f64) -> Option<Geometry<f64>> {
+  let sq_tolerance = tolerance * tolerance;
+
+  match geometry {
+    Geometry::LineString(line) => simplify_line(&line, sq_tolerance).map(Geometry::LineString),
+    Geometry::MultiLineString(multi_line) => {
+      let lines: Vec<_> = multi_line
+        .iter()
+        .filter_map(|line| simplify_line(line, sq_tolerance))
+        .collect();
+      (!lines.is_empty()).then_some(Geometry::MultiLineString(MultiLineString(lines)))
+    }
+    Geometry::Polygon(polygon) => simplify_polygon(&polygon, sq_tolerance).map(Geometry::Polygon),
+    Geometry::MultiPolygon(multi_polygon) => {
+      let polygons: Vec<_> = multi_polygon
+        .iter()
+        .filter_map(|polygon| simplify_polygon(polygon, sq_tolerance))
+        .collect();
+      (!polygons.is_empty()).then_some(Geometry::MultiPolygon(MultiPolygon(polygons)))
+    }
+    geometry => Some(geometry),
+  }
+}
+
+fn simplify_polygon(polygon: &Polygon<f64>, sq_tolerance: f64) -> Option<Polygon<f64>> {
+  let simplify_ring =
+    |ring: &LineString<f64>| simplify_line(ring, sq_tolerance).filter(|ring| ring.0.len() >= 4);
+  Some(Polygon::new(
+    simplify_ring(polygon.exterior())?,
+    polygon
+      .interiors()
+      .iter()
+      .filter_map(simplify_ring)
+      .collect(),
+  ))
+}
+
+/// first and last coordinate are always kept, so rings stay closed
+fn simplify_line(line: &LineString<f64>, sq_tolerance: f64) -> Option<LineString<f64>> {
+  let coords = &line.0;
+  if coords.len() < 2 {
+    return None;
+  }
+
+  let mut keep = vec![false; coords.len()];
+  keep[0] = true;
+  keep[coords.len() - 1] = true;
+
+  let mut ranges = vec![(0, coords.len() - 1)];
+  while let Some((first, last)) = ranges.pop() {
+    let farthest = (first + 1..last)
+      .map(|i| {
+        (
+          i,
+          get_sq_segment_distance(&coords[i], &coords[first], &coords[last]),
+        )
+      })
+      .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    if let Some((i, sq_distance)) = farthest
+      && sq_distance > sq_tolerance
+    {
+      keep[i] = true;
+      ranges.push((first, i));
+      ranges.push((i, last));
+    }
+  }
+
+  let coords: Vec<_> = coords
+    .iter()
+    .zip(keep)
+    .filter_map(|(coord, keep)| keep.then_some(*coord))
+    .collect();
+  Some(LineString(coords))
+}
+
+/// squared distance of p to the segment a b
+fn get_sq_segment_distance(p: &Coord<f64>, a: &Coord<f64>, b: &Coord<f64>) -> f64 {
+  let d = *b - *a;
+  let length_squared = d.x * d.x + d.y * d.y;
+  let t = if length_squared > 0.0 {
+    (((p.x - a.x) * d.x + (p.y - a.y) * d.y) / length_squared).clamp(0.0, 1.0)
+  } else {
+    0.0
+  };
+  let closest = *a + d * t;
+  (p.x - closest.x).powi(2) + (p.y - closest.y).powi(2)
+}
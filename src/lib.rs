@@ -1,42 +1,154 @@
 #![allow(incomplete_features)]
 #![feature(adt_const_params)]
 
-use log::error;
-use mvt_reader::feature::Feature;
-use ressource::tile::{Bucket, BucketType, Tile};
+use error::Error;
+use log::{error, warn};
+use mvt_reader::feature::{Feature, Value};
+use recovery::Recovery;
+use ressource::{
+  layer::Layer,
+  projection::Projection,
+  source::{Source, SourceData, TileFormat},
+  tile::{BucketType, FeatureRange, RasterImage, Tile, get_buffers},
+  view::{Camera, View},
+};
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::mpsc::TryRecvError::{Disconnected, Empty};
 use std::sync::mpsc::{Receiver, Sender, channel};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-use geo_types::Geometry::{LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon};
-
+mod compression;
+pub mod error;
+pub mod flatgeobuf;
+mod geojson;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mbtiles;
+mod mlt;
+pub mod pmtiles;
+mod recovery;
 pub mod renderer;
 mod ressource;
 mod tessellation;
+#[cfg(test)]
+mod test_utils;
 
-#[derive(Default)]
+/// map instance with its own renderer, sources, layers and tile parser queue. several
+/// instances can be used on the same thread, clones share the same map
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone)]
 pub struct Instance {
   renderer: Rc<RefCell<Option<renderer::Renderer>>>,
-  tiles: Rc<RefCell<Vec<Tile>>>,
-  current_size: Cell<(u32, u32)>,
+  sources: Rc<RefCell<Vec<Source>>>,
+  layers: Rc<RefCell<Vec<Layer>>>,
+  current_size: Rc<Cell<(u32, u32)>>,
+  recovery: Rc<Recovery>,
+  tile_parser_queue: Rc<(Sender<Message>, Receiver<Message>)>,
+  on_tile_ready: Rc<RefCell<Option<TileReadyCallback>>>,
+}
+
+/// called with the source name and the tile coordinate after all buckets of the tile are
+/// ready on the gpu
+pub type TileReadyCallback = Rc<dyn Fn(&str, &[u32])>;
+
+/// feature drawn at a screen position, see `Instance::query_rendered_features`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedFeature {
+  pub source: String,
+
+  pub layer: String,
+
+  /// name of the layer in the tile data
+  pub source_layer: String,
+
+  pub id: Option<u64>,
+
+  pub properties: HashMap<String, Value>,
 }
 
+/// geometry of a source layer, ready to be uploaded
 struct Message {
-  parsed_features: Vec<Feature>,
-  extent: Vec<f32>,
+  bucket_type: BucketType,
+  vertices: Vec<f32>,
+  indices: Vec<u32>,
+  extent: [f32; 4],
+  source: String,
+  source_layer: String,
+  features: Vec<FeatureRange>,
+
+  /// data generation of the source the bucket was parsed from
+  generation: u64,
+
+  /// pixels of raster buckets
+  image: Option<RasterImage>,
 }
 
-thread_local! {
-  static TILE_PARSER_QUEUE: (Sender<Message>, Receiver<Message>) = channel();
-  static INSTANCE: Instance = Instance::default();
+/// sends the buckets of a tile to the tile parser queue
+struct BucketSender {
+  sender: Sender<Message>,
+  source: String,
+  generation: u64,
 }
 
-#[cfg(target_arch = "wasm32")]
-const DIMENSIONS: usize = 2;
+impl BucketSender {
+  /// bucket of the features of a source layer, nothing is sent for unsupported geometry
+  fn send(
+    &self,
+    features: &[Feature],
+    extent: [f32; 4],
+    source_layer: String,
+  ) -> Result<(), String> {
+    let Some((bucket_type, (vertices, indices, features))) = get_buffers(features, extent) else {
+      return Ok(());
+    };
+    self
+      .sender
+      .send(Message {
+        bucket_type,
+        vertices,
+        indices,
+        extent,
+        source: self.source.clone(),
+        source_layer,
+        features,
+        generation: self.generation,
+        image: None,
+      })
+      .map_err(|err| err.to_string())
+  }
+
+  fn send_raster(&self, image: RasterImage, extent: [f32; 4]) -> Result<(), String> {
+    self
+      .sender
+      .send(Message {
+        bucket_type: BucketType::Raster,
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        extent,
+        source: self.source.clone(),
+        source_layer: RASTER_SOURCE_LAYER.to_owned(),
+        features: Vec::new(),
+        generation: self.generation,
+        image: Some(image),
+      })
+      .map_err(|err| err.to_string())
+  }
+}
+
+/// source and layer used by the free functions
+const DEFAULT_SOURCE: &str = "default";
+
+/// source layer of raster tiles
+const RASTER_SOURCE_LAYER: &str = "raster";
+
+thread_local! {
+  /// instance used by the free functions
+  static DEFAULT_INSTANCE: RefCell<Option<Instance>> = const { RefCell::new(None) };
+}
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm {
@@ -62,8 +174,7 @@ pub mod wasm {
     }
   }
 
-  #[wasm_bindgen(js_name = startWithOffscreenCanvas)]
-  pub async fn start_with_offscreencanvas(canvas: &web_sys::OffscreenCanvas) {
+  fn set_up() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 
@@ -72,172 +183,610 @@ pub mod wasm {
       Ok(()) => (),
       Err(err) => log::error!("{}", err),
     }
+  }
 
-    super::init(canvas, (canvas.width(), canvas.height())).await;
+  #[wasm_bindgen(js_name = startWithOffscreenCanvas)]
+  pub async fn start_with_offscreencanvas(
+    canvas: &web_sys::OffscreenCanvas,
+    sample_count: Option<u32>,
+  ) -> Result<(), super::error::Error> {
+    set_up();
+    super::init(canvas, (canvas.width(), canvas.height()), sample_count).await
   }
 
   #[wasm_bindgen(js_name = startWithCanvas)]
-  pub async fn start_with_canvas(canvas: &web_sys::HtmlCanvasElement) {
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
+  pub async fn start_with_canvas(
+    canvas: &web_sys::HtmlCanvasElement,
+    sample_count: Option<u32>,
+  ) -> Result<(), super::error::Error> {
+    set_up();
+    super::init(canvas, (canvas.width(), canvas.height()), sample_count).await
+  }
 
-    #[cfg(feature = "console_log")]
-    match console_log::init_with_level(log::Level::Info) {
-      Ok(()) => (),
-      Err(err) => log::error!("{}", err),
+  fn to_tile_ready_callback(callback: js_sys::Function) -> super::TileReadyCallback {
+    std::rc::Rc::new(move |source: &str, tile_coord: &[u32]| {
+      let tile_coord = js_sys::Uint32Array::from(tile_coord);
+      if let Err(err) = callback.call2(&JsValue::NULL, &JsValue::from_str(source), &tile_coord) {
+        log::error!("{:?}", err);
+      }
+    })
+  }
+
+  /// callback of the default instance, see `Instance::set_on_tile_ready_function`
+  #[wasm_bindgen(js_name = setOnTileReady)]
+  pub fn set_on_tile_ready(callback: Option<js_sys::Function>) -> Result<(), super::error::Error> {
+    super::default_instance()?.set_on_tile_ready(callback.map(to_tile_ready_callback));
+    Ok(())
+  }
+
+  /// archive of the default source, see `Instance::add_pmtiles_function`
+  #[wasm_bindgen(js_name = addPmTiles)]
+  pub async fn add_pmtiles(fetch_range: js_sys::Function) -> Result<(), super::error::Error> {
+    let provider = std::rc::Rc::new(super::pmtiles::FunctionProvider::new(fetch_range));
+    super::default_instance()?
+      .add_pmtiles(super::DEFAULT_SOURCE.to_owned(), provider)
+      .await
+  }
+
+  /// file of the default source, see `Instance::add_flatgeobuf_function`
+  #[wasm_bindgen(js_name = addFlatGeobuf)]
+  pub async fn add_flatgeobuf(fetch_range: js_sys::Function) -> Result<(), super::error::Error> {
+    let provider = std::rc::Rc::new(super::pmtiles::FunctionProvider::new(fetch_range));
+    super::default_instance()?
+      .add_flatgeobuf(super::DEFAULT_SOURCE.to_owned(), provider)
+      .await
+  }
+
+  /// object with source, layer, sourceLayer, id and properties like the features of
+  /// OpenLayers
+  fn to_js_feature(feature: super::RenderedFeature) -> JsValue {
+    use mvt_reader::feature::Value;
+
+    let properties = js_sys::Object::new();
+    for (key, value) in feature.properties {
+      let value = match value {
+        Value::String(value) => JsValue::from_str(&value),
+        Value::Float(value) => JsValue::from_f64(value as f64),
+        Value::Double(value) => JsValue::from_f64(value),
+        Value::Int(value) | Value::SInt(value) => JsValue::from_f64(value as f64),
+        Value::UInt(value) => JsValue::from_f64(value as f64),
+        Value::Bool(value) => JsValue::from_bool(value),
+        Value::Null => JsValue::NULL,
+      };
+      let _ = js_sys::Reflect::set(&properties, &JsValue::from_str(&key), &value);
     }
 
-    super::init(canvas, (canvas.width(), canvas.height())).await;
+    let object = js_sys::Object::new();
+    let id = feature
+      .id
+      .map_or(JsValue::UNDEFINED, |id| JsValue::from_f64(id as f64));
+    for (key, value) in [
+      ("source", JsValue::from_str(&feature.source)),
+      ("layer", JsValue::from_str(&feature.layer)),
+      ("sourceLayer", JsValue::from_str(&feature.source_layer)),
+      ("id", id),
+      ("properties", properties.into()),
+    ] {
+      let _ = js_sys::Reflect::set(&object, &JsValue::from_str(key), &value);
+    }
+    object.into()
   }
-}
 
-#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
-pub fn render(view_matrix: Vec<f32>, new_size: Vec<u32>) {
-  process_tile_parser_queue();
+  /// features of the default instance, see `Instance::query_rendered_features_array`
+  #[wasm_bindgen(js_name = queryRenderedFeatures)]
+  pub fn query_rendered_features(
+    x: f32,
+    y: f32,
+    radius: f32,
+  ) -> Result<js_sys::Array, super::error::Error> {
+    super::default_instance()?.query_rendered_features_array(x, y, radius)
+  }
+
+  #[wasm_bindgen]
+  impl super::Instance {
+    #[wasm_bindgen(js_name = fromOffscreenCanvas)]
+    pub async fn from_offscreencanvas(
+      canvas: web_sys::OffscreenCanvas,
+      sample_count: Option<u32>,
+    ) -> Result<super::Instance, super::error::Error> {
+      set_up();
+      super::Instance::new(&canvas, (canvas.width(), canvas.height()), sample_count).await
+    }
 
-  INSTANCE.with(|instance| {
-    let mut reference = instance.renderer.borrow_mut();
-    let renderer = reference.as_mut().unwrap();
+    /// the callback is called with the source name and the tile coordinate, see
+    /// `add_pbf_tile_data`
+    #[wasm_bindgen(js_name = setOnTileReady)]
+    pub fn set_on_tile_ready_function(&self, callback: Option<js_sys::Function>) {
+      self.set_on_tile_ready(callback.map(to_tile_ready_callback));
+    }
 
-    renderer
-      .view
-      .set_view_matrix(glam::Mat4::from_cols_slice(&view_matrix[..]));
+    /// PMTiles archive of the source, read with the function (offset, length) =>
+    /// Promise<ArrayBuffer | Uint8Array>, e.g. a fetch with a range header
+    #[wasm_bindgen(js_name = addPmTiles)]
+    pub async fn add_pmtiles_function(
+      &self,
+      source: String,
+      fetch_range: js_sys::Function,
+    ) -> Result<(), super::error::Error> {
+      let provider = std::rc::Rc::new(super::pmtiles::FunctionProvider::new(fetch_range));
+      self.add_pmtiles(source, provider).await
+    }
 
-    let current_size = instance.current_size.get();
-    if current_size.0 != new_size[0] || current_size.1 != new_size[1] {
-      instance.current_size.set((new_size[0], new_size[1]));
-      renderer.set_size(instance.current_size.get());
+    /// FlatGeobuf file of the source, read with a function like for `add_pmtiles_function`
+    #[wasm_bindgen(js_name = addFlatGeobuf)]
+    pub async fn add_flatgeobuf_function(
+      &self,
+      source: String,
+      fetch_range: js_sys::Function,
+    ) -> Result<(), super::error::Error> {
+      let provider = std::rc::Rc::new(super::pmtiles::FunctionProvider::new(fetch_range));
+      self.add_flatgeobuf(source, provider).await
     }
 
-    renderer.render(&instance.tiles.borrow());
-  });
+    /// features at the pixel of e.g. a pointer event, see `query_rendered_features`
+    #[wasm_bindgen(js_name = queryRenderedFeatures)]
+    pub fn query_rendered_features_array(
+      &self,
+      x: f32,
+      y: f32,
+      radius: f32,
+    ) -> Result<js_sys::Array, super::error::Error> {
+      Ok(
+        self
+          .query_rendered_features(x, y, radius)?
+          .into_iter()
+          .map(to_js_feature)
+          .collect(),
+      )
+    }
+
+    #[wasm_bindgen(js_name = fromCanvas)]
+    pub async fn from_canvas(
+      canvas: web_sys::HtmlCanvasElement,
+      sample_count: Option<u32>,
+    ) -> Result<super::Instance, super::error::Error> {
+      set_up();
+      super::Instance::new(&canvas, (canvas.width(), canvas.height()), sample_count).await
+    }
+  }
 }
 
-#[cfg(target_arch = "wasm32")]
-fn get_buffers(features: &[Feature]) -> (Vec<f32>, Vec<u32>) {
-  let mut all_vertices = vec![];
-  let mut all_indices = vec![];
-
-  for feature in features.iter() {
-    match feature.get_geometry() {
-      LineString(line) => {
-        let mut vertices = Vec::with_capacity(line.0.len() * DIMENSIONS);
-        let mut indices = Vec::with_capacity(line.0.len());
-        let offset = (all_vertices.len() / DIMENSIONS) as u32;
-
-        for (i, coord) in line.0.iter().enumerate() {
-          vertices.push(coord.x);
-          vertices.push(coord.y);
-          indices.push(i as u32 + offset);
-        }
+impl Instance {
+  /// sample count defaults to 4x msaa, falls back to 1 if not supported by the adapter or
+  /// not one of 1, 2, 4 and 8
+  pub async fn new<W: renderer::ToSurface>(
+    window: &W,
+    size: (u32, u32),
+    sample_count: Option<u32>,
+  ) -> Result<Self, Error> {
+    let sample_count = sample_count.unwrap_or(renderer::DEFAULT_SAMPLE_COUNT);
+    let renderer = renderer::Renderer::new(window, size, sample_count).await?;
 
-        if let Some(last) = indices.last() {
-          indices.push(*last); // separate linestring from the next one
-        }
+    Ok(Self {
+      renderer: Rc::new(RefCell::new(Some(renderer))),
+      sources: Rc::new(RefCell::new(Vec::new())),
+      layers: Rc::new(RefCell::new(Vec::new())),
+      current_size: Rc::new(Cell::new(size)),
+      recovery: Rc::new(Recovery::default()),
+      tile_parser_queue: Rc::new(channel()),
+      on_tile_ready: Rc::new(RefCell::new(None)),
+    })
+  }
 
-        all_vertices.append(&mut vertices);
-        all_indices.append(&mut indices);
-      }
-      MultiLineString(multi_line) => {
-        for line in multi_line.0.iter() {
-          let mut vertices = Vec::with_capacity(line.0.len() * DIMENSIONS);
-          let mut indices = Vec::with_capacity(line.0.len());
-          let offset = (all_vertices.len() / DIMENSIONS) as u32;
-          for (i, coord) in line.0.iter().enumerate() {
-            vertices.push(coord.x);
-            vertices.push(coord.y);
-            indices.push(i as u32 + offset);
-          }
+  fn with_renderer<T, F: FnOnce(&mut renderer::Renderer) -> T>(&self, f: F) -> Result<T, Error> {
+    let mut reference = self.renderer.borrow_mut();
+    let renderer = reference.as_mut().ok_or(Error::NotInitialized)?;
+
+    Ok(f(renderer))
+  }
+
+  /// new size is given in device pixels, style sizes are scaled by the pixel ratio
+  fn render_view<F: FnOnce(&mut View)>(
+    &self,
+    new_size: Vec<u32>,
+    pixel_ratio: f32,
+    update_view: F,
+  ) -> Result<(), Error> {
+    let &[width, height] = new_size.as_slice() else {
+      return Err(Error::InvalidSize(new_size));
+    };
 
-          if let Some(last) = indices.last() {
-            indices.push(*last); // separate linestring from the next one
+    // frames are skipped until the device is recreated
+    if self.recovery.is_active() {
+      return Ok(());
+    }
+
+    if self.with_renderer(|renderer| renderer.is_device_lost())? {
+      warn!("device lost, recreate device and tiles");
+      self.recovery.start();
+
+      #[cfg(target_arch = "wasm32")]
+      {
+        let instance = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+          if let Err(err) = instance.recover_device().await {
+            error!("{}", err);
           }
+        });
+        return Ok(());
+      }
 
-          all_vertices.append(&mut vertices);
-          all_indices.append(&mut indices);
-        }
+      #[cfg(not(target_arch = "wasm32"))]
+      pollster::block_on(self.recover_device())?;
+    }
+
+    // buckets of add_tile_data calls still pending on a worker are built here. a failing
+    // bucket is only logged, so the frame is still drawn
+    if let Err(err) = self.process_tile_parser_queue() {
+      error!("{}", err);
+    }
+
+    let mut reference = self.renderer.borrow_mut();
+    let renderer = reference.as_mut().ok_or(Error::NotInitialized)?;
+
+    let current_size = self.current_size.get();
+    if current_size != (width, height) {
+      self.current_size.set((width, height));
+      renderer.set_size(self.current_size.get());
+    }
+
+    renderer.view.set_pixel_ratio(pixel_ratio);
+    update_view(&mut renderer.view);
+
+    renderer.render(&self.sources.borrow(), &self.layers.borrow())
+  }
+
+  /// no frame is rendered until the device is recreated. sources and layers stay in
+  /// place and can be changed while the new device is requested, all of them are
+  /// recreated on it afterwards. tile data parsed in the meantime is built on the new
+  /// device before pending `add_tile_data` calls resolve
+  async fn recover_device(&self) -> Result<(), Error> {
+    let result = async {
+      let recovered = self
+        .with_renderer(|renderer| renderer.request_recovery())?
+        .await?;
+      let mut sources = self.sources.borrow_mut();
+      let mut layers = self.layers.borrow_mut();
+      self.with_renderer(|renderer| renderer.recover(recovered, &mut sources, &mut layers))?
+    }
+    .await
+    .and_then(|_| self.process_tile_parser_queue());
+    self.recovery.finish(result.is_ok());
+
+    result
+  }
+
+  /// tiles of removed sources are dropped
+  fn add_tile(&self, source: &str, tile: Tile) {
+    if let Some(source) = self
+      .sources
+      .borrow_mut()
+      .iter_mut()
+      .find(|candidate| candidate.get_name() == source)
+    {
+      source.add_tile(tile);
+    }
+  }
+
+  /// false if the source was removed or its data changed from the generation
+  fn is_current_generation(&self, source: &str, generation: u64) -> bool {
+    self
+      .sources
+      .borrow()
+      .iter()
+      .any(|candidate| candidate.get_name() == source && candidate.get_generation() == generation)
+  }
+
+  /// runs the parser on a worker thread with the multithreaded feature. resolves after
+  /// the buckets it sent are built into tiles and ready on the gpu. buckets are dropped
+  /// if the data of the source changed from the given generation in the meantime
+  async fn add_tile_data<P>(
+    &self,
+    source: String,
+    generation: u64,
+    tile_coord: Vec<u32>,
+    parse: P,
+    to_error: fn(String) -> Error,
+  ) -> Result<(), Error>
+  where
+    P: FnOnce(BucketSender) -> Result<(), String> + Send + 'static,
+  {
+    let sender = BucketSender {
+      sender: self.tile_parser_queue.0.clone(),
+      source: source.clone(),
+      generation,
+    };
+
+    #[cfg(not(feature = "multithreaded"))]
+    let result = parse(sender);
+
+    #[cfg(feature = "multithreaded")]
+    let result = {
+      let (result_sender, result_receiver) = futures::channel::oneshot::channel();
+      rayon::spawn(move || {
+        let _ = result_sender.send(parse(sender));
+      });
+      result_receiver
+        .await
+        .unwrap_or_else(|err| Err(err.to_string()))
+    };
+
+    result.map_err(to_error)?;
+
+    // tiles are built on the new device once it is recreated
+    if !self.recovery.wait().await {
+      return Err(Error::DeviceLost);
+    }
+
+    self.process_tile_parser_queue()?;
+    self
+      .with_renderer(|renderer| renderer.submitted_work_done())?
+      .await;
+
+    // cloned, so the callback can replace itself
+    let on_tile_ready = self.on_tile_ready.borrow().clone();
+    if let Some(on_tile_ready) = on_tile_ready {
+      on_tile_ready(&source, &tile_coord);
+    }
+
+    Ok(())
+  }
+
+  fn with_source<T, F: FnOnce(&mut Source) -> T>(&self, name: &str, f: F) -> Result<T, Error> {
+    let mut sources = self.sources.borrow_mut();
+    let source = sources
+      .iter_mut()
+      .find(|source| source.get_name() == name)
+      .ok_or_else(|| Error::UnknownSource(name.to_owned()))?;
+
+    Ok(f(source))
+  }
+
+  /// tiles built from previous data of the source are removed
+  fn set_source_data(&self, source: String, data: SourceData) -> Result<(), Error> {
+    self.with_source(&source, |source| source.set_data(data))
+  }
+
+  /// data of the source with its generation
+  fn get_source_data(&self, source: &str) -> Result<(Option<SourceData>, u64), Error> {
+    self.with_source(source, |source| {
+      (source.get_data(), source.get_generation())
+    })
+  }
+
+  /// tiles of the source are read from the PMTiles archive with `add_pmtiles_tile`,
+  /// removes the tiles of previous data. archives of MVT or MLT tiles set the format of
+  /// the source
+  pub async fn add_pmtiles(
+    &self,
+    source: String,
+    provider: Rc<dyn pmtiles::RangeProvider>,
+  ) -> Result<(), Error> {
+    if !self.has_source(&source) {
+      return Err(Error::UnknownSource(source));
+    }
+    let archive = pmtiles::PmTiles::new(provider)
+      .await
+      .map_err(Error::Archive)?;
+    if let Some(format) = archive.get_tile_format() {
+      self.with_source(&source, |source| source.set_format(format))?;
+    }
+    self.set_source_data(source, SourceData::PmTiles(Rc::new(archive)))
+  }
+
+  /// tiles of the source are read from the MBTiles file with `add_mbtiles_tile`, removes
+  /// the tiles of previous data. the metadata tells the bounds, zoom range and source
+  /// layers of the file
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn add_mbtiles(
+    &self,
+    source: String,
+    path: impl AsRef<std::path::Path>,
+  ) -> Result<mbtiles::Metadata, Error> {
+    if !self.has_source(&source) {
+      return Err(Error::UnknownSource(source));
+    }
+    let file = mbtiles::MbTiles::open(path).map_err(Error::Archive)?;
+    let metadata = file.get_metadata().clone();
+    self.set_source_data(source, SourceData::MbTiles(Rc::new(file)))?;
+    Ok(metadata)
+  }
+
+  /// reads the tile [z, x, y] from the MBTiles file of the source. resolves like
+  /// `add_pbf_tile_data`, also if the file has no such tile
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn add_mbtiles_tile(
+    &self,
+    source: String,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let &[z, x, y] = tile_coord.as_slice() else {
+      return Err(Error::InvalidTileCoord(tile_coord));
+    };
+    let (Some(SourceData::MbTiles(file)), generation) = self.get_source_data(&source)? else {
+      return Err(Error::Archive(format!(
+        "source {source} has no MBTiles file"
+      )));
+    };
+
+    let pbf = file.get_tile(z, x, y).map_err(Error::Archive)?;
+    self
+      .add_archive_tile(source, generation, pbf, tile_coord, extent)
+      .await
+  }
+
+  /// slices the tile from the index with the parser, so on a worker thread with the
+  /// multithreaded feature. the generation is the one of the source data the index was
+  /// read from
+  async fn add_sliced_tile(
+    &self,
+    (source, generation): (String, u64),
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+    index: Arc<geojson::GeoJsonIndex>,
+    source_layer: &'static str,
+    to_error: fn(String) -> Error,
+  ) -> Result<(), Error> {
+    let extent: [f32; 4] = extent.try_into().map_err(Error::InvalidExtent)?;
+    let &[z, x, y] = tile_coord.as_slice() else {
+      return Err(Error::InvalidTileCoord(tile_coord));
+    };
+
+    let slice = move |sender: BucketSender| -> Result<(), String> {
+      for features in index.get_tile(z, x, y) {
+        sender.send(&features, extent, source_layer.to_owned())?;
       }
-      _ => {
-        log::info!("Geometry type currently not supported");
+      Ok(())
+    };
+
+    self
+      .add_tile_data(source, generation, tile_coord, slice, to_error)
+      .await
+  }
+
+  /// tile read from an archive, a missing tile is reported as ready without data
+  async fn add_archive_tile(
+    &self,
+    source: String,
+    generation: u64,
+    pbf: Option<Vec<u8>>,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    match pbf {
+      Some(pbf) => {
+        self
+          .add_pbf_tile(source, generation, pbf, tile_coord, extent)
+          .await
+      }
+      None => {
+        let empty = |_: BucketSender| Ok(());
+        self
+          .add_tile_data(source, generation, tile_coord, empty, Error::Archive)
+          .await
       }
     }
   }
-  (all_vertices, all_indices)
-}
 
-fn process_tile_parser_queue() {
-  TILE_PARSER_QUEUE.with(|(_, receiver)| {
+  /// tiles of the source are read from the FlatGeobuf file with `add_flatgeobuf_tile`,
+  /// removes the tiles of previous data. the file needs a spatial index
+  pub async fn add_flatgeobuf(
+    &self,
+    source: String,
+    provider: Rc<dyn pmtiles::RangeProvider>,
+  ) -> Result<(), Error> {
+    if !self.has_source(&source) {
+      return Err(Error::UnknownSource(source));
+    }
+    let file = flatgeobuf::FlatGeobuf::new(provider)
+      .await
+      .map_err(Error::Archive)?;
+    self.set_source_data(source, SourceData::FlatGeobuf(Rc::new(file)))
+  }
+
+  /// features drawn within the radius around the pixel, both in css pixels from the
+  /// top left corner of the canvas. the top most first, a feature drawn by several
+  /// layers is returned for each of them. geometry is hit tested on the cpu against the
+  /// tiles of the last rendered frame, nothing is found while the globe is visible
+  pub fn query_rendered_features(
+    &self,
+    x: f32,
+    y: f32,
+    radius: f32,
+  ) -> Result<Vec<RenderedFeature>, Error> {
+    let sources = self.sources.borrow();
+    let layers = self.layers.borrow();
+    self.with_renderer(|renderer| {
+      renderer
+        .get_features_at(&sources, &layers, glam::Vec2::new(x, y), radius)
+        .into_iter()
+        .map(|(layer, tile, feature)| RenderedFeature {
+          source: layer.get_source().to_owned(),
+          layer: layer.get_name().to_owned(),
+          source_layer: tile.get_source_layer().to_owned(),
+          id: feature.id,
+          properties: feature.properties.clone(),
+        })
+        .collect()
+    })
+  }
+
+  fn has_source(&self, name: &str) -> bool {
+    self
+      .sources
+      .borrow()
+      .iter()
+      .any(|source| source.get_name() == name)
+  }
+
+  /// replaces a previous callback, none removes it
+  pub fn set_on_tile_ready(&self, callback: Option<TileReadyCallback>) {
+    self.on_tile_ready.replace(callback);
+  }
+
+  fn with_layer<T, F: FnOnce(&mut Layer) -> T>(&self, name: &str, f: F) -> Result<T, Error> {
+    let mut layers = self.layers.borrow_mut();
+    let layer = layers
+      .iter_mut()
+      .find(|layer| layer.get_name() == name)
+      .ok_or_else(|| Error::UnknownLayer(name.to_owned()))?;
+
+    Ok(f(layer))
+  }
+
+  /// builds all queued buckets into tiles. a failing bucket doesn't stop the others,
+  /// the first error is returned and the rest are logged
+  fn process_tile_parser_queue(&self) -> Result<(), Error> {
+    let (_, receiver) = self.tile_parser_queue.as_ref();
+    let mut result = Ok(());
     loop {
       match receiver.try_recv() {
         Ok(msg) => {
-          let mut parsed_features = msg.parsed_features;
-          if parsed_features.is_empty() {
-            return;
+          let Message {
+            bucket_type,
+            vertices,
+            indices,
+            extent,
+            source,
+            source_layer,
+            features,
+            generation,
+            image,
+          } = msg;
+
+          // parsed from data the source no longer has
+          if !self.is_current_generation(&source, generation) {
+            continue;
           }
 
-          INSTANCE.with(|instance| {
-            let extent: [f32; 4] = msg.extent.try_into().unwrap();
-            let mut reference = instance.renderer.try_borrow_mut().unwrap();
-            let renderer = reference.as_mut().unwrap();
-
-            if let Some(feature) = parsed_features.first() {
-              match feature.get_geometry() {
-                &Point(_) | &MultiPoint(_) => {
-                  let mut tile = renderer.create_tile::<Feature>(BucketType::Point, extent);
-
-                  if tile.get_bucket_type() == BucketType::Point {
-                    <Tile as Bucket<Feature, { BucketType::Point }>>::add_features(
-                      &mut tile,
-                      &mut parsed_features,
-                      &renderer.ressource_manager,
-                    );
-                  }
-
-                  instance.tiles.borrow_mut().push(tile);
-                }
-                &LineString(_) | &MultiLineString(_) => {
-                  #[cfg(target_arch = "wasm32")]
-                  {
-                    let (vertices, indices) = get_buffers(&parsed_features[..]);
-
-                    let clone = instance.renderer.clone();
-                    let tiles = instance.tiles.clone();
-
-                    #[allow(clippy::await_holding_refcell_ref)]
-                    wasm_bindgen_futures::spawn_local(async move {
-                      let mut reference = clone.borrow_mut();
-                      let renderer = reference.as_mut().unwrap();
-                      let (vertices_buffer, indices_buffer) =
-                        renderer.compute(&vertices[..], &indices[..]).await;
-
-                      let mut tile = renderer.create_tile::<Feature>(BucketType::Line, extent);
-                      tile.add_buffers(vertices_buffer, indices_buffer);
-                      tiles.borrow_mut().push(tile);
-                    });
-                  }
-                }
-                &Polygon(_) | &MultiPolygon(_) => {
-                  let mut tile = renderer.create_tile::<Feature>(BucketType::Fill, extent);
-
-                  if tile.get_bucket_type() == BucketType::Fill {
-                    <Tile as Bucket<Feature, { BucketType::Fill }>>::add_features(
-                      &mut tile,
-                      &mut parsed_features,
-                      &renderer.ressource_manager,
-                    );
-                  }
-
-                  instance.tiles.borrow_mut().push(tile);
-                }
-                _ => (),
-              }
-            }
-          });
+          let mut reference = self.renderer.borrow_mut();
+          let renderer = reference.as_mut().ok_or(Error::NotInitialized)?;
+
+          // features are uploaded with the geometry
+          let mut tile = renderer.create_tile::<Feature>(bucket_type.clone(), extent);
+          tile.set_source_layer(source_layer);
+          tile.set_features(features);
+          let built = if let Some(image) = image {
+            tile.add_image(image, &renderer.ressource_manager)
+          } else if bucket_type == BucketType::Line {
+            tile.retain_buffers(vertices, indices);
+            renderer
+              .compute(&tile)
+              .map(|(vertex_slot, index_slot)| tile.add_slots(vertex_slot, index_slot))
+          } else {
+            tile.add_buffers(vertices, indices, &renderer.ressource_manager)
+          };
+
+          match built {
+            Ok(()) => self.add_tile(&source, tile),
+            Err(err) if result.is_ok() => result = Err(err),
+            Err(err) => error!("{}", err),
+          }
         }
         Err(err) => match err {
           Disconnected => {
-            error!("{}", err);
-            break;
+            return result.and(Err(Error::ParserQueue(err)));
           }
           Empty => {
             break;
@@ -245,42 +794,429 @@ fn process_tile_parser_queue() {
         },
       }
     }
-  });
+    result
+  }
 }
 
-#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addPbfTileData))]
-pub async fn add_pbf_tile_data(pbf: Vec<u8>, _tile_coord: Vec<u32>, extent: Vec<f32>) {
-  TILE_PARSER_QUEUE.with(|(sender, _)| {
-    let sender = sender.clone();
-
-    let parse = move || {
-      let reader = mvt_reader::Reader::new(pbf).expect("parse error");
-      let layer_names = reader.get_layer_names().unwrap();
-
-      for (i, _) in layer_names.iter().enumerate() {
-        sender
-          .send(Message {
-            parsed_features: reader.get_features(i).unwrap(),
-            extent: extent.clone(),
-          })
-          .unwrap();
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Instance {
+  pub fn render(
+    &self,
+    view_matrix: Vec<f32>,
+    new_size: Vec<u32>,
+    pixel_ratio: f32,
+  ) -> Result<(), Error> {
+    self.render_view(new_size, pixel_ratio, |view| {
+      view.set_view_matrix(glam::Mat4::from_cols_slice(&view_matrix[..]))
+    })
+  }
+
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = renderWithCamera))]
+  pub fn render_with_camera(
+    &self,
+    center: Vec<f32>,
+    zoom: f32,
+    bearing: f32,
+    pitch: f32,
+    new_size: Vec<u32>,
+    pixel_ratio: f32,
+  ) -> Result<(), Error> {
+    self.render_view(new_size, pixel_ratio, |view| {
+      view.set_camera(Camera {
+        center: glam::Vec2::from_slice(&center[..]),
+        zoom,
+        bearing,
+        pitch,
+      })
+    })
+  }
+
+  /// extent of the map area visible in the last rendered frame, can be used to load
+  /// the tiles needed for a tilted view
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = getVisibleExtent))]
+  pub fn get_visible_extent(&self) -> Result<Vec<f32>, Error> {
+    self.with_renderer(|renderer| renderer.view.get_visible_extent().to_vec())
+  }
+
+  /// coordinate reference system of the camera and the tile extents, defaults to EPSG:3857.
+  /// extent, max resolution and resolutions are the ones of the OpenLayers projection and
+  /// tile grid, resolutions are only needed if they don't halve with every zoom level
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setProjection))]
+  pub fn set_projection(
+    &self,
+    code: String,
+    extent: Vec<f32>,
+    max_resolution: f32,
+    resolutions: Option<Vec<f32>>,
+  ) -> Result<(), Error> {
+    let extent: [f32; 4] = extent.try_into().map_err(Error::InvalidExtent)?;
+    let projection = Projection::new(&code, extent, max_resolution, resolutions)?;
+    self.with_renderer(|renderer| renderer.view.set_projection(projection))
+  }
+
+  /// project the map onto a globe at low zoom levels, only used with `render_with_camera`
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setGlobe))]
+  pub fn set_globe(&self, globe: bool) -> Result<(), Error> {
+    self.with_renderer(|renderer| renderer.view.set_globe(globe))
+  }
+
+  /// named set of tiles, replaces a source with the same name
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addSource))]
+  pub fn add_source(&self, name: String) {
+    self.remove_source(&name);
+    self.sources.borrow_mut().push(Source::new(name));
+  }
+
+  /// format of the tiles added with `add_pbf_tile_data`, "mvt" for Mapbox Vector Tiles
+  /// or "mlt" for MapLibre Tiles. sources start with "mvt"
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setSourceFormat))]
+  pub fn set_source_format(&self, source: String, format: String) -> Result<(), Error> {
+    let format = TileFormat::new(&format).ok_or(Error::UnknownTileFormat(format))?;
+    self.with_source(&source, |source| source.set_format(format))
+  }
+
+  /// removes the source and its tiles, layers of the source draw nothing
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = removeSource))]
+  pub fn remove_source(&self, name: &str) {
+    self
+      .sources
+      .borrow_mut()
+      .retain(|source| source.get_name() != name);
+  }
+
+  /// style layer drawing the tiles of the source layer, or of all layers of the tile data
+  /// if none is given. replaces a layer with the same name
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addLayer))]
+  pub fn add_layer(
+    &self,
+    name: String,
+    source: String,
+    source_layer: Option<String>,
+  ) -> Result<(), Error> {
+    if !self.has_source(&source) {
+      return Err(Error::UnknownSource(source));
+    }
+
+    let layer = self.with_renderer(|renderer| renderer.create_layer(name, source, source_layer))?;
+    self.remove_layer(layer.get_name());
+    self.layers.borrow_mut().push(layer);
+    Ok(())
+  }
+
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = removeLayer))]
+  pub fn remove_layer(&self, name: &str) {
+    self
+      .layers
+      .borrow_mut()
+      .retain(|layer| layer.get_name() != name);
+  }
+
+  /// layers with a higher z-index are drawn on top, layers with the same z-index in the
+  /// order they were added
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setLayerZIndex))]
+  pub fn set_layer_z_index(&self, name: &str, z_index: i32) -> Result<(), Error> {
+    self.with_layer(name, |layer| layer.set_z_index(z_index))
+  }
+
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setLayerVisible))]
+  pub fn set_layer_visible(&self, name: &str, visible: bool) -> Result<(), Error> {
+    self.with_layer(name, |layer| layer.set_visible(visible))
+  }
+
+  /// opacity between 0 and 1
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setLayerOpacity))]
+  pub fn set_layer_opacity(&self, name: &str, opacity: f32) -> Result<(), Error> {
+    self.with_layer(name, |layer| layer.set_opacity(opacity))
+  }
+
+  /// resolves after all buckets of the tile are uploaded and tessellated on the gpu, so
+  /// the next frame draws the tile. decoded in the format of the source, gzip or zlib
+  /// compressed tiles are decompressed.
+  /// rejects if the tile data is corrupt or decompresses to more than 64 MiB
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addPbfTileData))]
+  pub async fn add_pbf_tile_data(
+    &self,
+    source: String,
+    pbf: Vec<u8>,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let generation = self.with_source(&source, |source| source.get_generation())?;
+    self
+      .add_pbf_tile(source, generation, pbf, tile_coord, extent)
+      .await
+  }
+
+  /// tile data parsed for the given data generation of the source
+  async fn add_pbf_tile(
+    &self,
+    source: String,
+    generation: u64,
+    pbf: Vec<u8>,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let extent: [f32; 4] = extent.try_into().map_err(Error::InvalidExtent)?;
+    let format = self.with_source(&source, |source| source.get_format())?;
+
+    let parse = move |sender: BucketSender| -> Result<(), String> {
+      let pbf = compression::decompress(pbf)?;
+      match format {
+        TileFormat::Mvt => {
+          let reader = mvt_reader::Reader::new(pbf).map_err(|err| err.to_string())?;
+          let layer_names = reader.get_layer_names().map_err(|err| err.to_string())?;
+
+          for (i, source_layer) in layer_names.into_iter().enumerate() {
+            let parsed_features = reader.get_features(i).map_err(|err| err.to_string())?;
+            sender.send(&parsed_features, extent, source_layer)?;
+          }
+        }
+        TileFormat::Mlt => {
+          for layer in mlt::decode(&pbf, |_| true)? {
+            sender.send(&layer.features, extent, layer.name)?;
+          }
+        }
       }
+      Ok(())
     };
 
-    #[cfg(not(feature = "multithreaded"))]
-    parse();
+    self
+      .add_tile_data(source, generation, tile_coord, parse, Error::TileDecode)
+      .await
+  }
 
-    #[cfg(feature = "multithreaded")]
-    rayon::spawn(parse);
-  });
+  /// rgba pixels of a raster tile, not premultiplied and rows from top to bottom. drawn
+  /// by layers of the source without source layer or with the source layer "raster".
+  /// resolves like `add_pbf_tile_data`, rejects if the pixels don't match the size or
+  /// the size exceeds the maximum texture size of the device
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addRasterTileData))]
+  pub async fn add_raster_tile_data(
+    &self,
+    source: String,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let extent: [f32; 4] = extent.try_into().map_err(Error::InvalidExtent)?;
+    let max_size = self.with_renderer(|renderer| renderer.get_max_texture_size())?;
+    let image = RasterImage::new(pixels, width, height, max_size).map_err(Error::InvalidImage)?;
+    let generation = self.with_source(&source, |source| source.get_generation())?;
+
+    let send = move |sender: BucketSender| sender.send_raster(image, extent);
+    self
+      .add_tile_data(source, generation, tile_coord, send, Error::InvalidImage)
+      .await
+  }
+
+  /// GeoJSON in EPSG:4326 for the source, tiles are sliced from it with
+  /// `add_geojson_tile`. the features are drawn by layers of the source without source
+  /// layer or with the source layer "geojson". removes the tiles of previous data
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addGeoJson))]
+  pub fn add_geojson(&self, source: String, geojson: String) -> Result<(), Error> {
+    if !self.has_source(&source) {
+      return Err(Error::UnknownSource(source));
+    }
+    let index = geojson::GeoJsonIndex::new(&geojson).map_err(Error::GeoJson)?;
+    self.set_source_data(source, SourceData::GeoJson(Arc::new(index)))
+  }
+
+  /// slices the tile [z, x, y] of the web mercator tile grid from the GeoJSON of the
+  /// source. resolves like `add_pbf_tile_data`
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addGeoJsonTile))]
+  pub async fn add_geojson_tile(
+    &self,
+    source: String,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let (Some(SourceData::GeoJson(index)), generation) = self.get_source_data(&source)? else {
+      return Err(Error::GeoJson(format!("source {source} has no geojson")));
+    };
+    self
+      .add_sliced_tile(
+        (source, generation),
+        tile_coord,
+        extent,
+        index,
+        geojson::SOURCE_LAYER,
+        Error::GeoJson,
+      )
+      .await
+  }
+
+  /// reads the features of the tile [z, x, y] from the FlatGeobuf file of the source and
+  /// slices the tile from them. the features are drawn by layers of the source without
+  /// source layer or with the source layer "flatgeobuf". resolves like
+  /// `add_pbf_tile_data`
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addFlatGeobufTile))]
+  pub async fn add_flatgeobuf_tile(
+    &self,
+    source: String,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let &[z, x, y] = tile_coord.as_slice() else {
+      return Err(Error::InvalidTileCoord(tile_coord));
+    };
+    let (Some(SourceData::FlatGeobuf(file)), generation) = self.get_source_data(&source)? else {
+      return Err(Error::Archive(format!(
+        "source {source} has no FlatGeobuf file"
+      )));
+    };
+    let index = file.get_tile_index(z, x, y).await.map_err(Error::Archive)?;
+    self
+      .add_sliced_tile(
+        (source, generation),
+        tile_coord,
+        extent,
+        Arc::new(index),
+        flatgeobuf::SOURCE_LAYER,
+        Error::Archive,
+      )
+      .await
+  }
+
+  /// reads the tile [z, x, y] from the PMTiles archive of the source. resolves like
+  /// `add_pbf_tile_data`, also if the archive has no such tile
+  #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addPmTilesTile))]
+  pub async fn add_pmtiles_tile(
+    &self,
+    source: String,
+    tile_coord: Vec<u32>,
+    extent: Vec<f32>,
+  ) -> Result<(), Error> {
+    let &[z, x, y] = tile_coord.as_slice() else {
+      return Err(Error::InvalidTileCoord(tile_coord));
+    };
+    let (Some(SourceData::PmTiles(archive)), generation) = self.get_source_data(&source)? else {
+      return Err(Error::Archive(format!(
+        "source {source} has no PMTiles archive"
+      )));
+    };
+
+    let pbf = archive.get_tile(z, x, y).await.map_err(Error::Archive)?;
+    self
+      .add_archive_tile(source, generation, pbf, tile_coord, extent)
+      .await
+  }
+}
+
+/// the default instance is cloned, so no borrow is held while the function runs
+fn default_instance() -> Result<Instance, Error> {
+  DEFAULT_INSTANCE.with(|instance| instance.borrow().clone().ok_or(Error::NotInitialized))
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn render(view_matrix: Vec<f32>, new_size: Vec<u32>, pixel_ratio: f32) -> Result<(), Error> {
+  default_instance()?.render(view_matrix, new_size, pixel_ratio)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = renderWithCamera))]
+pub fn render_with_camera(
+  center: Vec<f32>,
+  zoom: f32,
+  bearing: f32,
+  pitch: f32,
+  new_size: Vec<u32>,
+  pixel_ratio: f32,
+) -> Result<(), Error> {
+  default_instance()?.render_with_camera(center, zoom, bearing, pitch, new_size, pixel_ratio)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = getVisibleExtent))]
+pub fn get_visible_extent() -> Result<Vec<f32>, Error> {
+  default_instance()?.get_visible_extent()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setProjection))]
+pub fn set_projection(
+  code: String,
+  extent: Vec<f32>,
+  max_resolution: f32,
+  resolutions: Option<Vec<f32>>,
+) -> Result<(), Error> {
+  default_instance()?.set_projection(code, extent, max_resolution, resolutions)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setGlobe))]
+pub fn set_globe(globe: bool) -> Result<(), Error> {
+  default_instance()?.set_globe(globe)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addPbfTileData))]
+pub async fn add_pbf_tile_data(
+  pbf: Vec<u8>,
+  tile_coord: Vec<u32>,
+  extent: Vec<f32>,
+) -> Result<(), Error> {
+  default_instance()?
+    .add_pbf_tile_data(DEFAULT_SOURCE.to_owned(), pbf, tile_coord, extent)
+    .await
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addRasterTileData))]
+pub async fn add_raster_tile_data(
+  pixels: Vec<u8>,
+  width: u32,
+  height: u32,
+  tile_coord: Vec<u32>,
+  extent: Vec<f32>,
+) -> Result<(), Error> {
+  default_instance()?
+    .add_raster_tile_data(
+      DEFAULT_SOURCE.to_owned(),
+      pixels,
+      width,
+      height,
+      tile_coord,
+      extent,
+    )
+    .await
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = setSourceFormat))]
+pub fn set_source_format(format: String) -> Result<(), Error> {
+  default_instance()?.set_source_format(DEFAULT_SOURCE.to_owned(), format)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addGeoJson))]
+pub fn add_geojson(geojson: String) -> Result<(), Error> {
+  default_instance()?.add_geojson(DEFAULT_SOURCE.to_owned(), geojson)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addGeoJsonTile))]
+pub async fn add_geojson_tile(tile_coord: Vec<u32>, extent: Vec<f32>) -> Result<(), Error> {
+  default_instance()?
+    .add_geojson_tile(DEFAULT_SOURCE.to_owned(), tile_coord, extent)
+    .await
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addPmTilesTile))]
+pub async fn add_pmtiles_tile(tile_coord: Vec<u32>, extent: Vec<f32>) -> Result<(), Error> {
+  default_instance()?
+    .add_pmtiles_tile(DEFAULT_SOURCE.to_owned(), tile_coord, extent)
+    .await
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = addFlatGeobufTile))]
+pub async fn add_flatgeobuf_tile(tile_coord: Vec<u32>, extent: Vec<f32>) -> Result<(), Error> {
+  default_instance()?
+    .add_flatgeobuf_tile(DEFAULT_SOURCE.to_owned(), tile_coord, extent)
+    .await
 }
 
-pub async fn init<W: renderer::ToSurface>(window: &W, size: (u32, u32)) {
-  let renderer = renderer::Renderer::new(window, size).await;
+/// creates the default instance used by the free functions, replaces a previous one
+pub async fn init<W: renderer::ToSurface>(
+  window: &W,
+  size: (u32, u32),
+  sample_count: Option<u32>,
+) -> Result<(), Error> {
+  let instance = Instance::new(window, size, sample_count).await?;
+  instance.add_source(DEFAULT_SOURCE.to_owned());
+  instance.add_layer(DEFAULT_SOURCE.to_owned(), DEFAULT_SOURCE.to_owned(), None)?;
+  DEFAULT_INSTANCE.with(|default_instance| default_instance.replace(Some(instance)));
 
-  INSTANCE.with(|instance| {
-    instance.renderer.replace(Some(renderer));
-    instance.tiles.replace(Vec::new());
-    instance.current_size.replace(size);
-  });
+  Ok(())
 }
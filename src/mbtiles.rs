@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+/// fields of the metadata table needed to show the tiles
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+  pub name: Option<String>,
+
+  /// [west, south, east, north] in EPSG:4326
+  pub bounds: Option<[f64; 4]>,
+
+  pub min_zoom: Option<u32>,
+
+  pub max_zoom: Option<u32>,
+
+  /// ids of the vector_layers in the json row, the source layers of the tiles
+  pub vector_layers: Vec<String>,
+}
+
+impl Metadata {
+  fn read(connection: &Connection) -> Result<Self, String> {
+    let mut statement = connection
+      .prepare("SELECT name, value FROM metadata")
+      .map_err(|err| err.to_string())?;
+    let rows = statement
+      .query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+      })
+      .map_err(|err| err.to_string())?;
+
+    let mut metadata = Self::default();
+    for row in rows {
+      let (name, value) = row.map_err(|err| err.to_string())?;
+      match name.as_str() {
+        "name" => metadata.name = Some(value),
+        "format" if value != "pbf" => return Err(format!("unsupported tile format {value}")),
+        "bounds" => metadata.bounds = parse_bounds(&value),
+        "minzoom" => metadata.min_zoom = value.trim().parse().ok(),
+        "maxzoom" => metadata.max_zoom = value.trim().parse().ok(),
+        "json" => metadata.vector_layers = parse_vector_layers(&value)?,
+        _ => (),
+      }
+    }
+    Ok(metadata)
+  }
+}
+
+/// MBTiles file of vector tiles, e.g. for offline use
+pub struct MbTiles {
+  connection: Connection,
+
+  metadata: Metadata,
+}
+
+impl MbTiles {
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+      .map_err(|err| err.to_string())?;
+    let metadata = Metadata::read(&connection)?;
+
+    Ok(Self {
+      connection,
+      metadata,
+    })
+  }
+
+  pub fn get_metadata(&self) -> &Metadata {
+    &self.metadata
+  }
+
+  /// tile data for the xyz tile coordinate as stored, usually gzip compressed. none if
+  /// the file has no tile at the coordinate. rows are stored with tms y
+  pub fn get_tile(&self, z: u32, x: u32, y: u32) -> Result<Option<Vec<u8>>, String> {
+    let Metadata {
+      min_zoom, max_zoom, ..
+    } = self.metadata;
+    if z >= 32
+      || y >= 1 << z
+      || min_zoom.is_some_and(|min| z < min)
+      || max_zoom.is_some_and(|max| z > max)
+    {
+      return Ok(None);
+    }
+    let tms_y = (1 << z) - 1 - y;
+
+    self
+      .connection
+      .query_row(
+        "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+        [z, x, tms_y],
+        |row| row.get(0),
+      )
+      .optional()
+      .map_err(|err| err.to_string())
+  }
+}
+
+/// "west,south,east,north"
+fn parse_bounds(value: &str) -> Option<[f64; 4]> {
+  let bounds: Vec<f64> = value
+    .split(',')
+    .map(|coord| coord.trim().parse())
+    .collect::<Result<_, _>>()
+    .ok()?;
+  bounds.try_into().ok()
+}
+
+fn parse_vector_layers(value: &str) -> Result<Vec<String>, String> {
+  let json: serde_json::Value = serde_json::from_str(value).map_err(|err| err.to_string())?;
+  let Some(vector_layers) = json
+    .get("vector_layers")
+    .and_then(|layers| layers.as_array())
+  else {
+    return Ok(Vec::new());
+  };
+  Ok(
+    vector_layers
+      .iter()
+      .filter_map(|layer| layer.get("id")?.as_str().map(str::to_owned))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_file(path: &Path, tile: &[u8]) {
+    let connection = Connection::open(path).unwrap();
+    connection
+      .execute_batch(
+        "CREATE TABLE metadata (name TEXT, value TEXT);
+        CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+        INSERT INTO metadata VALUES ('name', 'osm'), ('format', 'pbf'), ('minzoom', '0'),
+          ('maxzoom', '14'), ('bounds', '-180.0,-85.05,180.0,85.05'),
+          ('json', '{\"vector_layers\": [{\"id\": \"water\"}, {\"id\": \"roads\"}]}');",
+      )
+      .unwrap();
+    connection
+      .execute("INSERT INTO tiles VALUES (4, 8, 10, ?1)", [tile])
+      .unwrap();
+  }
+
+  #[test]
+  fn read_tiles() {
+    let path = std::env::temp_dir().join(format!("wgpu-layers-{}.mbtiles", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    create_file(&path, b"tile");
+
+    let mbtiles = MbTiles::open(&path).unwrap();
+    assert_eq!(
+      mbtiles.get_metadata(),
+      &Metadata {
+        name: Some("osm".to_owned()),
+        bounds: Some([-180.0, -85.05, 180.0, 85.05]),
+        min_zoom: Some(0),
+        max_zoom: Some(14),
+        vector_layers: vec!["water".to_owned(), "roads".to_owned()],
+      }
+    );
+    // tms row 10 is xyz row 5 at zoom 4
+    assert_eq!(mbtiles.get_tile(4, 8, 5).unwrap(), Some(b"tile".to_vec()));
+    assert_eq!(mbtiles.get_tile(4, 8, 10).unwrap(), None);
+    assert_eq!(mbtiles.get_tile(15, 0, 0).unwrap(), None);
+
+    drop(mbtiles);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
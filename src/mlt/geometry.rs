@@ -0,0 +1,162 @@
+use geo_types::{
+  Coord, Geometry, LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon,
+};
+
+use super::stream::{Reader, StreamType, dictionary, length, offset};
+
+const POINT: i64 = 0;
+const LINE_STRING: i64 = 1;
+const POLYGON: i64 = 2;
+const MULTI_POINT: i64 = 3;
+const MULTI_LINE_STRING: i64 = 4;
+const MULTI_POLYGON: i64 = 5;
+
+/// lengths of the topology streams, consumed in feature order
+struct Lengths {
+  name: &'static str,
+
+  values: Vec<usize>,
+
+  position: usize,
+}
+
+impl Lengths {
+  fn new(name: &'static str) -> Self {
+    Self {
+      name,
+      values: Vec::new(),
+      position: 0,
+    }
+  }
+
+  fn next(&mut self) -> Result<usize, String> {
+    let value = self
+      .values
+      .get(self.position)
+      .ok_or_else(|| format!("missing {} length", self.name))?;
+    self.position += 1;
+    Ok(*value)
+  }
+}
+
+/// vertex buffer, optionally referenced through a vertex dictionary
+struct Vertices {
+  vertices: Vec<[i32; 2]>,
+
+  offsets: Option<Vec<usize>>,
+
+  position: usize,
+
+  /// layer extent to the tile size of the renderer
+  scale: f32,
+}
+
+impl Vertices {
+  fn next(&mut self) -> Result<Coord<f32>, String> {
+    let index = match &self.offsets {
+      Some(offsets) => offsets.get(self.position).copied(),
+      None => Some(self.position),
+    };
+    let [x, y] = index
+      .and_then(|index| self.vertices.get(index))
+      .ok_or("missing vertex")?;
+    self.position += 1;
+    Ok(Coord {
+      x: *x as f32 * self.scale,
+      y: *y as f32 * self.scale,
+    })
+  }
+
+  fn next_line(&mut self, count: usize) -> Result<LineString<f32>, String> {
+    (0..count).map(|_| self.next()).collect()
+  }
+
+  /// the closing vertex isn't stored
+  fn next_ring(&mut self, count: usize) -> Result<LineString<f32>, String> {
+    let mut ring = self.next_line(count)?;
+    ring.close();
+    Ok(ring)
+  }
+}
+
+/// geometry column with the geometry type stream first, then the topology and vertex
+/// streams. pre-tessellated polygons are tessellated again by the bucket builders
+pub fn decode(reader: &mut Reader, scale: f32) -> Result<Vec<Geometry<f32>>, String> {
+  let num_streams = reader.read_varint()?;
+  let types = reader.read_stream()?.decode_ints(false)?;
+
+  let mut geometries = Lengths::new("geometry");
+  let mut parts = Lengths::new("part");
+  let mut rings = Lengths::new("ring");
+  let mut has_rings = false;
+  let mut vertices = Vertices {
+    vertices: Vec::new(),
+    offsets: None,
+    position: 0,
+    scale,
+  };
+
+  for _ in 1..num_streams {
+    let stream = reader.read_stream()?;
+    match stream.stream_type {
+      StreamType::Length(length::GEOMETRIES) => geometries.values = stream.decode_indices()?,
+      StreamType::Length(length::PARTS) => parts.values = stream.decode_indices()?,
+      StreamType::Length(length::RINGS) => {
+        rings.values = stream.decode_indices()?;
+        has_rings = true;
+      }
+      StreamType::Length(length::TRIANGLES) | StreamType::Offset(offset::INDEX) => (),
+      StreamType::Offset(offset::VERTEX) => vertices.offsets = Some(stream.decode_indices()?),
+      StreamType::Data(dictionary::VERTEX) => vertices.vertices = stream.decode_vertices()?,
+      StreamType::Data(dictionary::MORTON) => {
+        return Err("unsupported morton vertex dictionary".to_owned());
+      }
+      stream_type => return Err(format!("unexpected geometry stream {stream_type:?}")),
+    }
+  }
+
+  // lines take their vertex count from the ring lengths if the column has polygons
+  let next_line = |parts: &mut Lengths, rings: &mut Lengths, vertices: &mut Vertices| {
+    let count = if has_rings {
+      rings.next()?
+    } else {
+      parts.next()?
+    };
+    vertices.next_line(count)
+  };
+  let next_polygon = |parts: &mut Lengths, rings: &mut Lengths, vertices: &mut Vertices| {
+    let mut polygon = (0..parts.next()?)
+      .map(|_| vertices.next_ring(rings.next()?))
+      .collect::<Result<Vec<_>, String>>()?
+      .into_iter();
+    let exterior = polygon.next().ok_or("polygon without rings")?;
+    Ok::<_, String>(Polygon::new(exterior, polygon.collect()))
+  };
+
+  types
+    .into_iter()
+    .map(|geometry_type| {
+      Ok(match geometry_type {
+        POINT => Geometry::Point(Point(vertices.next()?)),
+        LINE_STRING => Geometry::LineString(next_line(&mut parts, &mut rings, &mut vertices)?),
+        POLYGON => Geometry::Polygon(next_polygon(&mut parts, &mut rings, &mut vertices)?),
+        MULTI_POINT => Geometry::MultiPoint(MultiPoint(
+          (0..geometries.next()?)
+            .map(|_| vertices.next().map(Point))
+            .collect::<Result<_, String>>()?,
+        )),
+        MULTI_LINE_STRING => Geometry::MultiLineString(MultiLineString(
+          (0..geometries.next()?)
+            .map(|_| next_line(&mut parts, &mut rings, &mut vertices))
+            .collect::<Result<_, String>>()?,
+        )),
+        MULTI_POLYGON => Geometry::MultiPolygon(MultiPolygon(
+          (0..geometries.next()?)
+            .map(|_| next_polygon(&mut parts, &mut rings, &mut vertices))
+            .collect::<Result<_, String>>()?,
+        )),
+        geometry_type => return Err(format!("invalid geometry type {geometry_type}")),
+      })
+    })
+    .collect()
+}
@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+
+use mvt_reader::feature::{Feature, Value};
+
+use stream::{Reader, StreamType, dictionary, length, offset};
+
+mod geometry;
+mod stream;
+
+/// tag of the blocks with a layer, other blocks are skipped
+const LAYER_TAG: u64 = 1;
+
+/// tile size the geometry is scaled to, like the extent of most MVT tiles
+const TILE_SIZE: f32 = 4096.0;
+
+/// limit of values per stream, so a corrupt header can't allocate too much
+const MAX_VALUES: usize = 1 << 24;
+
+/// decoded layer of a MapLibre Tile
+pub struct Layer {
+  pub name: String,
+
+  pub features: Vec<Feature>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+  Bool,
+  Int8,
+  UInt8,
+  Int32,
+  UInt32,
+  Int64,
+  UInt64,
+  Float,
+  Double,
+  String,
+}
+
+/// column of the embedded layer metadata
+#[derive(Debug)]
+enum Column {
+  Id {
+    nullable: bool,
+  },
+  Geometry,
+  Property {
+    name: String,
+    scalar: Scalar,
+    nullable: bool,
+  },
+}
+
+impl Column {
+  /// type codes 0 to 3 are ids, 4 is the geometry and scalar properties start at 10
+  /// with a nullable variant of each type
+  fn read(reader: &mut Reader) -> Result<Self, String> {
+    let type_code = reader.read_varint()?;
+    Ok(match type_code {
+      0..=3 => Column::Id {
+        nullable: type_code & 1 == 1,
+      },
+      4 => Column::Geometry,
+      10..=29 => {
+        let scalar = match (type_code - 10) / 2 {
+          0 => Scalar::Bool,
+          1 => Scalar::Int8,
+          2 => Scalar::UInt8,
+          3 => Scalar::Int32,
+          4 => Scalar::UInt32,
+          5 => Scalar::Int64,
+          6 => Scalar::UInt64,
+          7 => Scalar::Float,
+          8 => Scalar::Double,
+          _ => Scalar::String,
+        };
+        Column::Property {
+          name: reader.read_string()?,
+          scalar,
+          nullable: (type_code - 10) & 1 == 1,
+        }
+      }
+      type_code => return Err(format!("unsupported column type {type_code}")),
+    })
+  }
+}
+
+/// layers of a MapLibre Tile with embedded metadata. only the properties accepted by
+/// keep_property are decoded, the streams of the others are skipped. streams with
+/// FastPFOR, ALP, FSST or morton encoding aren't supported
+pub fn decode(data: &[u8], keep_property: impl Fn(&str) -> bool) -> Result<Vec<Layer>, String> {
+  let mut reader = Reader::new(data);
+  let mut layers = Vec::new();
+
+  while !reader.is_empty() {
+    let length = reader.read_varint()? as usize;
+    let mut block = Reader::new(reader.read_bytes(length)?);
+    if block.read_varint()? == LAYER_TAG {
+      layers.push(decode_layer(&mut block, &keep_property)?);
+    }
+  }
+  Ok(layers)
+}
+
+fn decode_layer(
+  reader: &mut Reader,
+  keep_property: impl Fn(&str) -> bool,
+) -> Result<Layer, String> {
+  let name = reader.read_string()?;
+  let extent = reader.read_varint()?;
+  if extent == 0 {
+    return Err(format!("invalid extent of layer {name}"));
+  }
+  let column_count = reader.read_varint()?;
+  let columns = (0..column_count)
+    .map(|_| Column::read(reader))
+    .collect::<Result<Vec<_>, String>>()?;
+
+  let mut ids = None;
+  let mut geometries = None;
+  let mut properties = Vec::new();
+  for column in columns {
+    match column {
+      Column::Id { nullable } => {
+        ids = Some(read_nullable(reader, nullable, |stream| {
+          Ok(
+            stream
+              .decode_ints(false)?
+              .into_iter()
+              .map(|id| id as u64)
+              .collect(),
+          )
+        })?);
+      }
+      Column::Geometry => {
+        geometries = Some(geometry::decode(reader, TILE_SIZE / extent as f32)?);
+      }
+      Column::Property {
+        name,
+        scalar,
+        nullable,
+      } => {
+        if keep_property(&name) {
+          properties.push((name, read_property(reader, scalar, nullable)?));
+        } else {
+          skip_property(reader, scalar, nullable)?;
+        }
+      }
+    }
+  }
+
+  let geometries = geometries.ok_or_else(|| format!("layer {name} has no geometry"))?;
+  let count = geometries.len();
+  if ids.as_ref().is_some_and(|ids| ids.len() != count)
+    || properties.iter().any(|(_, values)| values.len() != count)
+  {
+    return Err(format!("columns of layer {name} differ in length"));
+  }
+
+  let mut ids = ids.map(Vec::into_iter);
+  let mut properties: Vec<_> = properties
+    .into_iter()
+    .map(|(name, values)| (name, values.into_iter()))
+    .collect();
+  let features = geometries
+    .into_iter()
+    .map(|geometry| Feature {
+      geometry,
+      id: ids.as_mut().and_then(|ids| ids.next().flatten()),
+      properties: Some(
+        properties
+          .iter_mut()
+          .filter_map(|(name, values)| Some((name.clone(), values.next().flatten()?)))
+          .collect::<HashMap<_, _>>(),
+      ),
+    })
+    .collect();
+
+  Ok(Layer { name, features })
+}
+
+/// optional present stream followed by the data stream
+fn read_nullable<T>(
+  reader: &mut Reader,
+  nullable: bool,
+  decode: impl FnOnce(&stream::Stream) -> Result<Vec<T>, String>,
+) -> Result<Vec<Option<T>>, String> {
+  let present = if nullable {
+    Some(reader.read_stream()?.decode_bools()?)
+  } else {
+    None
+  };
+  let values = decode(&reader.read_stream()?)?;
+  apply_present(present, values)
+}
+
+/// values are only stored for present features
+fn apply_present<T>(present: Option<Vec<bool>>, values: Vec<T>) -> Result<Vec<Option<T>>, String> {
+  let Some(present) = present else {
+    return Ok(values.into_iter().map(Some).collect());
+  };
+  let mut values = values.into_iter();
+  present
+    .into_iter()
+    .map(|present| {
+      if present {
+        values
+          .next()
+          .map(Some)
+          .ok_or_else(|| "missing value".to_owned())
+      } else {
+        Ok(None)
+      }
+    })
+    .collect()
+}
+
+fn read_property(
+  reader: &mut Reader,
+  scalar: Scalar,
+  nullable: bool,
+) -> Result<Vec<Option<Value>>, String> {
+  let ints = |signed: bool, to_value: fn(i64) -> Value| {
+    move |stream: &stream::Stream| -> Result<Vec<Value>, String> {
+      Ok(
+        stream
+          .decode_ints(signed)?
+          .into_iter()
+          .map(to_value)
+          .collect(),
+      )
+    }
+  };
+
+  match scalar {
+    Scalar::Bool => read_nullable(reader, nullable, |stream| {
+      Ok(
+        stream
+          .decode_bools()?
+          .into_iter()
+          .map(Value::Bool)
+          .collect(),
+      )
+    }),
+    Scalar::Int8 | Scalar::Int32 | Scalar::Int64 => {
+      read_nullable(reader, nullable, ints(true, Value::SInt))
+    }
+    Scalar::UInt8 | Scalar::UInt32 | Scalar::UInt64 => read_nullable(
+      reader,
+      nullable,
+      ints(false, |value| Value::UInt(value as u64)),
+    ),
+    Scalar::Float => read_nullable(reader, nullable, |stream| {
+      Ok(
+        stream
+          .decode_f32s()?
+          .into_iter()
+          .map(Value::Float)
+          .collect(),
+      )
+    }),
+    Scalar::Double => read_nullable(reader, nullable, |stream| {
+      Ok(
+        stream
+          .decode_f64s()?
+          .into_iter()
+          .map(Value::Double)
+          .collect(),
+      )
+    }),
+    Scalar::String => read_strings(reader),
+  }
+}
+
+/// plain strings with a length and a data stream, or a dictionary with an offset per
+/// feature. the present stream comes first if there is one
+fn read_strings(reader: &mut Reader) -> Result<Vec<Option<Value>>, String> {
+  let num_streams = reader.read_varint()?;
+  let mut present = None;
+  let mut offsets = None;
+  let mut lengths = None;
+  let mut data = None;
+
+  for _ in 0..num_streams {
+    let stream = reader.read_stream()?;
+    match stream.stream_type {
+      StreamType::Present => present = Some(stream.decode_bools()?),
+      StreamType::Offset(offset::STRING) => offsets = Some(stream.decode_indices()?),
+      StreamType::Length(length::VAR_BINARY | length::DICTIONARY) => {
+        lengths = Some(stream.decode_indices()?)
+      }
+      StreamType::Data(dictionary::NONE | dictionary::SINGLE) => data = Some(stream.get_bytes()),
+      stream_type => return Err(format!("unsupported string stream {stream_type:?}")),
+    }
+  }
+
+  let (Some(lengths), Some(data)) = (lengths, data) else {
+    return Err("string column without lengths or data".to_owned());
+  };
+  let mut position = 0usize;
+  let strings = lengths
+    .into_iter()
+    .map(|length| {
+      let bytes = position
+        .checked_add(length)
+        .and_then(|end| data.get(position..end))
+        .ok_or("invalid string length")?;
+      position += length;
+      String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string())
+    })
+    .collect::<Result<Vec<_>, String>>()?;
+
+  let values = match offsets {
+    Some(offsets) => offsets
+      .into_iter()
+      .map(|offset| strings.get(offset).cloned().map(Value::String))
+      .collect::<Option<Vec<_>>>()
+      .ok_or("invalid dictionary offset")?,
+    None => strings.into_iter().map(Value::String).collect(),
+  };
+  apply_present(present, values)
+}
+
+/// only the stream headers are read
+fn skip_property(reader: &mut Reader, scalar: Scalar, nullable: bool) -> Result<(), String> {
+  let num_streams = match scalar {
+    Scalar::String => reader.read_varint()?,
+    _ if nullable => 2,
+    _ => 1,
+  };
+  for _ in 0..num_streams {
+    reader.read_stream()?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use geo_types::{Geometry, LineString, Point, Polygon, line_string};
+
+  use super::*;
+  use crate::test_utils::write_varint;
+
+  fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value.as_bytes());
+  }
+
+  fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+  }
+
+  /// varint stream with the given types and logical techniques
+  fn write_stream(bytes: &mut Vec<u8>, types: u8, logical: u8, values: &[u64]) {
+    let mut data = Vec::new();
+    for value in values {
+      write_varint(&mut data, *value);
+    }
+    bytes.push(types);
+    bytes.push(logical << 5 | 2);
+    write_varint(bytes, values.len() as u64);
+    write_varint(bytes, data.len() as u64);
+    bytes.extend(data);
+  }
+
+  fn write_raw_stream(bytes: &mut Vec<u8>, types: u8, num_values: usize, data: &[u8]) {
+    bytes.push(types);
+    bytes.push(0);
+    write_varint(bytes, num_values as u64);
+    write_varint(bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+  }
+
+  /// a point, a line and a polygon with an id, a skipped nullable string property and a
+  /// rle delta encoded unsigned property
+  fn create_tile() -> Vec<u8> {
+    let mut layer = Vec::new();
+    write_varint(&mut layer, LAYER_TAG);
+    write_string(&mut layer, "roads");
+    write_varint(&mut layer, 8192);
+    write_varint(&mut layer, 4);
+    layer.extend([0, 4, 29]);
+    write_string(&mut layer, "class");
+    layer.push(18);
+    write_string(&mut layer, "lanes");
+
+    // ids 7, 8, 9
+    write_stream(&mut layer, 0x10, 1, &[zigzag(7), zigzag(1), zigzag(1)]);
+
+    // geometry: types, parts, rings and componentwise delta vertices
+    write_varint(&mut layer, 4);
+    write_stream(&mut layer, 0x10, 0, &[0, 1, 2]);
+    write_stream(&mut layer, 0x30 | length::PARTS, 0, &[1]);
+    write_stream(&mut layer, 0x30 | length::RINGS, 0, &[2, 4]);
+    let vertices: [i64; 14] = [10, 20, 0, 0, 100, 100, 0, 0, 100, 0, 0, 100, -100, 0];
+    let vertices: Vec<u64> = vertices.into_iter().map(zigzag).collect();
+    write_stream(&mut layer, 0x10 | dictionary::VERTEX, 2, &vertices);
+
+    // class: present for the first and last feature, dictionary encoded
+    write_varint(&mut layer, 4);
+    write_raw_stream(&mut layer, 0x00, 3, &[0xff, 0b101]);
+    write_stream(&mut layer, 0x20 | offset::STRING, 0, &[0, 0]);
+    write_stream(&mut layer, 0x30 | length::DICTIONARY, 0, &[7]);
+    write_raw_stream(&mut layer, 0x10 | dictionary::SINGLE, 1, b"primary");
+
+    // lanes: 2, 2, 2 as rle runs of the deltas 2, 0
+    layer.extend([0x10, 1 << 5 | 3 << 2 | 2, 4, 4, 2, 3]);
+    layer.extend([1, 2, zigzag(2) as u8, 0]);
+
+    let mut tile = Vec::new();
+    write_varint(&mut tile, layer.len() as u64);
+    tile.extend(layer);
+    // unknown blocks are skipped
+    tile.extend([2, 9, 0]);
+    tile
+  }
+
+  #[test]
+  fn decode_layer() {
+    let layers = decode(&create_tile(), |name| name == "lanes").unwrap();
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].name, "roads");
+
+    let features = &layers[0].features;
+    let ids: Vec<_> = features.iter().map(|feature| feature.id).collect();
+    assert_eq!(ids, [Some(7), Some(8), Some(9)]);
+
+    // coordinates are scaled from the extent 8192 to 4096
+    assert_eq!(features[0].geometry, Geometry::Point(Point::new(5.0, 10.0)));
+    assert_eq!(
+      features[1].geometry,
+      Geometry::LineString(line_string![(x: 5.0, y: 10.0), (x: 55.0, y: 60.0)])
+    );
+    assert_eq!(
+      features[2].geometry,
+      Geometry::Polygon(Polygon::new(
+        LineString::from(vec![
+          (55.0, 60.0),
+          (105.0, 60.0),
+          (105.0, 110.0),
+          (55.0, 110.0),
+          (55.0, 60.0)
+        ]),
+        vec![],
+      ))
+    );
+
+    for feature in features {
+      let properties = feature.properties.as_ref().unwrap();
+      assert_eq!(properties.len(), 1);
+      assert_eq!(properties.get("lanes"), Some(&Value::UInt(2)));
+    }
+  }
+
+  #[test]
+  fn decode_dictionary_strings() {
+    let layers = decode(&create_tile(), |name| name == "class").unwrap();
+    let classes: Vec<_> = layers[0]
+      .features
+      .iter()
+      .map(|feature| feature.properties.as_ref().unwrap().get("class").cloned())
+      .collect();
+    let primary = Some(Value::String("primary".to_owned()));
+    assert_eq!(classes, [primary.clone(), None, primary]);
+  }
+}
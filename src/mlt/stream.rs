@@ -0,0 +1,327 @@
+/// logical subtype of data streams
+pub mod dictionary {
+  pub const NONE: u8 = 0;
+  pub const SINGLE: u8 = 1;
+  pub const VERTEX: u8 = 3;
+  pub const MORTON: u8 = 4;
+}
+
+/// logical subtype of offset streams
+pub mod offset {
+  pub const VERTEX: u8 = 0;
+  pub const INDEX: u8 = 1;
+  pub const STRING: u8 = 2;
+}
+
+/// logical subtype of length streams
+pub mod length {
+  pub const VAR_BINARY: u8 = 0;
+  pub const GEOMETRIES: u8 = 1;
+  pub const PARTS: u8 = 2;
+  pub const RINGS: u8 = 3;
+  pub const TRIANGLES: u8 = 4;
+  pub const DICTIONARY: u8 = 6;
+}
+
+const LOGICAL_NONE: u8 = 0;
+const LOGICAL_DELTA: u8 = 1;
+const LOGICAL_COMPONENTWISE_DELTA: u8 = 2;
+const LOGICAL_RLE: u8 = 3;
+const LOGICAL_MORTON: u8 = 4;
+
+const PHYSICAL_NONE: u8 = 0;
+const PHYSICAL_VARINT: u8 = 2;
+
+/// physical type of a stream with the logical subtype of data, offset and length streams
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamType {
+  Present,
+  Data(u8),
+  Offset(u8),
+  Length(u8),
+}
+
+/// stream of a column, the data is decoded on demand so skipped columns cost nothing
+#[derive(Debug)]
+pub struct Stream<'a> {
+  pub stream_type: StreamType,
+
+  /// logical level techniques, the second one is only used after delta
+  logical: [u8; 2],
+
+  physical: u8,
+
+  num_values: usize,
+
+  /// number of runs if a logical technique is rle
+  runs: Option<usize>,
+
+  data: &'a [u8],
+}
+
+impl<'a> Stream<'a> {
+  /// integers with zigzag decoding for signed values and delta encoded streams
+  pub fn decode_ints(&self, signed: bool) -> Result<Vec<i64>, String> {
+    let mut values = self.get_physical_values()?;
+
+    if let Some(runs) = self.runs {
+      values = expand_runs(&values, runs)?;
+    }
+
+    let delta = match self.logical {
+      [LOGICAL_NONE | LOGICAL_RLE, _] => false,
+      [LOGICAL_DELTA, LOGICAL_NONE | LOGICAL_RLE] => true,
+      logical => return Err(format!("unsupported logical technique {logical:?}")),
+    };
+    let mut values: Vec<i64> = if signed || delta {
+      values.into_iter().map(zigzag).collect()
+    } else {
+      values.into_iter().map(|value| value as i64).collect()
+    };
+    if delta {
+      for i in 1..values.len() {
+        values[i] = values[i].wrapping_add(values[i - 1]);
+      }
+    }
+    Ok(values)
+  }
+
+  /// counts or indices, which are never negative
+  pub fn decode_indices(&self) -> Result<Vec<usize>, String> {
+    self
+      .decode_ints(false)?
+      .into_iter()
+      .map(|value| usize::try_from(value).map_err(|_| format!("invalid index {value}")))
+      .collect()
+  }
+
+  /// x and y pairs, delta encoded per component
+  pub fn decode_vertices(&self) -> Result<Vec<[i32; 2]>, String> {
+    if self.logical[0] == LOGICAL_MORTON {
+      return Err("unsupported morton encoded vertices".to_owned());
+    }
+    let values: Vec<i64> = self
+      .get_physical_values()?
+      .into_iter()
+      .map(zigzag)
+      .collect();
+
+    let mut vertices: Vec<[i32; 2]> = values
+      .chunks_exact(2)
+      .map(|pair| [pair[0] as i32, pair[1] as i32])
+      .collect();
+    if self.logical[0] == LOGICAL_COMPONENTWISE_DELTA {
+      for i in 1..vertices.len() {
+        vertices[i][0] = vertices[i][0].wrapping_add(vertices[i - 1][0]);
+        vertices[i][1] = vertices[i][1].wrapping_add(vertices[i - 1][1]);
+      }
+    }
+    Ok(vertices)
+  }
+
+  /// bitset compressed with byte rle, least significant bit first
+  pub fn decode_bools(&self) -> Result<Vec<bool>, String> {
+    let bytes = decode_byte_rle(self.data, self.num_values.div_ceil(8))?;
+    Ok(
+      (0..self.num_values)
+        .map(|i| bytes[i / 8] >> (i % 8) & 1 == 1)
+        .collect(),
+    )
+  }
+
+  pub fn decode_f32s(&self) -> Result<Vec<f32>, String> {
+    self.check_length(4)?;
+    Ok(
+      self
+        .data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect(),
+    )
+  }
+
+  pub fn decode_f64s(&self) -> Result<Vec<f64>, String> {
+    self.check_length(8)?;
+    Ok(
+      self
+        .data
+        .chunks_exact(8)
+        .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+        .collect(),
+    )
+  }
+
+  pub fn get_bytes(&self) -> &'a [u8] {
+    self.data
+  }
+
+  fn check_length(&self, size: usize) -> Result<(), String> {
+    if self.data.len() != self.num_values * size {
+      return Err("invalid stream length".to_owned());
+    }
+    Ok(())
+  }
+
+  /// values before the logical decoding, only varint and little endian u32 are supported
+  fn get_physical_values(&self) -> Result<Vec<u64>, String> {
+    match self.physical {
+      PHYSICAL_VARINT => {
+        let mut reader = Reader::new(self.data);
+        let mut values = Vec::with_capacity(self.num_values);
+        while !reader.is_empty() {
+          values.push(reader.read_varint()?);
+        }
+        Ok(values)
+      }
+      PHYSICAL_NONE => {
+        self.check_length(4)?;
+        Ok(
+          self
+            .data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as u64)
+            .collect(),
+        )
+      }
+      physical => Err(format!("unsupported physical technique {physical}")),
+    }
+  }
+}
+
+/// run lengths followed by the values
+fn expand_runs(values: &[u64], runs: usize) -> Result<Vec<u64>, String> {
+  if runs.checked_mul(2) != Some(values.len()) {
+    return Err("invalid rle stream".to_owned());
+  }
+  let (lengths, values) = values.split_at(runs);
+  let count = lengths
+    .iter()
+    .try_fold(0u64, |count, length| count.checked_add(*length))
+    .filter(|count| *count <= super::MAX_VALUES as u64)
+    .ok_or("too many values")? as usize;
+
+  let mut expanded = Vec::with_capacity(count);
+  for (length, value) in lengths.iter().zip(values) {
+    expanded.extend(std::iter::repeat_n(*value, *length as usize));
+  }
+  Ok(expanded)
+}
+
+/// ORC byte rle, a control byte below 128 repeats the next byte control + 3 times,
+/// otherwise 256 - control literal bytes follow
+fn decode_byte_rle(data: &[u8], count: usize) -> Result<Vec<u8>, String> {
+  let invalid = || "invalid byte rle stream".to_owned();
+  let mut bytes = Vec::with_capacity(count);
+  let mut position = 0;
+
+  while bytes.len() < count {
+    let control = *data.get(position).ok_or_else(invalid)?;
+    position += 1;
+    if control < 128 {
+      let byte = *data.get(position).ok_or_else(invalid)?;
+      position += 1;
+      bytes.extend(std::iter::repeat_n(byte, control as usize + 3));
+    } else {
+      let length = 256 - control as usize;
+      bytes.extend_from_slice(data.get(position..position + length).ok_or_else(invalid)?);
+      position += length;
+    }
+  }
+  bytes.truncate(count);
+  Ok(bytes)
+}
+
+fn zigzag(value: u64) -> i64 {
+  (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// cursor over the bytes of a tile
+pub struct Reader<'a> {
+  bytes: &'a [u8],
+
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes, position: 0 }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.position >= self.bytes.len()
+  }
+
+  pub fn read_varint(&mut self) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+      let byte = self.read_bytes(1)?[0];
+      value |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    Err("varint too long".to_owned())
+  }
+
+  pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+    let bytes = self
+      .position
+      .checked_add(length)
+      .and_then(|end| self.bytes.get(self.position..end))
+      .ok_or("unexpected end of tile")?;
+    self.position += length;
+    Ok(bytes)
+  }
+
+  /// length prefixed utf-8
+  pub fn read_string(&mut self) -> Result<String, String> {
+    let length = self.read_varint()? as usize;
+    String::from_utf8(self.read_bytes(length)?.to_vec()).map_err(|err| err.to_string())
+  }
+
+  /// stream metadata followed by the stream data
+  pub fn read_stream(&mut self) -> Result<Stream<'a>, String> {
+    let types = self.read_bytes(1)?[0];
+    let subtype = types & 0x0f;
+    let stream_type = match types >> 4 {
+      0 => StreamType::Present,
+      1 => StreamType::Data(subtype),
+      2 => StreamType::Offset(subtype),
+      3 => StreamType::Length(subtype),
+      physical_type => return Err(format!("invalid stream type {physical_type}")),
+    };
+
+    let techniques = self.read_bytes(1)?[0];
+    let logical = [techniques >> 5, techniques >> 2 & 0x07];
+    let physical = techniques & 0x03;
+
+    let num_values = self.read_varint()? as usize;
+    let byte_length = self.read_varint()? as usize;
+    if num_values > super::MAX_VALUES {
+      return Err("too many values".to_owned());
+    }
+
+    let runs = if logical.contains(&LOGICAL_RLE) {
+      let runs = self.read_varint()? as usize;
+      // number of values after the runs are expanded
+      self.read_varint()?;
+      Some(runs)
+    } else {
+      None
+    };
+    if logical[0] == LOGICAL_MORTON {
+      // number of bits and coordinate shift
+      self.read_varint()?;
+      self.read_varint()?;
+    }
+
+    Ok(Stream {
+      stream_type,
+      logical,
+      physical,
+      num_values,
+      runs,
+      data: self.read_bytes(byte_length)?,
+    })
+  }
+}
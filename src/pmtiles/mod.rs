@@ -0,0 +1,453 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{compression, ressource::source::TileFormat};
+
+mod provider;
+
+pub use provider::RangeProvider;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use provider::FileProvider;
+
+#[cfg(target_arch = "wasm32")]
+pub use provider::FunctionProvider;
+
+const MAGIC: &[u8; 7] = b"PMTiles";
+
+const VERSION: u8 = 3;
+
+const HEADER_SIZE: usize = 127;
+
+/// header and root directory are always within the first bytes of the archive
+const INITIAL_FETCH_SIZE: u64 = 16384;
+
+/// root directory plus up to three levels of leaf directories
+const MAX_DIRECTORY_DEPTH: usize = 4;
+
+const TILE_TYPE_UNKNOWN: u8 = 0;
+
+const TILE_TYPE_MVT: u8 = 1;
+
+const TILE_TYPE_MLT: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Compression {
+  Unknown,
+  None,
+  Gzip,
+  Brotli,
+  Zstd,
+}
+
+impl Compression {
+  fn new(value: u8) -> Result<Self, String> {
+    match value {
+      0 => Ok(Compression::Unknown),
+      1 => Ok(Compression::None),
+      2 => Ok(Compression::Gzip),
+      3 => Ok(Compression::Brotli),
+      4 => Ok(Compression::Zstd),
+      _ => Err(format!("invalid compression {value}")),
+    }
+  }
+
+  /// unknown is treated as uncompressed
+  fn decompress(&self, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    match self {
+      Compression::Unknown | Compression::None => Ok(bytes),
+      Compression::Gzip => compression::gunzip(&bytes),
+      compression => Err(format!("unsupported compression {compression:?}")),
+    }
+  }
+}
+
+/// fields of the fixed size v3 header needed to read tiles, all offsets are absolute
+#[derive(Debug)]
+struct Header {
+  root_directory: (u64, u64),
+
+  leaf_directories_offset: u64,
+
+  tile_data_offset: u64,
+
+  internal_compression: Compression,
+
+  tile_compression: Compression,
+
+  min_zoom: u8,
+
+  max_zoom: u8,
+
+  /// none if the archive doesn't tell the format of its tiles
+  tile_format: Option<TileFormat>,
+}
+
+impl Header {
+  fn parse(bytes: &[u8]) -> Result<Self, String> {
+    if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+      return Err("not a PMTiles archive".to_owned());
+    }
+    if bytes[7] != VERSION {
+      return Err(format!("unsupported PMTiles version {}", bytes[7]));
+    }
+    let tile_format = match bytes[99] {
+      TILE_TYPE_UNKNOWN => None,
+      TILE_TYPE_MVT => Some(TileFormat::Mvt),
+      TILE_TYPE_MLT => Some(TileFormat::Mlt),
+      tile_type => return Err(format!("unsupported tile type {tile_type}")),
+    };
+
+    let get_u64 = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    Ok(Self {
+      root_directory: (get_u64(8), get_u64(16)),
+      leaf_directories_offset: get_u64(40),
+      tile_data_offset: get_u64(56),
+      internal_compression: Compression::new(bytes[97])?,
+      tile_compression: Compression::new(bytes[98])?,
+      min_zoom: bytes[100],
+      max_zoom: bytes[101],
+      tile_format,
+    })
+  }
+}
+
+/// entry of a directory, points to tile data or to a leaf directory if the run length
+/// is 0. offsets are relative to their section
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+  tile_id: u64,
+  offset: u64,
+  length: u64,
+  run_length: u64,
+}
+
+/// PMTiles v3 archive of vector tiles, read with range requests through the provider.
+/// leaf directories are cached after their first use
+pub struct PmTiles {
+  provider: Rc<dyn RangeProvider>,
+
+  header: Header,
+
+  root_directory: Rc<Vec<Entry>>,
+
+  leaf_directories: RefCell<HashMap<u64, Rc<Vec<Entry>>>>,
+}
+
+impl PmTiles {
+  pub async fn new(provider: Rc<dyn RangeProvider>) -> Result<Self, String> {
+    let bytes = provider.get_range(0, INITIAL_FETCH_SIZE).await?;
+    let header = Header::parse(&bytes)?;
+
+    let (offset, length) = header.root_directory;
+    let end = offset.checked_add(length).ok_or("invalid offset")?;
+    let initial_range = match (usize::try_from(offset), usize::try_from(end)) {
+      (Ok(start), Ok(end)) => bytes.get(start..end),
+      _ => None,
+    };
+    let root_directory = match initial_range {
+      Some(directory) => directory.to_vec(),
+      None => provider.get_range(offset, length).await?,
+    };
+    let root_directory = parse_directory(&header.internal_compression.decompress(root_directory)?)?;
+
+    Ok(Self {
+      provider,
+      header,
+      root_directory: Rc::new(root_directory),
+      leaf_directories: RefCell::new(HashMap::new()),
+    })
+  }
+
+  /// format of the tiles in the archive, none if the header doesn't tell it
+  pub(crate) fn get_tile_format(&self) -> Option<TileFormat> {
+    self.header.tile_format
+  }
+
+  /// decompressed tile data, none if the archive has no tile at the coordinate
+  pub async fn get_tile(&self, z: u32, x: u32, y: u32) -> Result<Option<Vec<u8>>, String> {
+    if z < self.header.min_zoom as u32 || z > self.header.max_zoom as u32 || z >= 32 {
+      return Ok(None);
+    }
+    let tile_id = zxy_to_tile_id(z, x, y);
+
+    let mut directory = self.root_directory.clone();
+    for _ in 0..MAX_DIRECTORY_DEPTH {
+      let Some(entry) = find_entry(&directory, tile_id) else {
+        return Ok(None);
+      };
+
+      if entry.run_length > 0 {
+        let offset = self
+          .header
+          .tile_data_offset
+          .checked_add(entry.offset)
+          .ok_or("invalid offset")?;
+        let bytes = self.provider.get_range(offset, entry.length).await?;
+        return self.header.tile_compression.decompress(bytes).map(Some);
+      }
+      directory = self.get_leaf_directory(&entry).await?;
+    }
+    Err("too many nested leaf directories".to_owned())
+  }
+
+  async fn get_leaf_directory(&self, entry: &Entry) -> Result<Rc<Vec<Entry>>, String> {
+    let offset = self
+      .header
+      .leaf_directories_offset
+      .checked_add(entry.offset)
+      .ok_or("invalid offset")?;
+    if let Some(directory) = self.leaf_directories.borrow().get(&offset) {
+      return Ok(directory.clone());
+    }
+
+    let bytes = self.provider.get_range(offset, entry.length).await?;
+    let directory = Rc::new(parse_directory(
+      &self.header.internal_compression.decompress(bytes)?,
+    )?);
+    self
+      .leaf_directories
+      .borrow_mut()
+      .insert(offset, directory.clone());
+    Ok(directory)
+  }
+}
+
+/// entries are stored column wise as varints, tile ids as deltas and offsets as 0 if
+/// the data directly follows the previous entry
+fn parse_directory(bytes: &[u8]) -> Result<Vec<Entry>, String> {
+  let mut position = 0;
+  let mut read = || read_varint(bytes, &mut position);
+
+  let count = read()? as usize;
+  // every entry needs at least 4 bytes, so a corrupt count can't allocate too much
+  if count > bytes.len() {
+    return Err("invalid directory".to_owned());
+  }
+  let mut entries = vec![
+    Entry {
+      tile_id: 0,
+      offset: 0,
+      length: 0,
+      run_length: 0,
+    };
+    count
+  ];
+
+  let mut tile_id = 0u64;
+  for entry in entries.iter_mut() {
+    tile_id = tile_id.checked_add(read()?).ok_or("invalid tile id")?;
+    entry.tile_id = tile_id;
+  }
+  for entry in entries.iter_mut() {
+    entry.run_length = read()?;
+  }
+  for entry in entries.iter_mut() {
+    entry.length = read()?;
+  }
+  for i in 0..count {
+    let value = read()?;
+    entries[i].offset = if value == 0 && i > 0 {
+      entries[i - 1]
+        .offset
+        .checked_add(entries[i - 1].length)
+        .ok_or("invalid offset")?
+    } else {
+      value.checked_sub(1).ok_or("invalid offset")?
+    };
+  }
+  Ok(entries)
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64, String> {
+  let mut value = 0u64;
+  for shift in (0..64).step_by(7) {
+    let byte = *bytes.get(*position).ok_or("unexpected end of directory")?;
+    *position += 1;
+    value |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err("varint too long".to_owned())
+}
+
+/// entry with the largest tile id not bigger than the searched one, if it covers the
+/// tile id or is a leaf directory
+fn find_entry(entries: &[Entry], tile_id: u64) -> Option<Entry> {
+  let index = entries.partition_point(|entry| entry.tile_id <= tile_id);
+  let entry = entries.get(index.checked_sub(1)?)?;
+  (entry.run_length == 0 || tile_id - entry.tile_id < entry.run_length).then_some(*entry)
+}
+
+/// tiles are numbered by zoom level and along a hilbert curve within a zoom level
+fn zxy_to_tile_id(z: u32, x: u32, y: u32) -> u64 {
+  let tiles_below = ((1u64 << (2 * z)) - 1) / 3;
+  let (mut x, mut y) = (x as u64, y as u64);
+  let mut d = 0;
+  let mut s = (1u64 << z) / 2;
+  while s > 0 {
+    let rx = ((x & s) > 0) as u64;
+    let ry = ((y & s) > 0) as u64;
+    d += s * s * ((3 * rx) ^ ry);
+    // rotate the quadrant
+    if ry == 0 {
+      if rx == 1 {
+        x = s - 1 - (x & (s - 1));
+        y = s - 1 - (y & (s - 1));
+      }
+      std::mem::swap(&mut x, &mut y);
+    }
+    s /= 2;
+  }
+  tiles_below + d
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::{gzip, write_varint};
+
+  fn write_directory(entries: &[Entry]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, entries.len() as u64);
+    let mut previous = 0;
+    for entry in entries {
+      write_varint(&mut bytes, entry.tile_id - previous);
+      previous = entry.tile_id;
+    }
+    for entry in entries {
+      write_varint(&mut bytes, entry.run_length);
+    }
+    for entry in entries {
+      write_varint(&mut bytes, entry.length);
+    }
+    for entry in entries {
+      write_varint(&mut bytes, entry.offset + 1);
+    }
+    bytes
+  }
+
+  fn entry(tile_id: u64, offset: u64, length: u64, run_length: u64) -> Entry {
+    Entry {
+      tile_id,
+      offset,
+      length,
+      run_length,
+    }
+  }
+
+  /// archive with the tiles 0/0/0 and 1/0/1 in the root directory and the tile 2/1/1 in
+  /// a leaf directory, tiles are gzipped and directories uncompressed
+  fn create_archive() -> Vec<u8> {
+    let tiles = [
+      gzip(b"tile 0", None),
+      gzip(b"tile 2", None),
+      gzip(b"tile 8", None),
+    ];
+    let leaf_tile_offset = (tiles[0].len() + tiles[1].len()) as u64;
+    let leaf = write_directory(&[entry(
+      zxy_to_tile_id(2, 1, 1),
+      leaf_tile_offset,
+      tiles[2].len() as u64,
+      1,
+    )]);
+    let root = write_directory(&[
+      entry(0, 0, tiles[0].len() as u64, 1),
+      entry(2, tiles[0].len() as u64, tiles[1].len() as u64, 1),
+      entry(5, 0, leaf.len() as u64, 0),
+    ]);
+
+    let root_offset = HEADER_SIZE as u64;
+    let leaf_offset = root_offset + root.len() as u64;
+    let tile_data_offset = leaf_offset + leaf.len() as u64;
+
+    let mut bytes = vec![0; HEADER_SIZE];
+    bytes[..7].copy_from_slice(MAGIC);
+    bytes[7] = VERSION;
+    for (offset, value) in [
+      (8, root_offset),
+      (16, root.len() as u64),
+      (40, leaf_offset),
+      (48, leaf.len() as u64),
+      (56, tile_data_offset),
+    ] {
+      bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+    bytes[97] = 1; // no internal compression
+    bytes[98] = 2; // gzipped tiles
+    bytes[99] = TILE_TYPE_MVT;
+    bytes[101] = 2; // max zoom
+
+    bytes.extend(root);
+    bytes.extend(leaf);
+    tiles.iter().for_each(|tile| bytes.extend(tile));
+    bytes
+  }
+
+  #[test]
+  fn tile_ids() {
+    assert_eq!(zxy_to_tile_id(0, 0, 0), 0);
+    assert_eq!(zxy_to_tile_id(1, 0, 0), 1);
+    assert_eq!(zxy_to_tile_id(1, 0, 1), 2);
+    assert_eq!(zxy_to_tile_id(1, 1, 1), 3);
+    assert_eq!(zxy_to_tile_id(1, 1, 0), 4);
+    assert_eq!(zxy_to_tile_id(2, 0, 0), 5);
+    assert_eq!(zxy_to_tile_id(3, 7, 0), 5 + 16 + 63);
+    assert_eq!(zxy_to_tile_id(12, 3423, 1763), 19078479);
+  }
+
+  #[test]
+  fn directory_roundtrip() {
+    let entries = [entry(3, 0, 10, 2), entry(7, 10, 20, 1), entry(100, 0, 5, 0)];
+    let directory = parse_directory(&write_directory(&entries)).unwrap();
+    assert_eq!(directory, entries);
+
+    assert_eq!(find_entry(&directory, 2), None);
+    assert_eq!(find_entry(&directory, 4), Some(entries[0]));
+    assert_eq!(find_entry(&directory, 5), None);
+    assert_eq!(find_entry(&directory, 1000), Some(entries[2]));
+
+    assert!(parse_directory(&[5, 1]).is_err());
+
+    // the second entry directly follows the first one, which ends beyond u64::MAX
+    let mut corrupt = Vec::new();
+    for value in [2, 0, 1, 1, 1, 5, 5, u64::MAX, 0] {
+      write_varint(&mut corrupt, value);
+    }
+    assert!(parse_directory(&corrupt).is_err());
+  }
+
+  #[test]
+  fn read_tiles() {
+    let provider: Rc<dyn RangeProvider> = Rc::new(create_archive());
+    let archive = futures::executor::block_on(PmTiles::new(provider)).unwrap();
+    let get_tile = |z, x, y| futures::executor::block_on(archive.get_tile(z, x, y)).unwrap();
+
+    assert_eq!(get_tile(0, 0, 0).as_deref(), Some(&b"tile 0"[..]));
+    assert_eq!(get_tile(1, 0, 1).as_deref(), Some(&b"tile 2"[..]));
+    assert_eq!(get_tile(2, 1, 1).as_deref(), Some(&b"tile 8"[..]));
+    assert_eq!(get_tile(1, 1, 1), None);
+    assert_eq!(get_tile(2, 0, 0), None);
+    assert_eq!(get_tile(3, 0, 0), None);
+
+    let provider: Rc<dyn RangeProvider> = Rc::new(b"MBTiles".to_vec());
+    assert!(futures::executor::block_on(PmTiles::new(provider)).is_err());
+  }
+
+  #[test]
+  fn tile_types() {
+    let get_format = |bytes: &[u8]| Header::parse(bytes).unwrap().tile_format;
+    let mut bytes = create_archive();
+    assert_eq!(get_format(&bytes), Some(TileFormat::Mvt));
+
+    bytes[99] = TILE_TYPE_MLT;
+    assert_eq!(get_format(&bytes), Some(TileFormat::Mlt));
+
+    bytes[99] = TILE_TYPE_UNKNOWN;
+    assert_eq!(get_format(&bytes), None);
+
+    // png raster tiles
+    bytes[99] = 2;
+    assert!(Header::parse(&bytes).is_err());
+  }
+}
@@ -0,0 +1,84 @@
+use futures::future::LocalBoxFuture;
+
+/// random access to the bytes of an archive, e.g. http range requests or a local file
+pub trait RangeProvider {
+  /// bytes from offset with the given length, fewer at the end of the archive
+  fn get_range(&self, offset: u64, length: u64) -> LocalBoxFuture<'_, Result<Vec<u8>, String>>;
+}
+
+/// whole archive in memory
+impl RangeProvider for Vec<u8> {
+  fn get_range(&self, offset: u64, length: u64) -> LocalBoxFuture<'_, Result<Vec<u8>, String>> {
+    let start = (offset as usize).min(self.len());
+    let end = (offset.saturating_add(length) as usize).min(self.len());
+    Box::pin(futures::future::ready(Ok(self[start..end].to_vec())))
+  }
+}
+
+/// archive on the local file system
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileProvider {
+  file: std::cell::RefCell<std::fs::File>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileProvider {
+  pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+    let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    Ok(Self {
+      file: std::cell::RefCell::new(file),
+    })
+  }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RangeProvider for FileProvider {
+  fn get_range(&self, offset: u64, length: u64) -> LocalBoxFuture<'_, Result<Vec<u8>, String>> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let read = || -> std::io::Result<Vec<u8>> {
+      let mut file = self.file.borrow_mut();
+      file.seek(SeekFrom::Start(offset))?;
+      let mut bytes = Vec::new();
+      file.by_ref().take(length).read_to_end(&mut bytes)?;
+      Ok(bytes)
+    };
+    Box::pin(futures::future::ready(
+      read().map_err(|err| err.to_string()),
+    ))
+  }
+}
+
+/// javascript function (offset, length) => Promise<ArrayBuffer | Uint8Array>, e.g. a
+/// fetch with a range header
+#[cfg(target_arch = "wasm32")]
+pub struct FunctionProvider {
+  function: js_sys::Function,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FunctionProvider {
+  pub fn new(function: js_sys::Function) -> Self {
+    Self { function }
+  }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl RangeProvider for FunctionProvider {
+  fn get_range(&self, offset: u64, length: u64) -> LocalBoxFuture<'_, Result<Vec<u8>, String>> {
+    use wasm_bindgen::JsValue;
+
+    let result = self.function.call2(
+      &JsValue::NULL,
+      &JsValue::from_f64(offset as f64),
+      &JsValue::from_f64(length as f64),
+    );
+    Box::pin(async move {
+      let promise = js_sys::Promise::resolve(&result.map_err(|err| format!("{err:?}"))?);
+      let value = wasm_bindgen_futures::JsFuture::from(promise)
+        .await
+        .map_err(|err| format!("{err:?}"))?;
+      Ok(js_sys::Uint8Array::new(&value).to_vec())
+    })
+  }
+}
@@ -0,0 +1,71 @@
+use std::cell::{Cell, RefCell};
+
+use futures::channel::oneshot;
+
+/// state of a device recovery, tile data added in the meantime waits for its end
+#[derive(Default)]
+pub struct Recovery {
+  active: Cell<bool>,
+
+  /// resolved with the success of the recovery
+  waiters: RefCell<Vec<oneshot::Sender<bool>>>,
+}
+
+impl Recovery {
+  pub fn start(&self) {
+    self.active.set(true);
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.active.get()
+  }
+
+  /// resolves with true right away if no recovery is active
+  pub fn wait(&self) -> impl Future<Output = bool> + use<> {
+    let receiver = if self.is_active() {
+      let (sender, receiver) = oneshot::channel();
+      self.waiters.borrow_mut().push(sender);
+      Some(receiver)
+    } else {
+      None
+    };
+
+    async move {
+      match receiver {
+        Some(receiver) => receiver.await.unwrap_or(false),
+        None => true,
+      }
+    }
+  }
+
+  pub fn finish(&self, success: bool) {
+    self.active.set(false);
+    for waiter in self.waiters.take() {
+      let _ = waiter.send(success);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures::executor::block_on;
+
+  use super::*;
+
+  #[test]
+  fn wait_for_recovery() {
+    let recovery = Recovery::default();
+    assert!(block_on(recovery.wait()));
+
+    recovery.start();
+    let recovered = recovery.wait();
+    recovery.finish(true);
+    assert!(block_on(recovered));
+
+    recovery.start();
+    let failed = recovery.wait();
+    recovery.finish(false);
+    assert!(!block_on(failed));
+    assert!(!recovery.is_active());
+  }
+}
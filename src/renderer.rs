@@ -1,17 +1,71 @@
-use log::info;
+use std::{
+  mem::size_of,
+  rc::Rc,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+};
+
+use log::{error, info, warn};
 
 use crate::{
+  error::Error,
   ressource::{
     RessourceManager,
-    tile::{BucketType, Tile},
+    arena::{ArenaType, Slot},
+    layer::Layer,
+    source::Source,
+    tile::{BucketType, FeatureRange, Tile, TileBuffers},
     view::View,
   },
-  tessellation::LineTessellation,
+  tessellation::{self, LineTessellation},
 };
 
+const INITIAL_INDIRECT_BUFFER_SIZE: u64 = 1 << 14;
+
 const PREFERRED_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
 const PREFERRED_ALPHA_MODE: wgpu::CompositeAlphaMode = wgpu::CompositeAlphaMode::PreMultiplied;
 
+pub const DEFAULT_SAMPLE_COUNT: u32 = 4;
+
+/// sample counts with standard sample positions, see `get_sample_offset` in common.wgsl
+const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// optional features for drawing the tiles of a layer with indirect draws
+const INDIRECT_FEATURES: wgpu::Features =
+  wgpu::Features::INDIRECT_FIRST_INSTANCE.union(wgpu::Features::MULTI_DRAW_INDIRECT);
+
+/// how the tiles of a group are drawn, depends on the features of the device
+#[derive(Clone, Copy)]
+enum DrawMode {
+  /// one draw call per tile, the tile transform is selected by the first instance
+  Direct,
+
+  /// one indirect draw call per tile
+  Indirect,
+
+  /// one draw call per group of tiles with the same layer and bucket type
+  MultiIndirect,
+
+  /// one draw call per tile, the tile transform and vertices are bound for every tile
+  Downlevel,
+}
+
+impl DrawMode {
+  fn new(features: wgpu::Features, downlevel: bool) -> Self {
+    if downlevel {
+      DrawMode::Downlevel
+    } else if !features.contains(wgpu::Features::INDIRECT_FIRST_INSTANCE) {
+      DrawMode::Direct
+    } else if !features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+      DrawMode::Indirect
+    } else {
+      DrawMode::MultiIndirect
+    }
+  }
+}
+
 pub struct Renderer {
   /// wgpu device queue pair
   pub device_queue: (wgpu::Device, wgpu::Queue),
@@ -25,15 +79,45 @@ pub struct Renderer {
   /// line tessellation
   line_tessellation: LineTessellation,
 
-  /// wgpu surface
-  swapchain: wgpu::Surface<'static>,
+  /// wgpu instance, needed to request a new adapter after a device loss
+  instance: wgpu::Instance,
+
+  /// set by the device lost callback
+  device_lost: Arc<AtomicBool>,
+
+  /// wgpu surface, shared with a pending device request after a device loss
+  swapchain: Rc<wgpu::Surface<'static>>,
 
   /// wgpu surfaceconfiguration
   swapchain_config: wgpu::SurfaceConfiguration,
 
+  /// msaa sample count
+  sample_count: u32,
+
+  /// msaa render target which is resolved into the surface texture
+  multisampled_framebuffer: Option<wgpu::TextureView>,
+
+  /// transforms of all tiles drawn in a frame
+  tile_buffers: TileBuffers,
+
+  /// draw args of all tiles drawn in a frame
+  indirect_buffer: wgpu::Buffer,
+
   pub ressource_manager: RessourceManager,
 }
 
+/// device requested to replace a lost one, see `Renderer::recover`
+pub struct RecoveredDevice {
+  adapter: wgpu::Adapter,
+
+  device: wgpu::Device,
+
+  queue: wgpu::Queue,
+
+  /// set by the device lost callback of the new device
+  device_lost: Arc<AtomicBool>,
+}
+
 pub trait ToSurface {
   fn create_surface(
     &self,
@@ -42,39 +126,30 @@ pub trait ToSurface {
 }
 
 impl Renderer {
-  pub async fn new<W: ToSurface>(window: &W, (width, height): (u32, u32)) -> Self {
-    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+  pub async fn new<W: ToSurface>(
+    window: &W,
+    (width, height): (u32, u32),
+    sample_count: u32,
+  ) -> Result<Self, Error> {
+    let instance_descriptor = wgpu::InstanceDescriptor {
       backends: wgpu::Backends::from_env().unwrap_or(wgpu::Backends::all()),
       ..Default::default()
-    });
-
-    let swapchain = match window.create_surface(&instance) {
-      Ok(surface) => surface,
-      Err(err) => {
-        panic!("{}", err.to_string())
-      }
     };
 
-    info!("surface: {:?}", &swapchain);
-
-    let adapter = instance
-      .request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::from_env()
-          .unwrap_or(wgpu::PowerPreference::HighPerformance),
-        force_fallback_adapter: false,
-        compatible_surface: Some(&swapchain),
-      })
-      .await
-      .expect("Adapter not created.");
+    // fall back to WebGL2 if the browser has no WebGPU adapter
+    #[cfg(feature = "webgl")]
+    let instance = wgpu::util::new_instance_with_webgpu_detection(&instance_descriptor).await;
+    #[cfg(not(feature = "webgl"))]
+    let instance = wgpu::Instance::new(&instance_descriptor);
 
-    info!("adapter: {:?}", &adapter);
+    let swapchain = window
+      .create_surface(&instance)
+      .map_err(|err| Error::Surface(err.to_string()))?;
 
-    let (device, queue) = adapter
-      .request_device(&wgpu::DeviceDescriptor::default())
-      .await
-      .expect("Device can't be created.");
+    info!("surface: {:?}", &swapchain);
 
-    info!("device: {:?}", device);
+    let device_lost = Arc::new(AtomicBool::new(false));
+    let (adapter, device, queue) = request_device(&instance, &swapchain, &device_lost).await?;
 
     let swapchain_capabilities = swapchain.get_capabilities(&adapter);
 
@@ -92,7 +167,7 @@ impl Renderer {
       swapchain_capabilities
         .formats
         .first()
-        .expect("Can't get texture format for surface.")
+        .ok_or(Error::Surface("no texture format".to_owned()))?
         .to_owned()
     };
 
@@ -110,7 +185,7 @@ impl Renderer {
       swapchain_capabilities
         .alpha_modes
         .first()
-        .expect("Can't get present mode for surface.")
+        .ok_or(Error::Surface("no alpha mode".to_owned()))?
         .to_owned()
     };
 
@@ -127,19 +202,51 @@ impl Renderer {
 
     swapchain.configure(&device, &swapchain_config);
 
-    let line_tessellation = LineTessellation::new((device.clone(), queue.clone()));
+    let sample_count = if SAMPLE_COUNTS.contains(&sample_count)
+      && adapter
+        .get_texture_format_features(texture_format)
+        .flags
+        .sample_count_supported(sample_count)
+    {
+      sample_count
+    } else {
+      warn!("{sample_count}x msaa not supported, fallback to 1x");
+      1
+    };
+
+    let multisampled_framebuffer =
+      create_multisampled_framebuffer(&device, &swapchain_config, sample_count);
+
+    let downlevel = is_downlevel(&adapter);
+    let line_tessellation =
+      create_line_tessellation(&adapter, (device.clone(), queue.clone()), downlevel);
 
-    let mut ressource_manager = RessourceManager::new(device.clone(), texture_format);
+    let mut ressource_manager = RessourceManager::new(
+      device.clone(),
+      queue.clone(),
+      texture_format,
+      sample_count,
+      downlevel,
+    );
+    let view = View::new((width, height), &mut ressource_manager);
+    let tile_buffers = TileBuffers::new(&ressource_manager);
+    let indirect_buffer = create_indirect_buffer(&device, INITIAL_INDIRECT_BUFFER_SIZE);
 
-    Self {
+    Ok(Self {
       device_queue: (device, queue),
       texture_format,
-      view: View::new((width, height), &mut ressource_manager),
+      view,
       line_tessellation,
-      swapchain,
+      instance,
+      device_lost,
+      swapchain: Rc::new(swapchain),
       swapchain_config,
+      sample_count,
+      multisampled_framebuffer,
+      tile_buffers,
+      indirect_buffer,
       ressource_manager,
-    }
+    })
   }
 
   pub fn create_tile<F>(&self, bucket_type: BucketType, extent: [f32; 4]) -> Tile {
@@ -152,37 +259,249 @@ impl Renderer {
     self.swapchain_config.width = width;
     self.swapchain_config.height = height;
     self.swapchain.configure(device, &self.swapchain_config);
+    self.multisampled_framebuffer =
+      create_multisampled_framebuffer(device, &self.swapchain_config, self.sample_count);
     self.view.set_size((width, height));
   }
 
-  pub async fn compute(
+  pub fn is_device_lost(&self) -> bool {
+    self.device_lost.load(Ordering::Acquire)
+  }
+
+  pub fn create_layer(&self, name: String, source: String, source_layer: Option<String>) -> Layer {
+    Layer::new(&self.ressource_manager, name, source, source_layer)
+  }
+
+  /// request a new device after a device loss. the renderer isn't borrowed while the
+  /// request is pending, so sources and layers can still be changed
+  pub fn request_recovery(&self) -> impl Future<Output = Result<RecoveredDevice, Error>> + use<> {
+    let instance = self.instance.clone();
+    let swapchain = self.swapchain.clone();
+
+    async move {
+      let device_lost = Arc::new(AtomicBool::new(false));
+      let (adapter, device, queue) = request_device(&instance, &swapchain, &device_lost).await?;
+      Ok(RecoveredDevice {
+        adapter,
+        device,
+        queue,
+        device_lost,
+      })
+    }
+  }
+
+  /// switch to the recovered device and upload all tiles again from their retained cpu
+  /// data. all tiles are recreated, if lines of a tile can't be tessellated it stays
+  /// without geometry and the first error is returned afterwards
+  pub fn recover(
     &mut self,
-    vertices: &[f32],
-    indices: &[u32],
-  ) -> (wgpu::Buffer, wgpu::Buffer) {
-    self.line_tessellation.tessellate((vertices, indices)).await
+    recovered: RecoveredDevice,
+    sources: &mut [Source],
+    layers: &mut [Layer],
+  ) -> Result<(), Error> {
+    let RecoveredDevice {
+      adapter,
+      device,
+      queue,
+      device_lost,
+    } = recovered;
+
+    self.swapchain.configure(&device, &self.swapchain_config);
+    self.multisampled_framebuffer =
+      create_multisampled_framebuffer(&device, &self.swapchain_config, self.sample_count);
+    let downlevel = is_downlevel(&adapter);
+    self.line_tessellation =
+      create_line_tessellation(&adapter, (device.clone(), queue.clone()), downlevel);
+    self.ressource_manager = RessourceManager::new(
+      device.clone(),
+      queue.clone(),
+      self.texture_format,
+      self.sample_count,
+      downlevel,
+    );
+    self.view.recreate(&mut self.ressource_manager);
+    self.tile_buffers = TileBuffers::new(&self.ressource_manager);
+    self.indirect_buffer = create_indirect_buffer(&device, self.indirect_buffer.size());
+    self.device_queue = (device, queue);
+    self.device_lost = device_lost;
+
+    for layer in layers.iter_mut() {
+      layer.recreate(&self.ressource_manager);
+    }
+
+    recreate_tiles(
+      sources.iter_mut().flat_map(|source| source.get_tiles_mut()),
+      &self.line_tessellation,
+      &self.ressource_manager,
+    )
+  }
+
+  /// largest width and height of raster tiles
+  pub fn get_max_texture_size(&self) -> u32 {
+    self.device_queue.0.limits().max_texture_dimension_2d
+  }
+
+  /// tessellate the retained lines of the tile into new slots of the line arenas, the
+  /// compute pass is submitted before returning
+  pub fn compute(&self, tile: &Tile) -> Result<(Slot, Slot), Error> {
+    tessellate_into_arenas(&self.line_tessellation, &self.ressource_manager, tile)
+  }
+
+  /// resolves after all work submitted so far is done on the gpu, including pending
+  /// buffer writes and line tessellations
+  pub fn submitted_work_done(&self) -> impl Future<Output = ()> + use<> {
+    let (_, queue) = &self.device_queue;
+    let (sender, receiver) = futures::channel::oneshot::channel();
+
+    // pending buffer writes are only executed with the next submission
+    queue.submit([]);
+    queue.on_submitted_work_done(move || {
+      let _ = sender.send(());
+    });
+
+    // the callback is only called while polling on native, the browser calls it itself
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(err) = self.device_queue.0.poll(wgpu::PollType::Wait) {
+      warn!("{err}");
+    }
+
+    async move {
+      let _ = receiver.await;
+    }
+  }
+
+  /// visible tiles in draw order. layers are drawn by z-index, tiles of a layer by
+  /// source layer and tiles of the same source layer grouped by bucket type, so the
+  /// material only changes between groups
+  fn get_draws<'a>(
+    &self,
+    sources: &'a [Source],
+    layers: &'a [Layer],
+  ) -> Vec<(&'a Layer, &'a Tile)> {
+    let mut layers: Vec<&Layer> = layers.iter().filter(|layer| layer.is_rendered()).collect();
+    layers.sort_by_key(|layer| layer.get_z_index());
+
+    let mut draws = Vec::new();
+    for layer in layers {
+      let Some(source) = sources
+        .iter()
+        .find(|source| source.get_name() == layer.get_source())
+      else {
+        continue;
+      };
+
+      let start = draws.len();
+      draws.extend(
+        source
+          .get_tiles()
+          .iter()
+          .filter(|tile| layer.contains(tile) && self.view.intersects(tile.get_extent()))
+          .map(|tile| (layer, tile)),
+      );
+      for group in
+        draws[start..].chunk_by_mut(|(_, a), (_, b)| a.get_source_layer() == b.get_source_layer())
+      {
+        group.sort_by_key(|(_, tile)| tile.get_bucket_type());
+      }
+    }
+    draws
+  }
+
+  /// features drawn within the radius around the pixel, both in css pixels. the top
+  /// most first, nothing is found while the globe is visible
+  pub fn get_features_at<'a>(
+    &self,
+    sources: &'a [Source],
+    layers: &'a [Layer],
+    pixel: glam::Vec2,
+    radius: f32,
+  ) -> Vec<(&'a Layer, &'a Tile, &'a FeatureRange)> {
+    let pixel = pixel * self.view.get_pixel_ratio();
+    let (Some(position), Some(neighbour)) = (
+      self.view.get_map_position(pixel),
+      self.view.get_map_position(pixel + glam::Vec2::X),
+    ) else {
+      return Vec::new();
+    };
+    let resolution = position.distance(neighbour) * self.view.get_pixel_ratio();
+
+    self
+      .get_draws(sources, layers)
+      .into_iter()
+      .rev()
+      .flat_map(|(layer, tile)| {
+        tile
+          .get_features_at(position, resolution, radius)
+          .into_iter()
+          .map(move |feature| (layer, tile, feature))
+      })
+      .collect()
   }
 
-  pub fn render(&self, tiles: &[Tile]) {
+  pub fn render(&mut self, sources: &[Source], layers: &[Layer]) -> Result<(), Error> {
     let (device, queue) = &self.device_queue;
     let mut command_encoder =
       device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-    let surface_texture = self
-      .swapchain
-      .get_current_texture()
-      .expect("Can't get current texture");
+    let surface_texture = match self.swapchain.get_current_texture() {
+      Ok(surface_texture) => surface_texture,
+      Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+        warn!("surface lost or outdated, reconfigure");
+        self.swapchain.configure(device, &self.swapchain_config);
+        self
+          .swapchain
+          .get_current_texture()
+          .map_err(Error::SurfaceLost)?
+      }
+      // skip the frame, the next one will most likely get a texture again
+      Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+      Err(err) => return Err(Error::SurfaceLost(err)),
+    };
 
     let view = surface_texture
       .texture
       .create_view(&wgpu::TextureViewDescriptor::default());
 
+    // render into the msaa target and resolve into the surface texture
+    let (target, resolve_target) = match self.multisampled_framebuffer.as_ref() {
+      Some(multisampled_framebuffer) => (multisampled_framebuffer, Some(&view)),
+      None => (&view, None),
+    };
+
+    let draws: Vec<_> = self
+      .get_draws(sources, layers)
+      .into_iter()
+      .filter_map(|(layer, tile)| Some((layer, tile, tile.get_draw_args()?)))
+      .collect();
+    self.tile_buffers.write(
+      &self.ressource_manager,
+      queue,
+      &self.view,
+      draws.iter().map(|(_, tile, _)| *tile),
+    );
+
+    let draw_mode = DrawMode::new(device.features(), self.ressource_manager.is_downlevel());
+    let draw_args: Vec<u8> = draws
+      .iter()
+      .flat_map(|(_, _, args)| args.as_bytes())
+      .copied()
+      .collect();
+    if matches!(draw_mode, DrawMode::Indirect | DrawMode::MultiIndirect) && !draw_args.is_empty() {
+      if draw_args.len() as u64 > self.indirect_buffer.size() {
+        let size = (draw_args.len() as u64).next_power_of_two();
+        self.indirect_buffer = create_indirect_buffer(device, size);
+      }
+      queue.write_buffer(&self.indirect_buffer, 0, &draw_args);
+    }
+
+    let arenas = self.ressource_manager.get_arenas();
+
     {
-      command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: None,
         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-          view: &view,
-          resolve_target: None,
+          view: target,
+          resolve_target,
           ops: wgpu::Operations {
             load: wgpu::LoadOp::Clear(wgpu::Color {
               r: 0.0,
@@ -190,36 +509,394 @@ impl Renderer {
               b: 0.58,
               a: 1.0,
             }),
-            store: wgpu::StoreOp::Store,
-          },
-        })],
-        ..Default::default()
-      });
-    } // out of scope
-
-    {
-      let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: None,
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-          view: &view,
-          resolve_target: None,
-          ops: wgpu::Operations {
-            load: wgpu::LoadOp::Load,
-            store: wgpu::StoreOp::Store,
+            store: if resolve_target.is_some() {
+              wgpu::StoreOp::Discard
+            } else {
+              wgpu::StoreOp::Store
+            },
           },
         })],
         ..Default::default()
       });
 
       self.view.set(&mut render_pass, queue);
-
-      // FIXME: set material / shader here. group by material in bucket
-      for tile in tiles.iter() {
-        tile.render(&mut render_pass, queue, &self.view);
+      self.tile_buffers.set(&mut render_pass);
+
+      // tiles of a layer with the same bucket type share their arenas and are drawn
+      // together. raster tiles are drawn one by one, each binds its own texture
+      let mut previous_layer: Option<&Layer> = None;
+      let mut start = 0;
+      for group in draws.chunk_by(|(a_layer, a_tile, _), (b_layer, b_tile, _)| {
+        std::ptr::eq(*a_layer, *b_layer)
+          && a_tile.get_bucket_type() == b_tile.get_bucket_type()
+          && a_tile.get_bucket_type() != BucketType::Raster
+      }) {
+        let (layer, tile, _) = group[0];
+        if previous_layer.is_none_or(|previous_layer| !std::ptr::eq(previous_layer, layer)) {
+          layer.set(&mut render_pass, queue);
+        }
+        tile.set_material(&mut render_pass);
+        tile.set_texture(&mut render_pass);
+        self
+          .tile_buffers
+          .set_vertex_buffers(&mut render_pass, &arenas, tile.get_bucket_type());
+
+        let stride = size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64;
+        match draw_mode {
+          DrawMode::MultiIndirect => render_pass.multi_draw_indexed_indirect(
+            &self.indirect_buffer,
+            start as u64 * stride,
+            group.len() as u32,
+          ),
+          DrawMode::Indirect => {
+            for i in start..start + group.len() {
+              render_pass.draw_indexed_indirect(&self.indirect_buffer, i as u64 * stride);
+            }
+          }
+          DrawMode::Direct => {
+            for (_, _, args) in group {
+              render_pass.draw_indexed(
+                args.first_index..args.first_index + args.index_count,
+                args.base_vertex,
+                args.first_instance..args.first_instance + args.instance_count,
+              );
+            }
+          }
+          DrawMode::Downlevel => {
+            for (_, tile, args) in group {
+              self.tile_buffers.set_tile(&mut render_pass, &arenas, tile);
+              render_pass.draw_indexed(
+                args.first_index..args.first_index + args.index_count,
+                0,
+                args.first_instance..args.first_instance + args.instance_count,
+              );
+            }
+          }
+        }
+
+        previous_layer = Some(layer);
+        start += group.len();
       }
     }
 
     queue.submit(Some(command_encoder.finish()));
     surface_texture.present();
+
+    Ok(())
+  }
+}
+
+/// recreate the tiles with the ressource manager of a new device, continues after a
+/// tile failed and returns the first error
+fn recreate_tiles<'a>(
+  tiles: impl Iterator<Item = &'a mut Tile>,
+  line_tessellation: &LineTessellation,
+  ressource_manager: &RessourceManager,
+) -> Result<(), Error> {
+  let mut result = Ok(());
+  for tile in tiles {
+    let recreated = tile.recreate(ressource_manager).and_then(|_| {
+      if tile.get_bucket_type() != BucketType::Line {
+        return Ok(());
+      }
+      let (vertex_slot, index_slot) =
+        tessellate_into_arenas(line_tessellation, ressource_manager, tile)?;
+      tile.add_slots(vertex_slot, index_slot);
+      Ok(())
+    });
+    match recreated {
+      Ok(()) => {}
+      Err(err) if result.is_ok() => result = Err(err),
+      Err(err) => error!("{err}"),
+    }
+  }
+  result
+}
+
+/// retained lines of the tile with the feature index of every vertex
+fn tessellate_into_arenas(
+  line_tessellation: &LineTessellation,
+  ressource_manager: &RessourceManager,
+  tile: &Tile,
+) -> Result<(Slot, Slot), Error> {
+  let (vertices, indices) = tile.get_retained_buffers();
+  let features = tile.get_vertex_features();
+  let (vertices_size, indices_size) = tessellation::get_output_sizes(indices.len());
+  let vertex_slot = ressource_manager.allocate(ArenaType::LineVertices, vertices_size)?;
+  let index_slot = ressource_manager.allocate(ArenaType::LineIndices, indices_size)?;
+
+  let arenas = ressource_manager.get_arenas();
+  line_tessellation.tessellate_into(
+    (vertices, indices, &features),
+    (
+      get_slot_binding(
+        arenas.get(ArenaType::LineVertices).get_buffer(),
+        &vertex_slot,
+        vertices_size,
+      ),
+      get_slot_binding(
+        arenas.get(ArenaType::LineIndices).get_buffer(),
+        &index_slot,
+        indices_size,
+      ),
+    ),
+  )?;
+
+  Ok((vertex_slot, index_slot))
+}
+
+async fn request_device(
+  instance: &wgpu::Instance,
+  swapchain: &wgpu::Surface<'static>,
+  device_lost: &Arc<AtomicBool>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), Error> {
+  let adapter = instance
+    .request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::from_env()
+        .unwrap_or(wgpu::PowerPreference::HighPerformance),
+      force_fallback_adapter: false,
+      compatible_surface: Some(swapchain),
+    })
+    .await
+    .map_err(Error::Adapter)?;
+
+  info!("adapter: {:?}", &adapter);
+
+  let (device, queue) = adapter
+    .request_device(&wgpu::DeviceDescriptor {
+      required_features: adapter.features() & INDIRECT_FEATURES,
+      required_limits: get_required_limits(&adapter),
+      ..Default::default()
+    })
+    .await
+    .map_err(Error::Device)?;
+
+  info!("device: {:?}", device);
+
+  let device_lost = device_lost.clone();
+  device.set_device_lost_callback(move |reason, message| {
+    // destroyed is reported when the device is dropped intentionally
+    if reason == wgpu::DeviceLostReason::Unknown {
+      warn!("device lost: {message}");
+      device_lost.store(true, Ordering::Release);
+    }
+  });
+
+  Ok((adapter, device, queue))
+}
+
+/// downlevel devices are requested without storage buffers
+fn get_required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+  if is_downlevel(adapter) {
+    warn!("downlevel adapter, draw tiles without storage buffers");
+    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+  } else {
+    wgpu::Limits::default()
+  }
+}
+
+/// adapters without storage buffers in render pipelines or without a base vertex use
+/// the downlevel shaders and draw mode, e.g. WebGL2
+fn is_downlevel(adapter: &wgpu::Adapter) -> bool {
+  let flags = wgpu::DownlevelFlags::VERTEX_STORAGE
+    | wgpu::DownlevelFlags::FRAGMENT_STORAGE
+    | wgpu::DownlevelFlags::BASE_VERTEX;
+  !adapter.get_downlevel_capabilities().flags.contains(flags)
+    || adapter.limits().max_storage_buffers_per_shader_stage == 0
+}
+
+/// lines are tessellated on the cpu if the adapter has no compute shaders or the
+/// device too few storage buffers, e.g. WebGL2. downlevel devices always tessellate on
+/// the cpu, they are requested without storage buffers and their line arenas can't be
+/// bound as storage, even if the adapter would support it
+fn create_line_tessellation(
+  adapter: &wgpu::Adapter,
+  device_queue: (wgpu::Device, wgpu::Queue),
+  downlevel: bool,
+) -> LineTessellation {
+  let compute_shaders = adapter
+    .get_downlevel_capabilities()
+    .flags
+    .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+  let storage_buffers = device_queue.0.limits().max_storage_buffers_per_shader_stage;
+  if !downlevel && compute_shaders && storage_buffers >= LineTessellation::STORAGE_BUFFERS {
+    LineTessellation::new(device_queue)
+  } else {
+    warn!("no compute shaders, tessellate lines on the cpu");
+    LineTessellation::new_on_cpu(device_queue)
+  }
+}
+
+fn create_indirect_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: None,
+    size,
+    usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false,
+  })
+}
+
+/// range of a slot written by the tessellation, the slot may be bigger because of its
+/// alignment
+fn get_slot_binding<'a>(
+  buffer: &'a wgpu::Buffer,
+  slot: &Slot,
+  size: u64,
+) -> wgpu::BufferBinding<'a> {
+  wgpu::BufferBinding {
+    buffer,
+    offset: slot.get_offset(),
+    size: wgpu::BufferSize::new(size),
+  }
+}
+
+fn create_multisampled_framebuffer(
+  device: &wgpu::Device,
+  config: &wgpu::SurfaceConfiguration,
+  sample_count: u32,
+) -> Option<wgpu::TextureView> {
+  if sample_count <= 1 {
+    return None;
+  }
+
+  let texture = device.create_texture(&wgpu::TextureDescriptor {
+    label: None,
+    size: wgpu::Extent3d {
+      width: config.width,
+      height: config.height,
+      depth_or_array_layers: 1,
+    },
+    mip_level_count: 1,
+    sample_count,
+    dimension: wgpu::TextureDimension::D2,
+    format: config.format,
+    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    view_formats: &[],
+  });
+
+  Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+  use mvt_reader::feature::Feature;
+
+  use super::*;
+  use crate::ressource::tile::RasterImage;
+
+  /// device of a downlevel renderer, even if the adapter supports more
+  async fn request_downlevel_device() -> (wgpu::Adapter, wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::from_env().unwrap_or(wgpu::Backends::all()),
+      ..Default::default()
+    });
+    let adapter = instance
+      .request_adapter(&wgpu::RequestAdapterOptions::default())
+      .await
+      .unwrap();
+    let (device, queue) = adapter
+      .request_device(&wgpu::DeviceDescriptor {
+        required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+          .using_resolution(adapter.limits()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    (adapter, device, queue)
+  }
+
+  #[test]
+  fn recreate_all_tiles() {
+    pollster::block_on(async {
+      let (adapter, device, queue) = request_downlevel_device().await;
+      let line_tessellation =
+        create_line_tessellation(&adapter, (device.clone(), queue.clone()), true);
+      let create_ressource_manager = || {
+        RessourceManager::new(
+          device.clone(),
+          queue.clone(),
+          PREFERRED_TEXTURE_FORMAT,
+          1,
+          true,
+        )
+      };
+      let ressource_manager = create_ressource_manager();
+      let extent = [0.0, 0.0, 1.0, 1.0];
+
+      // a single index has no edge, so the tessellation of the first tile fails
+      let mut invalid_line = ressource_manager.create_tile::<Feature>(BucketType::Line, extent);
+      invalid_line.retain_buffers(vec![0.0, 0.0], vec![0]);
+      let mut line = ressource_manager.create_tile::<Feature>(BucketType::Line, extent);
+      line.retain_buffers(vec![0.0, 0.0, 1.0, 0.0], vec![0, 1]);
+      let mut fill = ressource_manager.create_tile::<Feature>(BucketType::Fill, extent);
+      fill
+        .add_buffers(
+          vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0],
+          vec![0, 1, 2],
+          &ressource_manager,
+        )
+        .unwrap();
+      let mut tiles = [invalid_line, line, fill];
+
+      let ressource_manager = create_ressource_manager();
+      let result = recreate_tiles(tiles.iter_mut(), &line_tessellation, &ressource_manager);
+
+      assert!(matches!(result, Err(Error::Tessellation(_))));
+      assert!(tiles[0].get_draw_args().is_none());
+      assert!(tiles[1].get_draw_args().is_some());
+      assert!(tiles[2].get_draw_args().is_some());
+    });
+  }
+
+  #[test]
+  fn raster_tile() {
+    pollster::block_on(async {
+      let (_, device, queue) = request_downlevel_device().await;
+      let ressource_manager = RessourceManager::new(
+        device.clone(),
+        queue.clone(),
+        PREFERRED_TEXTURE_FORMAT,
+        1,
+        true,
+      );
+
+      device.push_error_scope(wgpu::ErrorFilter::Validation);
+      let mut tile =
+        ressource_manager.create_tile::<Feature>(BucketType::Raster, [0.0, 0.0, 1.0, 1.0]);
+      assert!(tile.get_draw_args().is_none());
+      let image = RasterImage::new(vec![255; 2 * 2 * 4], 2, 2, 2).unwrap();
+      tile.add_image(image, &ressource_manager).unwrap();
+      queue.submit([]);
+      assert!(device.pop_error_scope().await.is_none());
+
+      // the pixels are kept for a new device
+      tile.recreate(&ressource_manager).unwrap();
+      assert!(tile.get_draw_args().is_some());
+    });
+  }
+
+  #[test]
+  fn downlevel_lines() {
+    pollster::block_on(async {
+      let (adapter, device, queue) = request_downlevel_device().await;
+      let line_tessellation =
+        create_line_tessellation(&adapter, (device.clone(), queue.clone()), true);
+      let ressource_manager = RessourceManager::new(
+        device.clone(),
+        queue.clone(),
+        PREFERRED_TEXTURE_FORMAT,
+        1,
+        true,
+      );
+
+      device.push_error_scope(wgpu::ErrorFilter::Validation);
+      let mut tile =
+        ressource_manager.create_tile::<Feature>(BucketType::Line, [0.0, 0.0, 1.0, 1.0]);
+      tile.retain_buffers(vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0], vec![0, 1, 2]);
+      tessellate_into_arenas(&line_tessellation, &ressource_manager, &tile).unwrap();
+      queue.submit([]);
+      assert!(device.pop_error_scope().await.is_none());
+    });
   }
 }
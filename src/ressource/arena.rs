@@ -0,0 +1,261 @@
+use std::{cell::RefCell, mem::size_of, ops::Range, rc::Rc};
+
+use super::tile::{FILL_VERTEX_SIZE, LINE_VERTEX_SIZE, POINT_INSTANCE_SIZE};
+use crate::error::Error;
+
+/// hands out ranges of an address space, freed ranges are reused first fit
+#[derive(Default)]
+pub struct Allocator {
+  /// released ranges sorted by offset
+  free: Vec<Range<u64>>,
+
+  /// end of the used address space
+  end: u64,
+}
+
+impl Allocator {
+  fn allocate(&mut self, size: u64) -> Range<u64> {
+    if let Some(i) = self
+      .free
+      .iter()
+      .position(|range| range.end - range.start >= size)
+    {
+      let range = self.free[i].start..self.free[i].start + size;
+      self.free[i].start = range.end;
+      if self.free[i].is_empty() {
+        self.free.remove(i);
+      }
+      return range;
+    }
+
+    let range = self.end..self.end + size;
+    self.end = range.end;
+    range
+  }
+
+  fn release(&mut self, range: Range<u64>) {
+    let i = self.free.partition_point(|free| free.start < range.start);
+    self.free.insert(i, range);
+
+    // merge with the following and the previous range
+    if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+      self.free[i].end = self.free.remove(i + 1).end;
+    }
+    if i > 0 && self.free[i - 1].end == self.free[i].start {
+      self.free[i - 1].end = self.free.remove(i).end;
+    }
+
+    // the used address space shrinks if its last range is released
+    if let Some(free) = self.free.pop_if(|free| free.end == self.end) {
+      self.end = free.start;
+    }
+  }
+
+  pub fn get_end(&self) -> u64 {
+    self.end
+  }
+}
+
+/// range of an allocator, released when dropped
+pub struct Slot {
+  range: Range<u64>,
+
+  allocator: Rc<RefCell<Allocator>>,
+}
+
+impl Slot {
+  pub fn new(allocator: &Rc<RefCell<Allocator>>, size: u64) -> Self {
+    Self {
+      range: allocator.borrow_mut().allocate(size),
+      allocator: allocator.clone(),
+    }
+  }
+
+  pub fn get_offset(&self) -> u64 {
+    self.range.start
+  }
+
+  pub fn get_size(&self) -> u64 {
+    self.range.end - self.range.start
+  }
+}
+
+impl Drop for Slot {
+  fn drop(&mut self) {
+    self.allocator.borrow_mut().release(self.range.clone());
+  }
+}
+
+#[derive(Clone, Copy)]
+pub enum ArenaType {
+  FillVertices = 0,
+  FillIndices = 1,
+  LineVertices = 2,
+  LineIndices = 3,
+  PointInstances = 4,
+}
+
+/// growable gpu buffer shared by the geometry of all tiles of a bucket type
+pub struct Arena {
+  allocator: Rc<RefCell<Allocator>>,
+
+  /// alignment of all slots
+  alignment: u64,
+
+  usage: wgpu::BufferUsages,
+
+  /// the buffer doesn't grow beyond the limits of the device
+  max_size: u64,
+
+  buffer: wgpu::Buffer,
+}
+
+impl Arena {
+  const INITIAL_SIZE: u64 = 1 << 20;
+
+  /// arenas used as storage buffers also stay within the storage binding size, so any
+  /// slot can be bound by the line tessellation
+  pub fn new(device: &wgpu::Device, usage: wgpu::BufferUsages, alignment: u64) -> Self {
+    let limits = device.limits();
+    let mut max_size = limits.max_buffer_size;
+    if usage.contains(wgpu::BufferUsages::STORAGE) {
+      max_size = max_size.min(limits.max_storage_buffer_binding_size as u64);
+    }
+    // slots are aligned, so the buffer size is a multiple of the alignment
+    let max_size = max_size / alignment * alignment;
+
+    let usage = usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+    Self {
+      allocator: Rc::new(RefCell::new(Allocator::default())),
+      alignment,
+      usage,
+      max_size,
+      buffer: create_buffer(device, usage, Self::INITIAL_SIZE.min(max_size)),
+    }
+  }
+
+  /// the buffer grows if the slot doesn't fit, the content is copied to the new buffer.
+  /// fails if the buffer would exceed the limits of the device
+  pub fn allocate(
+    &mut self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    size: u64,
+  ) -> Result<Slot, Error> {
+    let slot = Slot::new(
+      &self.allocator,
+      size.div_ceil(self.alignment) * self.alignment,
+    );
+
+    let end = self.allocator.borrow().get_end();
+    if end > self.max_size {
+      return Err(Error::BufferLimit(self.max_size));
+    }
+    if end > self.buffer.size() {
+      let size = end.max(self.buffer.size() * 2).min(self.max_size);
+      let buffer = create_buffer(device, self.usage, size);
+      let mut command_encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+      command_encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+      queue.submit(Some(command_encoder.finish()));
+      self.buffer = buffer;
+    }
+
+    Ok(slot)
+  }
+
+  pub fn get_buffer(&self) -> &wgpu::Buffer {
+    &self.buffer
+  }
+}
+
+fn create_buffer(device: &wgpu::Device, usage: wgpu::BufferUsages, size: u64) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: None,
+    size,
+    usage,
+    mapped_at_creation: false,
+  })
+}
+
+/// smallest alignment which is a multiple of both alignments
+fn get_common_alignment(a: u64, b: u64) -> u64 {
+  let (mut x, mut y) = (a, b);
+  while y != 0 {
+    (x, y) = (y, x % y);
+  }
+  a / x * b
+}
+
+/// arenas of all bucket types
+pub struct Arenas {
+  arenas: [Arena; 5],
+}
+
+impl Arenas {
+  /// slots are aligned to the size of one element, so draws can address them by
+  /// index. the line tessellation binds its slots as storage buffers, which need a
+  /// bigger alignment. downlevel devices tessellate on the cpu and have no storage
+  /// buffers at all
+  pub fn new(device: &wgpu::Device, downlevel: bool) -> Self {
+    let (storage, storage_alignment) = if downlevel {
+      (wgpu::BufferUsages::empty(), size_of::<u32>() as u64)
+    } else {
+      (
+        wgpu::BufferUsages::STORAGE,
+        device.limits().min_storage_buffer_offset_alignment as u64,
+      )
+    };
+    let vertex = wgpu::BufferUsages::VERTEX;
+    let index = wgpu::BufferUsages::INDEX;
+
+    Self {
+      arenas: [
+        Arena::new(device, vertex, FILL_VERTEX_SIZE),
+        Arena::new(device, index, size_of::<u32>() as u64),
+        Arena::new(
+          device,
+          vertex | storage,
+          get_common_alignment(storage_alignment, LINE_VERTEX_SIZE),
+        ),
+        Arena::new(device, index | storage, storage_alignment),
+        Arena::new(device, vertex, POINT_INSTANCE_SIZE),
+      ],
+    }
+  }
+
+  pub fn get(&self, arena_type: ArenaType) -> &Arena {
+    &self.arenas[arena_type as usize]
+  }
+
+  pub fn get_mut(&mut self, arena_type: ArenaType) -> &mut Arena {
+    &mut self.arenas[arena_type as usize]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn release_and_reuse() {
+    let allocator = Rc::new(RefCell::new(Allocator::default()));
+    let a = Slot::new(&allocator, 4);
+    let b = Slot::new(&allocator, 8);
+    let c = Slot::new(&allocator, 4);
+    assert_eq!(c.get_offset(), 12);
+
+    // first fit into the released range
+    drop(b);
+    let d = Slot::new(&allocator, 4);
+    assert_eq!(d.get_offset(), 4);
+
+    // released ranges at the end shrink the used address space
+    drop(c);
+    assert_eq!(allocator.borrow().get_end(), 8);
+    drop(d);
+    drop(a);
+    assert_eq!(allocator.borrow().get_end(), 0);
+    assert!(allocator.borrow().free.is_empty());
+  }
+}
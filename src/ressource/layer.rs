@@ -0,0 +1,155 @@
+use std::mem;
+
+use super::{BindGroupScope, RessourceManager, tile::Tile};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
+struct LayerUniform {
+  opacity: f32,
+
+  _pad: [u32; 3],
+}
+
+pub struct LayerManager;
+
+/// style layer, draws the tiles of a source in the order given by the z-index
+pub struct Layer {
+  name: String,
+
+  /// name of the source the tiles are taken from
+  source: String,
+
+  /// name of the layer in the tile data, all layers of the source if none
+  source_layer: Option<String>,
+
+  /// layers with a higher z-index are drawn on top, like in OpenLayers
+  z_index: i32,
+
+  visible: bool,
+
+  opacity: f32,
+
+  layer_uniform_buffer: wgpu::Buffer,
+
+  bind_group: wgpu::BindGroup,
+}
+
+impl Layer {
+  pub fn new(
+    ressource_manager: &RessourceManager,
+    name: String,
+    source: String,
+    source_layer: Option<String>,
+  ) -> Self {
+    let layer_uniform = LayerUniform {
+      opacity: 1.0,
+      _pad: [0; 3],
+    };
+    let layer_uniform_buffer =
+      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&[layer_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+    let bind_group =
+      ressource_manager.create_bind_group(&BindGroupScope::Layer, &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: layer_uniform_buffer.as_entire_binding(),
+      }]);
+
+    Self {
+      name,
+      source,
+      source_layer,
+      z_index: 0,
+      visible: true,
+      opacity: layer_uniform.opacity,
+      layer_uniform_buffer,
+      bind_group,
+    }
+  }
+
+  /// recreate the gpu ressources on a new device, style properties are kept
+  pub fn recreate(&mut self, ressource_manager: &RessourceManager) {
+    let layer = Layer::new(ressource_manager, String::new(), String::new(), None);
+    self.layer_uniform_buffer = layer.layer_uniform_buffer;
+    self.bind_group = layer.bind_group;
+  }
+
+  pub fn set<'frame>(
+    &'frame self,
+    render_pass: &mut wgpu::RenderPass<'frame>,
+    queue: &wgpu::Queue,
+  ) {
+    render_pass.set_bind_group(BindGroupScope::Layer as u32, Some(&self.bind_group), &[]);
+
+    queue.write_buffer(
+      &self.layer_uniform_buffer,
+      0,
+      bytemuck::cast_slice(&[LayerUniform {
+        opacity: self.opacity,
+        _pad: [0; 3],
+      }]),
+    );
+  }
+
+  /// tile belongs to the source layer drawn by this layer
+  pub fn contains(&self, tile: &Tile) -> bool {
+    self
+      .source_layer
+      .as_ref()
+      .is_none_or(|source_layer| source_layer == tile.get_source_layer())
+  }
+
+  /// nothing needs to be drawn for hidden or fully transparent layers
+  pub fn is_rendered(&self) -> bool {
+    self.visible && self.opacity > 0.0
+  }
+
+  pub fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn get_source(&self) -> &str {
+    &self.source
+  }
+
+  pub fn get_z_index(&self) -> i32 {
+    self.z_index
+  }
+
+  pub fn set_z_index(&mut self, z_index: i32) {
+    self.z_index = z_index;
+  }
+
+  pub fn set_visible(&mut self, visible: bool) {
+    self.visible = visible;
+  }
+
+  pub fn set_opacity(&mut self, opacity: f32) {
+    self.opacity = opacity.clamp(0.0, 1.0);
+  }
+}
+
+impl LayerManager {
+  pub fn new(ressource_manager: &mut RessourceManager) -> Self {
+    ressource_manager.register_bind_group_layout(
+      BindGroupScope::Layer,
+      &wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(mem::size_of::<LayerUniform>() as _),
+          },
+          count: None,
+        }],
+      },
+    );
+
+    Self
+  }
+}
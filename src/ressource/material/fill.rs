@@ -7,11 +7,18 @@ impl CreatePipeline<{ MaterialType::Fill }> for Material {
     let vertex_state = wgpu::VertexState {
       module: shader_module,
       entry_point: Some("vs_fill"),
-      buffers: &[wgpu::VertexBufferLayout {
-        array_stride: 8,
-        step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x2],
-      }],
+      buffers: &[
+        wgpu::VertexBufferLayout {
+          array_stride: 12,
+          step_mode: wgpu::VertexStepMode::Vertex,
+          attributes: &wgpu::vertex_attr_array![0 => Float32x2, 2 => Uint32],
+        },
+        wgpu::VertexBufferLayout {
+          array_stride: 4,
+          step_mode: wgpu::VertexStepMode::Instance,
+          attributes: &wgpu::vertex_attr_array![1 => Uint32],
+        },
+      ],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
     };
     let fragment_state = wgpu::FragmentState {
@@ -24,7 +31,7 @@ impl CreatePipeline<{ MaterialType::Fill }> for Material {
       })],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
     };
-    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state);
+    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state, false);
 
     let style = Style {
       fill_color: [0.506, 0.694, 0.31, 1.0],
@@ -45,7 +52,8 @@ impl CreatePipeline<{ MaterialType::Fill }> for Material {
 
     Self {
       pipeline,
-      bind_group,
+      bind_group: Some(bind_group),
+      size: 0.0,
     }
   }
 }
@@ -1,17 +1,24 @@
 use crate::ressource::{BindGroupScope, RessourceManager};
 
-use super::{CreatePipeline, Material, MaterialType, Style};
+use super::{CreatePipeline, Material, MaterialType, STROKE_WIDTH, Style};
 
 impl CreatePipeline<{ MaterialType::Line }> for Material {
   fn new(ressource_manager: &RessourceManager, shader_module: &wgpu::ShaderModule) -> Self {
     let vertex_state = wgpu::VertexState {
       module: shader_module,
       entry_point: Some("vs_stroke"),
-      buffers: &[wgpu::VertexBufferLayout {
-        array_stride: 16,
-        step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
-      }],
+      buffers: &[
+        wgpu::VertexBufferLayout {
+          array_stride: 24,
+          step_mode: wgpu::VertexStepMode::Vertex,
+          attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 3 => Uint32],
+        },
+        wgpu::VertexBufferLayout {
+          array_stride: 4,
+          step_mode: wgpu::VertexStepMode::Instance,
+          attributes: &wgpu::vertex_attr_array![2 => Uint32],
+        },
+      ],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
     };
     let fragment_state = wgpu::FragmentState {
@@ -24,13 +31,12 @@ impl CreatePipeline<{ MaterialType::Line }> for Material {
       })],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
     };
-    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state);
+    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state, false);
 
-    let stroke_width = 2.5;
     let style = Style {
       fill_color: [0.0, 0.0, 0.0, 1.0],
       stroke_color: [0.0, 0.0, 0.0, 1.0],
-      stroke_width: stroke_width * 0.5, // multiply by half because of double sided buffer
+      stroke_width: STROKE_WIDTH * 0.5, // multiply by half because of double sided buffer
       _pad: [0, 0, 0],
     };
     let style_buffer = ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -46,7 +52,8 @@ impl CreatePipeline<{ MaterialType::Line }> for Material {
 
     Self {
       pipeline,
-      bind_group,
+      bind_group: Some(bind_group),
+      size: STROKE_WIDTH,
     }
   }
 }
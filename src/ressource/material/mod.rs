@@ -5,6 +5,13 @@ use super::{BindGroupScope, RessourceManager, ShaderModuleScope};
 mod fill;
 mod line;
 mod point;
+mod raster;
+
+/// width of lines in css pixels
+pub const STROKE_WIDTH: f32 = 2.5;
+
+/// size of points in css pixels, injected into common.wgsl
+pub const POINT_SIZE: f32 = 6.0;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
@@ -26,20 +33,30 @@ pub enum MaterialType {
   Fill,
   Line,
   Point,
+  Raster,
 }
 
 pub struct Material {
   /// wgpu pipeline
   pipeline: wgpu::RenderPipeline,
 
-  /// wgpu bind group
-  bind_group: wgpu::BindGroup,
+  /// wgpu bind group, none if every tile binds its own like raster tiles
+  bind_group: Option<wgpu::BindGroup>,
+
+  /// width of lines or size of points in css pixels as drawn, 0 for areas
+  size: f32,
 }
 
 impl Material {
   pub fn set<'frame>(&'frame self, render_pass: &mut wgpu::RenderPass<'frame>) {
     render_pass.set_pipeline(&self.pipeline);
-    render_pass.set_bind_group(BindGroupScope::Material as u32, Some(&self.bind_group), &[]);
+    if let Some(bind_group) = self.bind_group.as_ref() {
+      render_pass.set_bind_group(BindGroupScope::Material as u32, Some(bind_group), &[]);
+    }
+  }
+
+  pub fn get_size(&self) -> f32 {
+    self.size
   }
 }
 
@@ -58,9 +75,15 @@ pub struct MaterialManager {
 
 impl MaterialManager {
   pub fn new(ressource_manager: &mut RessourceManager) -> Self {
+    let backend = if ressource_manager.downlevel {
+      include_str!("shader/downlevel.wgsl")
+    } else {
+      include_str!("shader/default.wgsl")
+    };
+    let constants = format!("const POINT_SIZE: f32 = {POINT_SIZE:?}; // in css pixels\n");
     let shader_module = ressource_manager.create_shader_module(
       ShaderModuleScope::Common,
-      std::borrow::Cow::Borrowed(include_str!("shader/common.wgsl")),
+      std::borrow::Cow::Owned([backend, &constants, include_str!("shader/common.wgsl")].concat()),
     );
 
     ressource_manager.register_bind_group_layout(
@@ -119,6 +142,14 @@ impl MaterialManager {
             &self.shader_module,
           ))
         }
+        MaterialType::Raster =>
+        {
+          #[allow(clippy::arc_with_non_send_sync)]
+          Arc::new(<Material as CreatePipeline<{ MaterialType::Raster }>>::new(
+            ressource_manager,
+            &self.shader_module,
+          ))
+        }
       });
     material.clone()
   }
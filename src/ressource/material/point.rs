@@ -1,6 +1,6 @@
 use crate::ressource::{BindGroupScope, RessourceManager};
 
-use super::{CreatePipeline, Material, MaterialType, Style};
+use super::{CreatePipeline, Material, MaterialType, POINT_SIZE, Style};
 
 impl CreatePipeline<{ MaterialType::Point }> for Material {
   fn new(ressource_manager: &RessourceManager, shader_module: &wgpu::ShaderModule) -> Self {
@@ -14,9 +14,9 @@ impl CreatePipeline<{ MaterialType::Point }> for Material {
           attributes: &wgpu::vertex_attr_array![0 => Float32x2],
         },
         wgpu::VertexBufferLayout {
-          array_stride: 8,
+          array_stride: 16,
           step_mode: wgpu::VertexStepMode::Instance,
-          attributes: &wgpu::vertex_attr_array![1 => Float32x2],
+          attributes: &wgpu::vertex_attr_array![1 => Float32x2, 2 => Uint32, 3 => Uint32],
         },
       ],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -31,7 +31,7 @@ impl CreatePipeline<{ MaterialType::Point }> for Material {
       })],
       compilation_options: wgpu::PipelineCompilationOptions::default(),
     };
-    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state);
+    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state, false);
 
     let style = Style {
       fill_color: [1.0, 0.0, 0.0, 1.0],
@@ -52,7 +52,8 @@ impl CreatePipeline<{ MaterialType::Point }> for Material {
 
     Self {
       pipeline,
-      bind_group,
+      bind_group: Some(bind_group),
+      size: POINT_SIZE,
     }
   }
 }
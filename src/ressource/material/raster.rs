@@ -0,0 +1,43 @@
+use crate::ressource::RessourceManager;
+
+use super::{CreatePipeline, Material, MaterialType};
+
+impl CreatePipeline<{ MaterialType::Raster }> for Material {
+  /// the texture is bound by every tile, see `Tile::set_texture`
+  fn new(ressource_manager: &RessourceManager, shader_module: &wgpu::ShaderModule) -> Self {
+    let vertex_state = wgpu::VertexState {
+      module: shader_module,
+      entry_point: Some("vs_raster"),
+      buffers: &[
+        wgpu::VertexBufferLayout {
+          array_stride: 8,
+          step_mode: wgpu::VertexStepMode::Vertex,
+          attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+        },
+        wgpu::VertexBufferLayout {
+          array_stride: 4,
+          step_mode: wgpu::VertexStepMode::Instance,
+          attributes: &wgpu::vertex_attr_array![1 => Uint32],
+        },
+      ],
+      compilation_options: wgpu::PipelineCompilationOptions::default(),
+    };
+    let fragment_state = wgpu::FragmentState {
+      module: shader_module,
+      entry_point: Some("fs_raster"),
+      targets: &[Some(wgpu::ColorTargetState {
+        format: ressource_manager.texture_format,
+        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        write_mask: wgpu::ColorWrites::default(),
+      })],
+      compilation_options: wgpu::PipelineCompilationOptions::default(),
+    };
+    let pipeline = ressource_manager.create_render_pipeline(vertex_state, fragment_state, true);
+
+    Self {
+      pipeline,
+      bind_group: None,
+      size: 0.0,
+    }
+  }
+}
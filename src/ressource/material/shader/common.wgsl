@@ -1,4 +1,7 @@
+// backend specific declarations are prepended, see default.wgsl and downlevel.wgsl
+
 struct Tile {
+  model_matrix: mat4x4<f32>,
   model_view_matrix: mat4x4<f32>,
   clipping_rect: vec4<f32>,
 }
@@ -7,6 +10,10 @@ struct View {
   view_matrix: mat4x4<f32>,
   width: u32,
   height: u32,
+  sample_count: u32,
+  pixel_ratio: f32,
+  eye: vec4<f32>,
+  globe: vec4<f32>, // center x, center y, radius, transition
 }
 
 struct Style {
@@ -15,19 +22,31 @@ struct Style {
   stroke_width: f32,
 }
 
+struct Layer {
+  opacity: f32,
+}
+
+// feature inputs are the index in the features of the tile, 0xFFFFFFFF for none
 struct VertexInput {
   @location(0) position: vec2<f32>,
   @location(1) normal: vec2<f32>,
+  @location(2) tile_index: u32,
+  @location(3) feature: u32,
 }
 
-struct FragmentOutput {
-  @location(0) color: vec4<f32>,
-  @builtin(sample_mask) mask_out: u32,
+struct FillFragmentInput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) tile_position: vec2<f32>,
+  @location(1) facing: f32,
+  @location(2) @interpolate(flat) tile_index: u32,
 }
 
-struct FragmentInput {
+struct RasterFragmentInput {
   @builtin(position) position: vec4<f32>,
-  @location(0) @interpolate(linear, center) normal: vec2<f32>,
+  @location(0) tile_position: vec2<f32>,
+  @location(1) facing: f32,
+  @location(2) @interpolate(flat) tile_index: u32,
+  @location(3) texture_position: vec2<f32>,
 }
 
 @group(0) @binding(0)
@@ -36,58 +55,220 @@ var<uniform> view: View;
 @group(1) @binding(0)
 var<uniform> style: Style;
 
-@group(2) @binding(0)
-var<uniform> tile: Tile;
+// material of raster tiles instead of the style, bound for every tile
+@group(1) @binding(1)
+var raster_texture: texture_2d<f32>;
+
+@group(1) @binding(2)
+var raster_sampler: sampler;
+
+// tile of the current vertex or fragment, set at the start of every entry point
+var<private> tile: Tile;
+
+@group(3) @binding(0)
+var<uniform> layer: Layer;
+
+// POINT_SIZE is prepended by the material manager from the constant in Rust
+
+const TILE_SIZE: f32 = 4096.0; // extent of the tile coordinates
+
+// standard 2x msaa sample positions relative to the pixel center
+const SAMPLE_OFFSETS_2X = array<vec2<f32>, 2>(
+  vec2<f32>(0.25, 0.25),
+  vec2<f32>(-0.25, -0.25),
+);
+
+// standard 4x msaa sample positions relative to the pixel center
+const SAMPLE_OFFSETS_4X = array<vec2<f32>, 4>(
+  vec2<f32>(-0.125, -0.375),
+  vec2<f32>(0.375, -0.125),
+  vec2<f32>(-0.375, 0.125),
+  vec2<f32>(0.125, 0.375),
+);
+
+// standard 8x msaa sample positions relative to the pixel center, in 1/16 pixels
+const SAMPLE_OFFSETS_8X = array<vec2<f32>, 8>(
+  vec2<f32>(1.0, -3.0),
+  vec2<f32>(-1.0, 3.0),
+  vec2<f32>(5.0, 1.0),
+  vec2<f32>(-3.0, -5.0),
+  vec2<f32>(-5.0, 5.0),
+  vec2<f32>(-7.0, -1.0),
+  vec2<f32>(3.0, 7.0),
+  vec2<f32>(7.0, -7.0),
+);
 
-const GLOBAL_SCALE: f32 = 0.0032; // can this be calculated?
+const EARTH_RADIUS: f32 = 6378137.0;
+
+const PI: f32 = 3.141592653589793;
+
+fn get_latitude(y: f32) -> f32 {
+  return 2.0 * atan(exp(y / EARTH_RADIUS)) - 0.5 * PI;
+}
+
+// position on the globe in world-space, the globe touches the map plane at the view center
+fn get_globe_position(map_position: vec2<f32>) -> vec3<f32> {
+  var center_latitude = get_latitude(view.globe.y);
+  var latitude = get_latitude(map_position.y);
+  var delta_longitude = (map_position.x - view.globe.x) / EARTH_RADIUS;
+  var sphere_position = vec3<f32>(
+    cos(latitude) * sin(delta_longitude),
+    cos(center_latitude) * sin(latitude) - sin(center_latitude) * cos(latitude) * cos(delta_longitude),
+    sin(center_latitude) * sin(latitude) + cos(center_latitude) * cos(latitude) * cos(delta_longitude),
+  );
+  return vec3<f32>(view.globe.xy, -view.globe.z) + sphere_position * view.globe.z;
+}
+
+// tile coordinates to clip space, blends between globe and map plane
+fn project(pos: vec2<f32>) -> vec4<f32> {
+  if (view.globe.w <= 0.0) {
+    return tile.model_view_matrix * vec4<f32>(pos, 0.0, 1.0);
+  }
+  var map_position = (tile.model_matrix * vec4<f32>(pos, 0.0, 1.0)).xy;
+  var position = mix(vec3<f32>(map_position, 0.0), get_globe_position(map_position), view.globe.w);
+  return view.view_matrix * vec4<f32>(position, 1.0);
+}
+
+// negative on the back side of the globe
+fn get_facing(pos: vec2<f32>) -> f32 {
+  if (view.globe.w <= 0.0) {
+    return 1.0;
+  }
+  var globe_position = get_globe_position((tile.model_matrix * vec4<f32>(pos, 0.0, 1.0)).xy);
+  var globe_center = vec3<f32>(view.globe.xy, -view.globe.z);
+  return dot(globe_position - globe_center, view.eye.xyz - globe_position);
+}
+
+fn get_half_size() -> vec2<f32> {
+  return vec2<f32>(f32(view.width), f32(view.height)) * 0.5;
+}
+
+// offset a clip space position by a distance in css pixels
+fn offset_in_pixels(position: vec4<f32>, offset: vec2<f32>) -> vec4<f32> {
+  return vec4<f32>(position.xy + offset * view.pixel_ratio / get_half_size() * position.w, position.zw);
+}
 
 @vertex
 fn vs_fill(
-  @location(0) pos: vec2<f32>
-) -> @builtin(position) vec4<f32> {
-  return tile.model_view_matrix * vec4<f32>(pos, 0.0, 1.0);
+  @location(0) pos: vec2<f32>,
+  @location(1) tile_index: u32,
+  @location(2) feature: u32,
+) -> FillFragmentInput {
+  tile = get_tile(tile_index);
+  return FillFragmentInput(project(pos), pos, get_facing(pos), tile_index);
 }
 
 @vertex
 fn vs_stroke(vertex: VertexInput) -> FragmentInput {
-  // TODO: precalculate (scale * GLOBAL_SCALE * style.stroke_width) on cpu
-  var scale = 1.0 / (view.view_matrix[0][0] * f32(view.width));
-  var delta = vec2<f32>(vertex.normal * scale * GLOBAL_SCALE * style.stroke_width);
-  var position = tile.model_view_matrix * vec4<f32>(vertex.position + delta, 0.0, 1.0);
-  return FragmentInput(position, vertex.normal);
+  tile = get_tile(vertex.tile_index);
+  // project the line direction to get the normal in screen space, the tile space normal
+  // isn't perpendicular anymore after rotating or tilting the view
+  var direction = vec2<f32>(vertex.normal.y, -vertex.normal.x);
+  var position = project(vertex.position);
+  var next = project(vertex.position + direction);
+  var screen_direction = normalize((next.xy / next.w - position.xy / position.w) * get_half_size());
+  var screen_normal = vec2<f32>(-screen_direction.y, screen_direction.x);
+  position = offset_in_pixels(position, screen_normal * style.stroke_width);
+  return FragmentInput(position, vertex.normal, vertex.position, get_facing(vertex.position), vertex.tile_index);
+}
+
+@vertex
+fn vs_point(
+  @location(0) pos: vec2<f32>,
+  @location(1) point_location: vec2<f32>,
+  @location(2) tile_index: u32,
+  @location(3) feature: u32,
+) -> FillFragmentInput {
+  tile = get_tile(tile_index);
+  var position = offset_in_pixels(project(point_location), pos * POINT_SIZE);
+  return FillFragmentInput(position, point_location, get_facing(point_location), tile_index);
 }
 
+// grid from 0 to 1 over the tile, rows of the texture from top to bottom like the tile
 @vertex
-fn vs_point(@location(0) pos: vec2<f32>, @location(1) point_location: vec2<f32>) -> @builtin(position) vec4<f32> {
-  var scale = 1.0 / (view.view_matrix[0][0] * f32(view.width));
-  return tile.model_view_matrix * vec4<f32>((pos * 0.02 * scale) + point_location, 0.0, 1.0);
-}
-
-fn clipping_and_premul_alpha(position: vec4<f32>, input_color: vec4<f32>) -> FragmentOutput {
-  var color = input_color.a * vec4<f32>(input_color.rgb, 1.0); // pre-multiplied alpha
-  var fragment_output = FragmentOutput(color, 0xFFFFFFFFu);
-
-  if (
-    position.x < tile.clipping_rect[0] ||
-    position.y < tile.clipping_rect[1] ||
-    position.x > tile.clipping_rect[2] ||
-    position.y > tile.clipping_rect[3]
-  ) {
-    fragment_output.mask_out = 0u;
+fn vs_raster(
+  @location(0) grid_position: vec2<f32>,
+  @location(1) tile_index: u32,
+) -> RasterFragmentInput {
+  tile = get_tile(tile_index);
+  var pos = grid_position * TILE_SIZE;
+  return RasterFragmentInput(project(pos), pos, get_facing(pos), tile_index, grid_position);
+}
+
+fn is_inside_clipping_rect(tile_position: vec2<f32>) -> bool {
+  return tile_position.x >= tile.clipping_rect[0] &&
+    tile_position.y >= tile.clipping_rect[1] &&
+    tile_position.x <= tile.clipping_rect[2] &&
+    tile_position.y <= tile.clipping_rect[3];
+}
+
+// the renderer only uses sample counts with standard positions
+fn get_sample_offset(index: u32) -> vec2<f32> {
+  switch (view.sample_count) {
+    case 2u: {
+      var sample_offsets = SAMPLE_OFFSETS_2X;
+      return sample_offsets[index];
+    }
+    case 8u: {
+      var sample_offsets = SAMPLE_OFFSETS_8X;
+      return sample_offsets[index] / 16.0;
+    }
+    default: {
+      var sample_offsets = SAMPLE_OFFSETS_4X;
+      return sample_offsets[index];
+    }
   }
-  return fragment_output;
+}
+
+// clip every sample on its own, tile position is extrapolated to the sample positions
+fn get_sample_mask(tile_position: vec2<f32>) -> u32 {
+  var dx = dpdx(tile_position);
+  var dy = dpdy(tile_position);
+
+  if (view.sample_count <= 1u) {
+    return select(0u, 0xFFFFFFFFu, is_inside_clipping_rect(tile_position));
+  }
+
+  var mask = 0u;
+  for (var i = 0u; i < view.sample_count; i++) {
+    var offset = get_sample_offset(i);
+    if (is_inside_clipping_rect(tile_position + dx * offset.x + dy * offset.y)) {
+      mask |= 1u << i;
+    }
+  }
+  return mask;
+}
+
+fn clipping_and_premul_alpha(tile_position: vec2<f32>, facing: f32, input_color: vec4<f32>) -> FragmentOutput {
+  var alpha = input_color.a * layer.opacity;
+  var color = alpha * vec4<f32>(input_color.rgb, 1.0); // pre-multiplied alpha
+  var mask = get_sample_mask(tile_position);
+
+  if (facing < 0.0) {
+    mask = 0u;
+  }
+  return get_fragment_output(color, mask);
 }
 
 @fragment
-fn fs_fill(@builtin(position) position: vec4<f32>) -> FragmentOutput {
-  return clipping_and_premul_alpha(position, style.fill_color);
+fn fs_fill(input: FillFragmentInput) -> FragmentOutput {
+  tile = get_tile(input.tile_index);
+  return clipping_and_premul_alpha(input.tile_position, input.facing, style.fill_color);
 }
 
 @fragment
 fn fs_stroke(input: FragmentInput) -> FragmentOutput {
+  tile = get_tile(input.tile_index);
   var distance = length(input.normal);
-  var blur = 0.8;
-  var alpha = (1.0 - distance) / (blur / style.stroke_width);
+  var blur = 0.8; // in device pixels
+  var alpha = (1.0 - distance) / (blur / (style.stroke_width * view.pixel_ratio));
   var color = vec4<f32>(style.stroke_color.rgb, alpha);
-  return clipping_and_premul_alpha(input.position, color);
+  return clipping_and_premul_alpha(input.tile_position, input.facing, color);
+}
+
+@fragment
+fn fs_raster(input: RasterFragmentInput) -> FragmentOutput {
+  tile = get_tile(input.tile_index);
+  var color = textureSample(raster_texture, raster_sampler, input.texture_position);
+  return clipping_and_premul_alpha(input.tile_position, input.facing, color);
 }
\ No newline at end of file
@@ -0,0 +1,24 @@
+struct FragmentOutput {
+  @location(0) color: vec4<f32>,
+  @builtin(sample_mask) mask_out: u32,
+}
+
+struct FragmentInput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(linear, center) normal: vec2<f32>,
+  @location(1) tile_position: vec2<f32>,
+  @location(2) facing: f32,
+  @location(3) @interpolate(flat) tile_index: u32,
+}
+
+// transforms of all tiles, indexed by the transform slot of the tile
+@group(2) @binding(0)
+var<storage, read> tiles: array<Tile>;
+
+fn get_tile(index: u32) -> Tile {
+  return tiles[index];
+}
+
+fn get_fragment_output(color: vec4<f32>, mask: u32) -> FragmentOutput {
+  return FragmentOutput(color, mask);
+}
@@ -0,0 +1,29 @@
+// WebGL2 has no storage buffers, no sample mask output and no linear interpolation
+
+struct FragmentOutput {
+  @location(0) color: vec4<f32>,
+}
+
+struct FragmentInput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) normal: vec2<f32>,
+  @location(1) tile_position: vec2<f32>,
+  @location(2) facing: f32,
+  @location(3) @interpolate(flat) tile_index: u32,
+}
+
+// transform of the drawn tile, bound with a dynamic offset for every draw
+@group(2) @binding(0)
+var<uniform> tile_uniform: Tile;
+
+fn get_tile(index: u32) -> Tile {
+  return tile_uniform;
+}
+
+// clipping is done per pixel instead of per sample
+fn get_fragment_output(color: vec4<f32>, mask: u32) -> FragmentOutput {
+  if (mask == 0u) {
+    discard;
+  }
+  return FragmentOutput(color);
+}
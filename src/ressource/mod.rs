@@ -1,11 +1,24 @@
-use std::{borrow::Cow, cell::RefCell, collections::HashMap, sync::Arc};
+use std::{
+  borrow::Cow,
+  cell::{Ref, RefCell},
+  collections::HashMap,
+  rc::Rc,
+  sync::Arc,
+};
 
+use arena::{Allocator, ArenaType, Arenas, Slot};
+use layer::LayerManager;
 use material::{Material, MaterialManager, MaterialType};
 use wgpu::util::DeviceExt;
 
 use self::tile::{Bucket, BucketType, Tile, TileManager};
+use crate::error::Error;
 
+pub mod arena;
+pub mod layer;
 mod material;
+pub mod projection;
+pub mod source;
 pub mod tile;
 pub mod view;
 
@@ -14,6 +27,7 @@ enum BindGroupScope {
   Global = 0,
   Material = 1,
   Model = 2,
+  Layer = 3,
 }
 
 impl BindGroupScope {
@@ -22,6 +36,7 @@ impl BindGroupScope {
       BindGroupScope::Global => BindGroupScope::Global as usize,
       BindGroupScope::Material => BindGroupScope::Material as usize,
       BindGroupScope::Model => BindGroupScope::Model as usize,
+      BindGroupScope::Layer => BindGroupScope::Layer as usize,
     }
   }
 }
@@ -35,19 +50,47 @@ enum ShaderModuleScope {
 pub struct RessourceManager {
   device: wgpu::Device,
 
+  queue: wgpu::Queue,
+
   texture_format: wgpu::TextureFormat,
 
+  /// msaa sample count of all render pipelines
+  sample_count: u32,
+
+  /// no storage buffers in the render pipelines, e.g. WebGL2
+  downlevel: bool,
+
   material_manager: Option<RefCell<MaterialManager>>,
 
   tile_manager: Option<RefCell<TileManager>>,
 
-  bind_group_layouts: [wgpu::BindGroupLayout; 3],
+  layer_manager: Option<RefCell<LayerManager>>,
+
+  bind_group_layouts: [wgpu::BindGroupLayout; 4],
+
+  /// material bind group layout of raster tiles, texture and sampler of the tile
+  raster_bind_group_layout: wgpu::BindGroupLayout,
+
+  /// linear and clamped to the edge, so neighbouring tiles don't bleed into each other
+  raster_sampler: wgpu::Sampler,
 
   shader_modules: HashMap<ShaderModuleScope, wgpu::ShaderModule>,
+
+  /// geometry of all tiles
+  arenas: RefCell<Arenas>,
+
+  /// indices of the tile transforms in the storage buffer
+  transform_slots: Rc<RefCell<Allocator>>,
 }
 
 impl RessourceManager {
-  pub fn new(device: wgpu::Device, texture_format: wgpu::TextureFormat) -> Self {
+  pub fn new(
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    texture_format: wgpu::TextureFormat,
+    sample_count: u32,
+    downlevel: bool,
+  ) -> Self {
     let empty_desc = &wgpu::BindGroupLayoutDescriptor {
       label: None,
       entries: &[],
@@ -56,17 +99,55 @@ impl RessourceManager {
       device.create_bind_group_layout(empty_desc),
       device.create_bind_group_layout(empty_desc),
       device.create_bind_group_layout(empty_desc),
+      device.create_bind_group_layout(empty_desc),
     ];
+    let raster_bind_group_layout =
+      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[
+          wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+              sample_type: wgpu::TextureSampleType::Float { filterable: true },
+              view_dimension: wgpu::TextureViewDimension::D2,
+              multisampled: false,
+            },
+            count: None,
+          },
+          wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+          },
+        ],
+      });
+    let raster_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()
+    });
+    let arenas = RefCell::new(Arenas::new(&device, downlevel));
     let mut manager = Self {
       device,
+      queue,
       texture_format,
+      sample_count,
+      downlevel,
       material_manager: None,
       tile_manager: None,
+      layer_manager: None,
       bind_group_layouts,
+      raster_bind_group_layout,
+      raster_sampler,
       shader_modules: HashMap::new(),
+      arenas,
+      transform_slots: Rc::new(RefCell::new(Allocator::default())),
     };
     manager.material_manager = Some(RefCell::new(MaterialManager::new(&mut manager)));
     manager.tile_manager = Some(RefCell::new(TileManager::new(&mut manager)));
+    manager.layer_manager = Some(RefCell::new(LayerManager::new(&mut manager)));
     manager
   }
 
@@ -103,19 +184,97 @@ impl RessourceManager {
     self.device.create_buffer_init(desc)
   }
 
+  pub(self) fn create_buffer(&self, desc: &wgpu::BufferDescriptor) -> wgpu::Buffer {
+    self.device.create_buffer(desc)
+  }
+
+  pub(self) fn create_texture_with_data(
+    &self,
+    desc: &wgpu::TextureDescriptor,
+    data: &[u8],
+  ) -> wgpu::Texture {
+    self.device.create_texture_with_data(
+      &self.queue,
+      desc,
+      wgpu::util::TextureDataOrder::LayerMajor,
+      data,
+    )
+  }
+
+  /// material bind group of a raster tile
+  pub(self) fn create_raster_bind_group(&self, view: &wgpu::TextureView) -> wgpu::BindGroup {
+    self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: None,
+      layout: &self.raster_bind_group_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::TextureView(view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: wgpu::BindingResource::Sampler(&self.raster_sampler),
+        },
+      ],
+    })
+  }
+
+  /// copy the contents into a new slot of the arena
+  pub(self) fn upload(&self, arena_type: ArenaType, contents: &[u8]) -> Result<Slot, Error> {
+    let slot = self.allocate(arena_type, contents.len() as u64)?;
+    let arenas = self.arenas.borrow();
+    let buffer = arenas.get(arena_type).get_buffer();
+    self.queue.write_buffer(buffer, slot.get_offset(), contents);
+    Ok(slot)
+  }
+
+  /// slot of the arena for geometry written on the gpu
+  pub fn allocate(&self, arena_type: ArenaType, size: u64) -> Result<Slot, Error> {
+    self
+      .arenas
+      .borrow_mut()
+      .get_mut(arena_type)
+      .allocate(&self.device, &self.queue, size)
+  }
+
+  pub fn get_arenas(&self) -> Ref<'_, Arenas> {
+    self.arenas.borrow()
+  }
+
+  pub fn is_downlevel(&self) -> bool {
+    self.downlevel
+  }
+
+  pub(self) fn allocate_transform_slot(&self) -> Slot {
+    Slot::new(&self.transform_slots, 1)
+  }
+
+  /// number of transforms the transforms buffer needs to hold
+  pub(self) fn get_transform_slot_count(&self) -> u64 {
+    self.transform_slots.borrow().get_end()
+  }
+
+  /// raster pipelines bind the texture of each tile as material instead of the style
   pub(self) fn create_render_pipeline(
     &self,
     vertex_state: wgpu::VertexState,
     fragment_state: wgpu::FragmentState,
+    raster: bool,
   ) -> wgpu::RenderPipeline {
+    let material_layout = if raster {
+      &self.raster_bind_group_layout
+    } else {
+      &self.bind_group_layouts[1]
+    };
     let pipeline_layout = self
       .device
       .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: None,
         bind_group_layouts: &[
           &self.bind_group_layouts[0],
-          &self.bind_group_layouts[1],
+          material_layout,
           &self.bind_group_layouts[2],
+          &self.bind_group_layouts[3],
         ],
         push_constant_ranges: &[],
       });
@@ -127,7 +286,10 @@ impl RessourceManager {
         vertex: vertex_state,
         fragment: Some(fragment_state),
         primitive: wgpu::PrimitiveState::default(),
-        multisample: wgpu::MultisampleState::default(),
+        multisample: wgpu::MultisampleState {
+          count: self.sample_count,
+          ..Default::default()
+        },
         depth_stencil: None,
         multiview: None,
         cache: None,
@@ -147,6 +309,7 @@ impl RessourceManager {
       BucketType::Fill => Bucket::<F, { BucketType::Fill }>::new(self, extent),
       BucketType::Line => Bucket::<F, { BucketType::Line }>::new(self, extent),
       BucketType::Point => Bucket::<F, { BucketType::Point }>::new(self, extent),
+      BucketType::Raster => Bucket::<F, { BucketType::Raster }>::new(self, extent),
     }
   }
 
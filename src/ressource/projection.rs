@@ -0,0 +1,141 @@
+use std::f32::consts::PI;
+
+use crate::error::Error;
+
+/// radius of the web mercator sphere
+pub const EARTH_RADIUS: f32 = 6_378_137.0;
+
+/// projected coordinate reference system the tile extents and the camera are expressed in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projection {
+  /// projection code as used by OpenLayers, e.g. EPSG:3035
+  code: String,
+
+  /// validity extent of the projection in map units [min_x, min_y, max_x, max_y]
+  extent: [f32; 4],
+
+  /// resolution (map units per pixel) at zoom level 0 of the tile grid
+  max_resolution: f32,
+
+  /// resolutions of the zoom levels of a tile grid that doesn't halve the resolution
+  /// with every level, descending
+  resolutions: Option<Vec<f32>>,
+}
+
+impl Default for Projection {
+  fn default() -> Self {
+    let half_world_size = PI * EARTH_RADIUS;
+    Self::new(
+      "EPSG:3857",
+      [
+        -half_world_size,
+        -half_world_size,
+        half_world_size,
+        half_world_size,
+      ],
+      2.0 * half_world_size / 256.0,
+      None,
+    )
+    .unwrap()
+  }
+}
+
+impl Projection {
+  /// the extent needs a positive width and height. resolutions replace the max
+  /// resolution if given, they need at least two positive and descending values
+  pub fn new(
+    code: &str,
+    extent: [f32; 4],
+    max_resolution: f32,
+    resolutions: Option<Vec<f32>>,
+  ) -> Result<Self, Error> {
+    let [min_x, min_y, max_x, max_y] = extent;
+    if !extent.iter().all(|value| value.is_finite()) || min_x >= max_x || min_y >= max_y {
+      return Err(Error::InvalidExtent(extent.to_vec()));
+    }
+
+    let max_resolution = match resolutions.as_deref() {
+      Some(resolutions @ [first, _, ..])
+        if resolutions.windows(2).all(|pair| pair[0] > pair[1])
+          && is_valid_resolution(resolutions[resolutions.len() - 1]) =>
+      {
+        *first
+      }
+      Some(resolutions) => return Err(Error::InvalidResolution(resolutions.to_vec())),
+      None if is_valid_resolution(max_resolution) => max_resolution,
+      None => return Err(Error::InvalidResolution(vec![max_resolution])),
+    };
+
+    Ok(Self {
+      code: code.to_owned(),
+      extent,
+      max_resolution,
+      resolutions,
+    })
+  }
+
+  pub fn get_extent(&self) -> [f32; 4] {
+    self.extent
+  }
+
+  /// resolutions are interpolated between the zoom levels of the grid like in OpenLayers,
+  /// and don't get smaller than the last one
+  pub fn get_resolution(&self, zoom: f32) -> f32 {
+    let Some(resolutions) = self.resolutions.as_ref() else {
+      return self.max_resolution / zoom.exp2();
+    };
+    let level = (zoom.floor().max(0.0) as usize).min(resolutions.len() - 2);
+    let factor = resolutions[level] / resolutions[level + 1];
+    resolutions[level] / factor.powf((zoom - level as f32).clamp(0.0, 1.0))
+  }
+
+  /// only web mercator can be projected onto the globe
+  pub fn is_mercator(&self) -> bool {
+    matches!(
+      self.code.as_str(),
+      "EPSG:3857" | "EPSG:900913" | "EPSG:102100" | "EPSG:102113"
+    )
+  }
+}
+
+/// positive and finite, so the camera matrix can be inverted
+fn is_valid_resolution(resolution: f32) -> bool {
+  resolution.is_finite() && resolution > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resolutions() {
+    let extent = [0.0, 0.0, 1000.0, 1000.0];
+    let projection = Projection::new("EPSG:25832", extent, 100.0, None).unwrap();
+    assert_eq!(projection.get_resolution(0.0), 100.0);
+    assert_eq!(projection.get_resolution(2.0), 25.0);
+
+    let resolutions = vec![100.0, 25.0, 5.0];
+    let projection = Projection::new("EPSG:25832", extent, 1.0, Some(resolutions)).unwrap();
+    assert_eq!(projection.get_resolution(0.0), 100.0);
+    assert_eq!(projection.get_resolution(0.5), 50.0);
+    assert_eq!(projection.get_resolution(1.0), 25.0);
+    assert_eq!(projection.get_resolution(2.0), 5.0);
+    assert_eq!(projection.get_resolution(3.0), 5.0);
+  }
+
+  #[test]
+  fn invalid_projection() {
+    let extent = [0.0, 0.0, 1000.0, 1000.0];
+    let new = |extent, max_resolution, resolutions| {
+      Projection::new("EPSG:25832", extent, max_resolution, resolutions)
+    };
+    assert!(new([0.0, 0.0, 0.0, 1000.0], 100.0, None).is_err());
+    assert!(new([0.0, f32::NAN, 1000.0, 1000.0], 100.0, None).is_err());
+    assert!(new(extent, 0.0, None).is_err());
+    assert!(new(extent, -1.0, None).is_err());
+    assert!(new(extent, f32::NAN, None).is_err());
+    assert!(new(extent, 100.0, Some(vec![100.0])).is_err());
+    assert!(new(extent, 100.0, Some(vec![100.0, 100.0])).is_err());
+    assert!(new(extent, 100.0, Some(vec![100.0, 0.0])).is_err());
+  }
+}
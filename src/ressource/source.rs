@@ -0,0 +1,165 @@
+use std::{
+  rc::Rc,
+  sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+  },
+};
+
+use super::tile::Tile;
+use crate::{flatgeobuf::FlatGeobuf, geojson::GeoJsonIndex, pmtiles::PmTiles};
+
+/// data the tiles of a source are built from on demand
+#[derive(Clone)]
+pub enum SourceData {
+  GeoJson(Arc<GeoJsonIndex>),
+  PmTiles(Rc<PmTiles>),
+
+  FlatGeobuf(Rc<FlatGeobuf>),
+
+  #[cfg(not(target_arch = "wasm32"))]
+  MbTiles(Rc<crate::mbtiles::MbTiles>),
+}
+
+/// encoding of the tile data of a source
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TileFormat {
+  /// Mapbox Vector Tile
+  #[default]
+  Mvt,
+
+  /// MapLibre Tile
+  Mlt,
+}
+
+impl TileFormat {
+  pub fn new(name: &str) -> Option<Self> {
+    match name {
+      "mvt" => Some(TileFormat::Mvt),
+      "mlt" => Some(TileFormat::Mlt),
+      _ => None,
+    }
+  }
+}
+
+/// counter of the data generations of all sources
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+  GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// named set of tiles, e.g. of one tile service
+pub struct Source {
+  name: String,
+
+  /// layer names of the tile data in the order they appear first
+  source_layers: Vec<String>,
+
+  /// sorted by source layer, so layers are drawn on top of each other across all tiles.
+  /// tiles of the same source layer stay in the order they were added
+  tiles: Vec<Tile>,
+
+  /// none if the tiles are added from outside
+  data: Option<SourceData>,
+
+  format: TileFormat,
+
+  /// changes with the data, tiles parsed from other generations are dropped. unique
+  /// across sources, so a source added again with the same name doesn't match either
+  generation: u64,
+}
+
+impl Source {
+  pub fn new(name: String) -> Self {
+    Self {
+      name,
+      source_layers: Vec::new(),
+      tiles: Vec::new(),
+      data: None,
+      format: TileFormat::default(),
+      generation: next_generation(),
+    }
+  }
+
+  pub fn add_tile(&mut self, tile: Tile) {
+    let rank = self.get_source_layer_rank(tile.get_source_layer());
+    let index = self.tiles.partition_point(|other| {
+      self
+        .source_layers
+        .iter()
+        .position(|source_layer| source_layer == other.get_source_layer())
+        .is_some_and(|other_rank| other_rank <= rank)
+    });
+    self.tiles.insert(index, tile);
+  }
+
+  /// unknown source layers are drawn on top of the known ones
+  fn get_source_layer_rank(&mut self, source_layer: &str) -> usize {
+    match self
+      .source_layers
+      .iter()
+      .position(|candidate| candidate == source_layer)
+    {
+      Some(rank) => rank,
+      None => {
+        self.source_layers.push(source_layer.to_owned());
+        self.source_layers.len() - 1
+      }
+    }
+  }
+
+  /// tiles built from the previous data are removed
+  pub fn set_data(&mut self, data: SourceData) {
+    self.data = Some(data);
+    self.tiles.clear();
+    self.generation = next_generation();
+  }
+
+  pub fn get_data(&self) -> Option<SourceData> {
+    self.data.clone()
+  }
+
+  pub fn set_format(&mut self, format: TileFormat) {
+    self.format = format;
+  }
+
+  pub fn get_format(&self) -> TileFormat {
+    self.format
+  }
+
+  pub fn get_generation(&self) -> u64 {
+    self.generation
+  }
+
+  pub fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn get_tiles(&self) -> &[Tile] {
+    &self.tiles
+  }
+
+  pub fn get_tiles_mut(&mut self) -> &mut [Tile] {
+    &mut self.tiles
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geojson::GeoJsonIndex;
+
+  #[test]
+  fn new_generation_with_data() {
+    let mut source = Source::new("source".to_owned());
+    let other = Source::new("source".to_owned());
+    let generation = source.get_generation();
+    assert_ne!(generation, other.get_generation());
+
+    let index = GeoJsonIndex::new(r#"{"type":"FeatureCollection","features":[]}"#).unwrap();
+    source.set_data(SourceData::GeoJson(Arc::new(index)));
+    assert_ne!(source.get_generation(), generation);
+    assert_ne!(source.get_generation(), other.get_generation());
+  }
+}
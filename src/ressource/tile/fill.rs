@@ -1,116 +1,237 @@
+use std::collections::HashMap;
+
 use geo_types::Geometry::{MultiPolygon, Polygon};
 use log::{error, info};
 use mvt_reader::feature::Feature;
 
-use crate::ressource::{BindGroupScope, RessourceManager, material::MaterialType};
+use crate::{
+  error::Error,
+  ressource::{RessourceManager, arena::ArenaType, material::MaterialType},
+};
 
-use super::{Bucket, BucketType, Tile, TileUniform};
+use super::{
+  Bucket, BucketType, Buffers, FeatureRange, FillVertex, Tile, get_globe_segment_length,
+  get_segment_distance,
+};
 
 const DIMENSIONS: usize = 2;
 
-impl Tile {
-  fn add_polygon(&mut self, polygon: &geo_types::Polygon<f32>) {
-    let exterior = polygon.exterior();
-    let interior = polygon.interiors();
-    let mut vertex_count = exterior.0.len() - 1;
-    let mut rings = Vec::with_capacity(1 + interior.len());
-    rings.push(exterior);
-    interior.iter().for_each(|r| {
-      rings.push(r);
-      // ignore last coordinate (closed ring)
-      vertex_count += r.0.len() - 1;
-    });
-    let mut vertices = Vec::with_capacity(vertex_count * DIMENSIONS);
-    let mut hole_indices = Vec::new();
-    for (i, ring) in rings.iter().enumerate() {
-      // ignore last coordinate (closed ring)
-      let end = ring.0.len() - 1;
-      let coordinate_slice = &ring.0[..end];
-      for coord in coordinate_slice.iter() {
-        vertices.push(coord.x);
-        vertices.push(coord.y);
+/// triangulated polygons of the features, runs on the parser thread
+pub fn get_buffers(features: &[Feature], extent: [f32; 4]) -> Buffers {
+  let mut all_vertices = vec![];
+  let mut all_indices = vec![];
+  let mut ranges = vec![];
+  let segment_length = get_globe_segment_length(extent);
+
+  for feature in features.iter() {
+    let start = (all_vertices.len(), all_indices.len());
+    match feature.get_geometry() {
+      Polygon(polygon) => {
+        add_polygon(polygon, segment_length, &mut all_vertices, &mut all_indices);
+      }
+      MultiPolygon(multi_polygon) => {
+        for polygon in multi_polygon.iter() {
+          add_polygon(polygon, segment_length, &mut all_vertices, &mut all_indices);
+        }
       }
-      if i < rings.len() - 1 {
-        hole_indices.push(vertices.len() / DIMENSIONS)
+      _ => {
+        info!("Geometry type currently not supported");
       }
     }
+    let end = (all_vertices.len(), all_indices.len());
+    ranges.extend(FeatureRange::new(feature, start, end));
+  }
+  (all_vertices, all_indices, ranges)
+}
 
-    let earcut_result = earcutr::earcut(&vertices, &hole_indices, DIMENSIONS);
-    match earcut_result {
-      Ok(indices) => {
-        let offset = (self.vertex_buffer.len() / DIMENSIONS) as u32;
-        self.vertex_buffer.append(&mut vertices);
-        self
-          .index_buffer
-          .append(&mut indices.iter().map(|i| (*i as u32) + offset).collect());
-      }
-      Err(_) => {
-        error!("earcut parsing error");
-      }
+fn add_polygon(
+  polygon: &geo_types::Polygon<f32>,
+  segment_length: Option<f32>,
+  all_vertices: &mut Vec<f32>,
+  all_indices: &mut Vec<u32>,
+) {
+  let exterior = polygon.exterior();
+  let interior = polygon.interiors();
+  let mut vertex_count = exterior.0.len() - 1;
+  let mut rings = Vec::with_capacity(1 + interior.len());
+  rings.push(exterior);
+  interior.iter().for_each(|r| {
+    rings.push(r);
+    // ignore last coordinate (closed ring)
+    vertex_count += r.0.len() - 1;
+  });
+  let mut vertices = Vec::with_capacity(vertex_count * DIMENSIONS);
+  let mut hole_indices = Vec::new();
+  for (i, ring) in rings.iter().enumerate() {
+    // ignore last coordinate (closed ring)
+    let end = ring.0.len() - 1;
+    let coordinate_slice = &ring.0[..end];
+    for coord in coordinate_slice.iter() {
+      vertices.push(coord.x);
+      vertices.push(coord.y);
+    }
+    if i < rings.len() - 1 {
+      hole_indices.push(vertices.len() / DIMENSIONS)
+    }
+  }
+
+  let earcut_result = earcutr::earcut(&vertices, &hole_indices, DIMENSIONS);
+  match earcut_result {
+    Ok(indices) => {
+      let indices = match segment_length {
+        Some(segment_length) => subdivide(&mut vertices, &indices, segment_length),
+        None => indices,
+      };
+      let offset = (all_vertices.len() / DIMENSIONS) as u32;
+      all_vertices.append(&mut vertices);
+      all_indices.append(&mut indices.iter().map(|i| (*i as u32) + offset).collect());
+    }
+    Err(_) => {
+      error!("earcut parsing error");
     }
   }
 }
 
+/// any triangle contains the position or is within the tolerance of it
+pub fn intersects(
+  indices: &[u32],
+  get_vertex: impl Fn(u32) -> Option<glam::Vec2>,
+  position: glam::Vec2,
+  tolerance: f32,
+) -> bool {
+  indices.chunks_exact(3).any(|triangle| {
+    let (Some(a), Some(b), Some(c)) = (
+      get_vertex(triangle[0]),
+      get_vertex(triangle[1]),
+      get_vertex(triangle[2]),
+    ) else {
+      return false;
+    };
+    let edges = [(a, b), (b, c), (c, a)];
+    // same side of all edges, independent of the winding
+    let sides = edges.map(|(start, end)| (end - start).perp_dot(position - start));
+    let inside = (b - a).perp_dot(c - a) != 0.0
+      && (sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0));
+    inside
+      || edges
+        .iter()
+        .any(|(start, end)| get_segment_distance(position, *start, *end) <= tolerance)
+  })
+}
+
+/// split triangles at the midpoint of their longest edge until no edge is longer than
+/// max_length. neighbours split shared edges at the same vertex, so no cracks appear
+fn subdivide(vertices: &mut Vec<f32>, indices: &[usize], max_length: f32) -> Vec<usize> {
+  let mut result = Vec::with_capacity(indices.len());
+  let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+  let mut triangles: Vec<[usize; 3]> = indices
+    .chunks_exact(3)
+    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+    .collect();
+
+  while let Some(triangle) = triangles.pop() {
+    let position =
+      |i: usize| glam::Vec2::new(vertices[i * DIMENSIONS], vertices[i * DIMENSIONS + 1]);
+    let (edge, length_squared) = (0..3)
+      .map(|edge| {
+        let start = position(triangle[edge]);
+        let end = position(triangle[(edge + 1) % 3]);
+        (edge, start.distance_squared(end))
+      })
+      .max_by(|a, b| a.1.total_cmp(&b.1))
+      .unwrap();
+
+    if length_squared <= max_length * max_length {
+      result.extend_from_slice(&triangle);
+      continue;
+    }
+
+    let (a, b, c) = (
+      triangle[edge],
+      triangle[(edge + 1) % 3],
+      triangle[(edge + 2) % 3],
+    );
+    let midpoint = (position(a) + position(b)) * 0.5;
+    let m = *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+      vertices.push(midpoint.x);
+      vertices.push(midpoint.y);
+      vertices.len() / DIMENSIONS - 1
+    });
+    triangles.push([a, m, c]);
+    triangles.push([m, b, c]);
+  }
+  result
+}
+
 impl<F> Bucket<F, { BucketType::Fill }> for Tile {
   fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self {
-    let tile_uniform = TileUniform::default();
-    let tile_uniform_buffer =
-      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&[tile_uniform]),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-      });
-    let bind_group =
-      ressource_manager.create_bind_group(&BindGroupScope::Model, &[wgpu::BindGroupEntry {
-        binding: 0,
-        resource: tile_uniform_buffer.as_entire_binding(),
-      }]);
-
     Self {
       material: ressource_manager.get_material(MaterialType::Fill),
-      bind_group,
-      tile_uniform_buffer,
-      vertex_wgpu_buffer: None,
+      transform_slot: ressource_manager.allocate_transform_slot(),
+      vertex_slot: None,
       vertex_buffer: Vec::with_capacity(0),
-      index_wgpu_buffer: None,
+      index_slot: None,
       index_buffer: Vec::with_capacity(0),
-      instance_wgpu_buffer: None,
       extent,
+      source_layer: String::new(),
+      features: Vec::new(),
+      image: None,
+      texture_bind_group: None,
       bucket_type: BucketType::Fill,
     }
   }
 
-  fn add_features(&mut self, features: &mut Vec<Feature>, ressource_manager: &RessourceManager) {
-    for feature in features.iter() {
-      match feature.get_geometry() {
-        Polygon(polygon) => {
-          self.add_polygon(polygon);
-        }
-        MultiPolygon(multi_polygon) => {
-          for polygon in multi_polygon.iter() {
-            self.add_polygon(polygon);
-          }
-        }
-        _ => {
-          info!("Geometry type currently not supported");
-        }
+  /// vertices are uploaded with the index of their feature
+  fn upload(&mut self, ressource_manager: &RessourceManager) -> Result<(), Error> {
+    if self.index_buffer.is_empty() {
+      return Ok(());
+    }
+    let vertices: Vec<FillVertex> = self
+      .vertex_buffer
+      .chunks_exact(DIMENSIONS)
+      .zip(self.get_vertex_features())
+      .map(|(position, feature)| FillVertex {
+        position: [position[0], position[1]],
+        feature,
+      })
+      .collect();
+    self.vertex_slot =
+      Some(ressource_manager.upload(ArenaType::FillVertices, bytemuck::cast_slice(&vertices))?);
+    self.index_slot = Some(ressource_manager.upload(
+      ArenaType::FillIndices,
+      bytemuck::cast_slice(&self.index_buffer),
+    )?);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn subdivide_shared_edge() {
+    // two triangles of a square share the diagonal from 0 to 2
+    let mut vertices = vec![0.0, 0.0, 4.0, 0.0, 4.0, 4.0, 0.0, 4.0];
+    let indices = subdivide(&mut vertices, &[0, 1, 2, 0, 2, 3], 1.5);
+
+    let position =
+      |i: usize| glam::Vec2::new(vertices[i * DIMENSIONS], vertices[i * DIMENSIONS + 1]);
+    for triangle in indices.chunks_exact(3) {
+      for edge in 0..3 {
+        let length = position(triangle[edge]).distance(position(triangle[(edge + 1) % 3]));
+        assert!(length <= 1.5, "edge of length {length}");
       }
     }
 
-    self.vertex_wgpu_buffer = Some(ressource_manager.create_buffer_init(
-      &wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&self.vertex_buffer),
-        usage: wgpu::BufferUsages::VERTEX,
-      },
-    ));
-
-    self.index_wgpu_buffer = Some(ressource_manager.create_buffer_init(
-      &wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&self.index_buffer),
-        usage: wgpu::BufferUsages::INDEX,
-      },
-    ));
+    // every split creates one vertex, so no position exists twice
+    let positions: Vec<_> = (0..vertices.len() / DIMENSIONS).map(position).collect();
+    for (i, a) in positions.iter().enumerate() {
+      assert!(
+        positions[i + 1..].iter().all(|b| a != b),
+        "duplicate vertex {a}"
+      );
+    }
+    assert!(positions.contains(&glam::Vec2::new(2.0, 2.0)));
   }
 }
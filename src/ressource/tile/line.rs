@@ -1,37 +1,122 @@
+use geo_types::Geometry::{LineString, MultiLineString};
+use log::info;
 use mvt_reader::feature::Feature;
 
-use crate::ressource::{BindGroupScope, RessourceManager, material::MaterialType};
+use crate::{
+  error::Error,
+  ressource::{RessourceManager, material::MaterialType},
+};
 
-use super::{Bucket, BucketType, Tile, TileUniform};
+use super::{
+  Bucket, BucketType, Buffers, FeatureRange, Tile, get_globe_segment_length, get_segment_distance,
+};
+
+const DIMENSIONS: usize = 2;
+
+fn push_line(
+  line: &geo_types::LineString<f32>,
+  segment_length: Option<f32>,
+  all_vertices: &mut Vec<f32>,
+  all_indices: &mut Vec<u32>,
+) {
+  let mut vertices = Vec::with_capacity(line.0.len() * DIMENSIONS);
+  let mut indices = Vec::with_capacity(line.0.len());
+  let offset = (all_vertices.len() / DIMENSIONS) as u32;
+
+  for (i, coord) in line.0.iter().enumerate() {
+    // split long segments so they follow the curvature of the globe
+    if let (Some(segment_length), Some(previous)) = (segment_length, i.checked_sub(1)) {
+      let previous = line.0[previous];
+      let delta = *coord - previous;
+      let steps = (delta.x.hypot(delta.y) / segment_length).ceil() as usize;
+      for step in 1..steps {
+        let t = step as f32 / steps as f32;
+        vertices.push(previous.x + delta.x * t);
+        vertices.push(previous.y + delta.y * t);
+        indices.push((vertices.len() / DIMENSIONS) as u32 - 1 + offset);
+      }
+    }
+    vertices.push(coord.x);
+    vertices.push(coord.y);
+    indices.push((vertices.len() / DIMENSIONS) as u32 - 1 + offset);
+  }
+
+  if let Some(last) = indices.last() {
+    indices.push(*last); // separate linestring from the next one
+  }
+
+  all_vertices.append(&mut vertices);
+  all_indices.append(&mut indices);
+}
+
+/// linestrings of the features with separating indices, runs on the parser thread
+pub fn get_buffers(features: &[Feature], extent: [f32; 4]) -> Buffers {
+  let mut all_vertices = vec![];
+  let mut all_indices = vec![];
+  let mut ranges = vec![];
+  let segment_length = get_globe_segment_length(extent);
+
+  for feature in features.iter() {
+    let start = (all_vertices.len(), all_indices.len());
+    match feature.get_geometry() {
+      LineString(line) => {
+        push_line(line, segment_length, &mut all_vertices, &mut all_indices);
+      }
+      MultiLineString(multi_line) => {
+        for line in multi_line.0.iter() {
+          push_line(line, segment_length, &mut all_vertices, &mut all_indices);
+        }
+      }
+      _ => {
+        info!("Geometry type currently not supported");
+      }
+    }
+    let end = (all_vertices.len(), all_indices.len());
+    ranges.extend(FeatureRange::new(feature, start, end));
+  }
+  (all_vertices, all_indices, ranges)
+}
+
+/// any segment is within the tolerance of the position, segments are skipped at the
+/// separating indices like in the tessellation
+pub fn intersects(
+  indices: &[u32],
+  get_vertex: impl Fn(u32) -> Option<glam::Vec2>,
+  position: glam::Vec2,
+  tolerance: f32,
+) -> bool {
+  (0..indices.len().saturating_sub(1)).any(|x| {
+    let (i1, i2) = (indices[x], indices[x + 1]);
+    if i1 == i2 || (x > 0 && indices[x - 1] == i1) {
+      return false;
+    }
+    match (get_vertex(i1), get_vertex(i2)) {
+      (Some(start), Some(end)) => get_segment_distance(position, start, end) <= tolerance,
+      _ => false,
+    }
+  })
+}
 
 impl<F> Bucket<F, { BucketType::Line }> for Tile {
   fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self {
-    let tile_uniform = TileUniform::default();
-    let tile_uniform_buffer =
-      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&[tile_uniform]),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-      });
-    let bind_group =
-      ressource_manager.create_bind_group(&BindGroupScope::Model, &[wgpu::BindGroupEntry {
-        binding: 0,
-        resource: tile_uniform_buffer.as_entire_binding(),
-      }]);
-
     Self {
       material: ressource_manager.get_material(MaterialType::Line),
-      bind_group,
-      tile_uniform_buffer,
-      vertex_wgpu_buffer: None,
+      transform_slot: ressource_manager.allocate_transform_slot(),
+      vertex_slot: None,
       vertex_buffer: Vec::with_capacity(0),
-      index_wgpu_buffer: None,
+      index_slot: None,
       index_buffer: Vec::with_capacity(0),
-      instance_wgpu_buffer: None,
       extent,
+      source_layer: String::new(),
+      features: Vec::new(),
+      image: None,
+      texture_bind_group: None,
       bucket_type: BucketType::Line,
     }
   }
 
-  fn add_features(&mut self, _: &mut Vec<Feature>, _: &RessourceManager) {}
+  /// lines are tessellated on the gpu, see `Renderer::compute`
+  fn upload(&mut self, _: &RessourceManager) -> Result<(), Error> {
+    Ok(())
+  }
 }
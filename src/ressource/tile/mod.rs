@@ -1,121 +1,376 @@
-use std::{marker::ConstParamTy, mem::size_of, num::NonZeroU64, sync::Arc};
-
-use log::info;
-use mvt_reader::feature::Feature;
-
-use super::{BindGroupScope, RessourceManager, material::Material, view::View};
+use std::{
+  collections::HashMap, marker::ConstParamTy, mem::size_of, num::NonZeroU64, ops::Range, sync::Arc,
+};
+
+use geo_types::Geometry::{LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon};
+use mvt_reader::feature::{Feature, Value};
+
+use super::{
+  BindGroupScope, RessourceManager,
+  arena::{ArenaType, Arenas, Slot},
+  material::Material,
+  projection::EARTH_RADIUS,
+  view::View,
+};
+use crate::{error::Error, tessellation};
 
 mod fill;
 mod line;
 mod point;
+mod raster;
+
+pub use raster::RasterImage;
 
 const DIMENSIONS: usize = 2;
 
 const TILE_SIZE: f32 = 4096.0;
 
-#[derive(PartialEq, Eq, Clone, ConstParamTy)]
+/// maximum segment length in map units, so that geometry follows the curvature of the globe
+const GLOBE_SEGMENT_LENGTH: f32 = 2.0 * std::f32::consts::PI * EARTH_RADIUS / 64.0;
+
+/// raster tiles are drawn below the vector buckets of the same source layer
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, ConstParamTy)]
 pub enum BucketType {
+  Raster,
   Fill,
   Line,
   Point,
 }
 
+/// feature index of vertices which belong to no feature
+pub const NO_FEATURE: u32 = u32::MAX;
+
+/// size of one vertex of a fill in the arena
+pub const FILL_VERTEX_SIZE: u64 = size_of::<FillVertex>() as u64;
+
+/// size of one vertex of the line tessellation output in the arena, position, normal,
+/// feature index and padding
+pub const LINE_VERTEX_SIZE: u64 = (2 * DIMENSIONS * size_of::<f32>() + 2 * size_of::<u32>()) as u64;
+
+/// size of one point instance in the arena
+pub const POINT_INSTANCE_SIZE: u64 = size_of::<PointInstance>() as u64;
+
+/// vertices, indices and the ranges of the features in them
+pub type Buffers = (Vec<f32>, Vec<u32>, Vec<FeatureRange>);
+
+const RECT_VERTEX_BUFFER: [f32; 8] = [-0.5, -0.5, 0.5, -0.5, 0.5, 0.5, -0.5, 0.5];
+const RECT_INDICES_BUFFER: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
 #[repr(C)]
 #[derive(Default, Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
 struct TileUniform {
+  model_matrix: glam::Mat4,
   model_view_matrix: glam::Mat4,
   clipping_rect: [f32; 4],
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
+struct FillVertex {
+  position: [f32; 2],
+
+  /// index in the features of the tile
+  feature: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
+struct PointInstance {
+  position: [f32; 2],
+
+  /// slot of the tile transform
+  tile: u32,
+
+  /// index in the features of the tile
+  feature: u32,
+}
+
+/// identity of a feature and the range of its geometry in the buffers of the tile.
+/// vertices of point tiles are the points, indices of line tiles are the ones before
+/// the tessellation
+#[derive(Clone, Debug)]
+pub struct FeatureRange {
+  pub id: Option<u64>,
+
+  pub properties: HashMap<String, Value>,
+
+  pub vertices: Range<u32>,
+
+  pub indices: Range<u32>,
+}
+
+impl FeatureRange {
+  /// range between the lengths of the buffers before and after the feature was added,
+  /// none if it added no geometry
+  fn new(feature: &Feature, start: (usize, usize), end: (usize, usize)) -> Option<Self> {
+    (end.0 > start.0).then(|| Self {
+      id: feature.id,
+      properties: feature.properties.clone().unwrap_or_default(),
+      vertices: (start.0 / DIMENSIONS) as u32..(end.0 / DIMENSIONS) as u32,
+      indices: start.1 as u32..end.1 as u32,
+    })
+  }
+}
+
 pub struct TileManager;
 
-pub struct Tile {
-  material: Arc<Material>,
+/// buffers shared by all tiles. transforms of the drawn tiles are written once per frame
+/// into a storage buffer, indexed by the transform slot of the tile. downlevel devices
+/// bind the transform of every tile as uniform with a dynamic offset instead
+pub struct TileBuffers {
+  /// number of transforms the transforms buffer can hold
+  capacity: u64,
+
+  /// distance between two transforms, aligned to the uniform offset alignment on
+  /// downlevel devices
+  stride: u64,
+
+  downlevel: bool,
+
+  transforms_buffer: wgpu::Buffer,
+
+  /// transform slot of fills, lines and rasters as instance attribute, each slot is its
+  /// own index so the draw selects it with the first instance
+  tile_index_buffer: wgpu::Buffer,
 
   bind_group: wgpu::BindGroup,
 
-  tile_uniform_buffer: wgpu::Buffer,
+  /// quad drawn for every point instance
+  point_vertex_buffer: wgpu::Buffer,
+
+  point_index_buffer: wgpu::Buffer,
+
+  /// grid drawn for every raster tile
+  raster_vertex_buffer: wgpu::Buffer,
 
-  /// vertex buffer
-  vertex_wgpu_buffer: Option<wgpu::Buffer>,
+  raster_index_buffer: wgpu::Buffer,
+
+  data: Vec<u8>,
+}
+
+pub struct Tile {
+  material: Arc<Material>,
+
+  /// index of the tile transform in the transforms buffer
+  transform_slot: Slot,
+
+  /// vertices in the arena of the bucket type, instances for points
+  vertex_slot: Option<Slot>,
 
   vertex_buffer: Vec<f32>,
 
-  /// index buffer
-  index_wgpu_buffer: Option<wgpu::Buffer>,
+  /// indices in the arena of the bucket type
+  index_slot: Option<Slot>,
 
   index_buffer: Vec<u32>,
 
-  instance_wgpu_buffer: Option<wgpu::Buffer>,
-
   extent: [f32; 4],
 
+  /// name of the layer in the tile data
+  source_layer: String,
+
+  features: Vec<FeatureRange>,
+
+  /// retained pixels of raster tiles
+  image: Option<RasterImage>,
+
+  /// texture of raster tiles
+  texture_bind_group: Option<wgpu::BindGroup>,
+
   bucket_type: BucketType,
 }
 
 impl Tile {
-  pub fn add_buffers(&mut self, vertices_buffer: wgpu::Buffer, indices_buffer: wgpu::Buffer) {
-    self.vertex_wgpu_buffer = Some(vertices_buffer);
-    self.index_wgpu_buffer = Some(indices_buffer);
+  /// slots the line tessellation has written to
+  pub fn add_slots(&mut self, vertex_slot: Slot, index_slot: Slot) {
+    self.vertex_slot = Some(vertex_slot);
+    self.index_slot = Some(index_slot);
   }
 
-  pub fn render<'frame>(
-    &'frame self,
-    render_pass: &mut wgpu::RenderPass<'frame>,
-    queue: &wgpu::Queue,
-    view: &'frame View,
-  ) {
-    match (
-      self.vertex_wgpu_buffer.as_ref(),
-      self.index_wgpu_buffer.as_ref(),
-    ) {
-      (Some(vertex_buffer), Some(index_buffer)) => {
-        self.material.set(render_pass);
-        render_pass.set_bind_group(BindGroupScope::Model as u32, Some(&self.bind_group), &[]);
-
-        let (half_width, half_height) = view.get_half_size();
-        let tile_uniform =
-          get_transforms(view.get_view_matrix(), self.extent, half_width, half_height);
-
-        queue.write_buffer(
-          &self.tile_uniform_buffer,
-          0,
-          bytemuck::cast_slice(&[tile_uniform]),
-        );
+  /// keep the line geometry on the cpu, so it can be tessellated again after a device loss
+  pub fn retain_buffers(&mut self, vertices: Vec<f32>, indices: Vec<u32>) {
+    self.vertex_buffer = vertices;
+    self.index_buffer = indices;
+  }
 
-        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-
-        let end = index_buffer.size() as u32 / std::mem::size_of::<u32>() as u32;
-
-        match self.get_bucket_type() {
-          BucketType::Point => {
-            render_pass.set_vertex_buffer(1, self.instance_wgpu_buffer.as_ref().unwrap().slice(..));
-            let instance_end = (self.vertex_buffer.len() / DIMENSIONS) as _;
-            render_pass.draw_indexed(0..end, 0, 0..instance_end);
-          }
-          _ => {
-            render_pass.draw_indexed(0..end, 0, 0..1);
-          }
-        }
-      }
-      _ => {
-        info!("No features found")
+  pub fn get_retained_buffers(&self) -> (&[f32], &[u32]) {
+    (&self.vertex_buffer[..], &self.index_buffer[..])
+  }
+
+  /// upload geometry built by `get_buffers`, line tiles need to be tessellated
+  /// afterwards
+  pub fn add_buffers(
+    &mut self,
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+    ressource_manager: &RessourceManager,
+  ) -> Result<(), Error> {
+    self.vertex_buffer = vertices;
+    self.index_buffer = indices;
+
+    match self.get_bucket_type() {
+      BucketType::Fill => Bucket::<Feature, { BucketType::Fill }>::upload(self, ressource_manager),
+      BucketType::Line => Bucket::<Feature, { BucketType::Line }>::upload(self, ressource_manager),
+      BucketType::Point => {
+        Bucket::<Feature, { BucketType::Point }>::upload(self, ressource_manager)
       }
+      BucketType::Raster => Ok(()),
+    }
+  }
+
+  /// upload the pixels of a raster tile into its texture
+  pub fn add_image(
+    &mut self,
+    image: RasterImage,
+    ressource_manager: &RessourceManager,
+  ) -> Result<(), Error> {
+    self.image = Some(image);
+    Bucket::<Feature, { BucketType::Raster }>::upload(self, ressource_manager)
+  }
+
+  /// recreate the wgpu buffers from the retained cpu data, line tiles need to be
+  /// tessellated again afterwards. the data is kept if the upload fails
+  pub fn recreate(&mut self, ressource_manager: &RessourceManager) -> Result<(), Error> {
+    let mut tile = ressource_manager.create_tile::<Feature>(self.get_bucket_type(), self.extent);
+    tile.source_layer = std::mem::take(&mut self.source_layer);
+    tile.features = std::mem::take(&mut self.features);
+    let result = match self.image.take() {
+      Some(image) => tile.add_image(image, ressource_manager),
+      None => tile.add_buffers(
+        std::mem::take(&mut self.vertex_buffer),
+        std::mem::take(&mut self.index_buffer),
+        ressource_manager,
+      ),
+    };
+    *self = tile;
+    result
+  }
+
+  /// pipeline and style of the tile, only needs to be set if the previous tile has
+  /// another bucket type
+  pub fn set_material<'frame>(&'frame self, render_pass: &mut wgpu::RenderPass<'frame>) {
+    self.material.set(render_pass);
+  }
+
+  /// texture of a raster tile, needs to be set for every raster tile
+  pub fn set_texture<'frame>(&'frame self, render_pass: &mut wgpu::RenderPass<'frame>) {
+    if let Some(bind_group) = self.texture_bind_group.as_ref() {
+      render_pass.set_bind_group(BindGroupScope::Material as u32, Some(bind_group), &[]);
+    }
+  }
+
+  /// draw of the tile geometry in the arenas of the bucket type, none if the tile has
+  /// no geometry (yet)
+  pub fn get_draw_args(&self) -> Option<wgpu::util::DrawIndexedIndirectArgs> {
+    let tile = self.transform_slot.get_offset() as u32;
+    if self.bucket_type == BucketType::Raster {
+      self.texture_bind_group.as_ref()?;
+      return Some(wgpu::util::DrawIndexedIndirectArgs {
+        index_count: raster::GRID_INDEX_COUNT,
+        instance_count: 1,
+        first_index: 0,
+        base_vertex: 0,
+        first_instance: tile,
+      });
+    }
+    let vertex_slot = self.vertex_slot.as_ref()?;
+
+    match self.bucket_type {
+      BucketType::Fill => Some(wgpu::util::DrawIndexedIndirectArgs {
+        index_count: self.index_buffer.len() as u32,
+        instance_count: 1,
+        first_index: (self.index_slot.as_ref()?.get_offset() / size_of::<u32>() as u64) as u32,
+        base_vertex: (vertex_slot.get_offset() / FILL_VERTEX_SIZE) as i32,
+        first_instance: tile,
+      }),
+      BucketType::Line => Some(wgpu::util::DrawIndexedIndirectArgs {
+        index_count: tessellation::get_line_index_count(self.index_buffer.len()) as u32,
+        instance_count: 1,
+        first_index: (self.index_slot.as_ref()?.get_offset() / size_of::<u32>() as u64) as u32,
+        base_vertex: (vertex_slot.get_offset() / LINE_VERTEX_SIZE) as i32,
+        first_instance: tile,
+      }),
+      BucketType::Point => Some(wgpu::util::DrawIndexedIndirectArgs {
+        index_count: RECT_INDICES_BUFFER.len() as u32,
+        instance_count: (self.vertex_buffer.len() / DIMENSIONS) as u32,
+        first_index: 0,
+        base_vertex: 0,
+        first_instance: (vertex_slot.get_offset() / POINT_INSTANCE_SIZE) as u32,
+      }),
+      BucketType::Raster => None,
     }
   }
 
   pub fn get_bucket_type(&self) -> BucketType {
     self.bucket_type.clone()
   }
+
+  pub fn get_extent(&self) -> [f32; 4] {
+    self.extent
+  }
+
+  pub fn set_source_layer(&mut self, source_layer: String) {
+    self.source_layer = source_layer;
+  }
+
+  pub fn get_source_layer(&self) -> &str {
+    &self.source_layer
+  }
+
+  pub fn set_features(&mut self, features: Vec<FeatureRange>) {
+    self.features = features;
+  }
+
+  pub fn get_features(&self) -> &[FeatureRange] {
+    &self.features
+  }
+
+  /// feature index of every retained vertex, uploaded with the geometry
+  pub fn get_vertex_features(&self) -> Vec<u32> {
+    get_vertex_features(&self.features, self.vertex_buffer.len() / DIMENSIONS)
+  }
+
+  /// features drawn within the radius in css pixels around the position in map
+  /// coordinates, the last drawn first. resolution is given in map units per css pixel
+  pub fn get_features_at(
+    &self,
+    position: glam::Vec2,
+    resolution: f32,
+    radius: f32,
+  ) -> Vec<&FeatureRange> {
+    let position = get_model_matrix(self.extent, TILE_SIZE)
+      .inverse()
+      .transform_point3(position.extend(0.0))
+      .truncate();
+    // geometry outside of the tile is clipped
+    if position.cmplt(glam::Vec2::ZERO).any() || position.cmpgt(glam::Vec2::splat(TILE_SIZE)).any()
+    {
+      return Vec::new();
+    }
+
+    // raster tiles have no features
+    if self.bucket_type == BucketType::Raster {
+      return Vec::new();
+    }
+    let radius = radius + self.material.get_size() * 0.5;
+    let tolerance = radius * resolution * TILE_SIZE / (self.extent[2] - self.extent[0]);
+    get_features_at(
+      self.bucket_type.clone(),
+      (&self.vertex_buffer, &self.index_buffer),
+      &self.features,
+      position,
+      tolerance,
+    )
+  }
 }
 
-/// tile_transform * flip_tile_transform because of Y-axis swap
+/// tile_transform * flip_tile_transform because of Y-axis swap, tiles don't need to be
+/// square in map units
 #[rustfmt::skip]
 fn get_model_matrix(extent: [f32; 4], tile_size: f32) -> glam::Mat4 {
   let tile_transform = glam::Mat4::from_cols_array(&[
     (extent[2] - extent[0]) / tile_size, 0.0, 0.0, 0.0, // a11 a21 a31 a41
-    0.0, (extent[2] - extent[0]) / tile_size, 0.0, 0.0, // a12 a22 a32 a42
+    0.0, (extent[3] - extent[1]) / tile_size, 0.0, 0.0, // a12 a22 a32 a42
     0.0, 0.0, 1.0, 0.0,                                 // a13 a23 a33 a43
     extent[0], extent[1], 0.0, 1.0,                     // a14 a24 a34 a44
   ]);
@@ -128,40 +383,321 @@ fn get_model_matrix(extent: [f32; 4], tile_size: f32) -> glam::Mat4 {
   tile_transform.mul_mat4(&flip_tile_transform)
 }
 
-fn get_transforms(
-  view_matrix: glam::Mat4,
-  extent: [f32; 4],
-  half_width: f32,
-  half_height: f32,
-) -> TileUniform {
+fn get_transforms(view_matrix: glam::Mat4, extent: [f32; 4]) -> TileUniform {
   let model_matrix = get_model_matrix(extent, TILE_SIZE);
   let model_view_matrix = view_matrix.mul_mat4(&model_matrix);
 
-  let min_extent = model_view_matrix.mul_vec4(glam::Vec4::W);
-  let max_extent =
-    model_view_matrix.mul_vec4(glam::Vec4::from_slice(&[TILE_SIZE, TILE_SIZE, 0.0, 1.0]));
-
-  // convert from view to screen coordinates (pixels)
-  let clipping_rect = [
-    (min_extent[0] + 1.0) * half_width,
-    (-min_extent[1] + 1.0) * half_height,
-    (max_extent[0] + 1.0) * half_width,
-    (-max_extent[1] + 1.0) * half_height,
-  ];
+  // clipping happens in tile coordinates, so it stays correct for rotated and tilted views
+  let clipping_rect = [0.0, 0.0, TILE_SIZE, TILE_SIZE];
 
   TileUniform {
+    model_matrix,
     model_view_matrix,
     clipping_rect,
   }
 }
 
+/// cpu geometry of the features of a source layer, built on the parser thread so only
+/// the upload is left for the render thread. the bucket type is the one of the first
+/// feature, none if it has no supported geometry
+pub fn get_buffers(features: &[Feature], extent: [f32; 4]) -> Option<(BucketType, Buffers)> {
+  match features.first()?.get_geometry() {
+    Point(_) | MultiPoint(_) => Some((BucketType::Point, point::get_buffers(features))),
+    LineString(_) | MultiLineString(_) => {
+      Some((BucketType::Line, line::get_buffers(features, extent)))
+    }
+    Polygon(_) | MultiPolygon(_) => Some((BucketType::Fill, fill::get_buffers(features, extent))),
+    _ => None,
+  }
+}
+
+/// index of the feature of every vertex in the features, `NO_FEATURE` for vertices
+/// outside of all feature ranges
+fn get_vertex_features(features: &[FeatureRange], vertex_count: usize) -> Vec<u32> {
+  let mut vertex_features = vec![NO_FEATURE; vertex_count];
+  for (i, feature) in features.iter().enumerate() {
+    let range = feature.vertices.start as usize..feature.vertices.end as usize;
+    if let Some(vertices) = vertex_features.get_mut(range) {
+      vertices.fill(i as u32);
+    }
+  }
+  vertex_features
+}
+
+/// features of the buffers within the tolerance of the position, both in tile
+/// coordinates. the last drawn first
+fn get_features_at<'a>(
+  bucket_type: BucketType,
+  (vertices, indices): (&[f32], &[u32]),
+  features: &'a [FeatureRange],
+  position: glam::Vec2,
+  tolerance: f32,
+) -> Vec<&'a FeatureRange> {
+  let get_vertex = |i: u32| {
+    let i = i as usize * DIMENSIONS;
+    vertices
+      .get(i..i + DIMENSIONS)
+      .map(|vertex| glam::Vec2::new(vertex[0], vertex[1]))
+  };
+  let get_indices = |feature: &FeatureRange| {
+    indices
+      .get(feature.indices.start as usize..feature.indices.end as usize)
+      .unwrap_or_default()
+  };
+
+  features
+    .iter()
+    .rev()
+    .filter(|feature| match bucket_type {
+      BucketType::Fill => fill::intersects(get_indices(feature), get_vertex, position, tolerance),
+      BucketType::Line => line::intersects(get_indices(feature), get_vertex, position, tolerance),
+      BucketType::Point => feature
+        .vertices
+        .clone()
+        .filter_map(get_vertex)
+        .any(|point| point.distance(position) <= tolerance),
+      BucketType::Raster => false,
+    })
+    .collect()
+}
+
+/// distance of the position to the segment from start to end
+fn get_segment_distance(position: glam::Vec2, start: glam::Vec2, end: glam::Vec2) -> f32 {
+  let segment = end - start;
+  let length_squared = segment.length_squared();
+  let t = if length_squared > 0.0 {
+    ((position - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+  } else {
+    0.0
+  };
+  position.distance(start + segment * t)
+}
+
+/// maximum segment length in tile coordinates for the globe projection, none if the
+/// tile is small enough to stay flat
+pub fn get_globe_segment_length(extent: [f32; 4]) -> Option<f32> {
+  let segment_length = TILE_SIZE * GLOBE_SEGMENT_LENGTH / (extent[2] - extent[0]);
+  (segment_length < TILE_SIZE).then_some(segment_length)
+}
+
+impl TileBuffers {
+  const INITIAL_CAPACITY: u64 = 256;
+
+  pub fn new(ressource_manager: &RessourceManager) -> Self {
+    let downlevel = ressource_manager.downlevel;
+    let stride = if downlevel {
+      let alignment = ressource_manager
+        .device
+        .limits()
+        .min_uniform_buffer_offset_alignment as u64;
+      (size_of::<TileUniform>() as u64).div_ceil(alignment) * alignment
+    } else {
+      size_of::<TileUniform>() as u64
+    };
+    let (transforms_buffer, tile_index_buffer, bind_group) =
+      create_transforms_buffers(ressource_manager, Self::INITIAL_CAPACITY, stride);
+
+    let point_vertex_buffer =
+      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&RECT_VERTEX_BUFFER),
+        usage: wgpu::BufferUsages::VERTEX,
+      });
+
+    let point_index_buffer =
+      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&RECT_INDICES_BUFFER),
+        usage: wgpu::BufferUsages::INDEX,
+      });
+
+    let (grid_vertices, grid_indices) = raster::get_grid();
+    let raster_vertex_buffer =
+      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&grid_vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+      });
+
+    let raster_index_buffer =
+      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&grid_indices),
+        usage: wgpu::BufferUsages::INDEX,
+      });
+
+    Self {
+      capacity: Self::INITIAL_CAPACITY,
+      stride,
+      downlevel,
+      transforms_buffer,
+      tile_index_buffer,
+      bind_group,
+      point_vertex_buffer,
+      point_index_buffer,
+      raster_vertex_buffer,
+      raster_index_buffer,
+      data: Vec::new(),
+    }
+  }
+
+  /// upload the transforms of all drawn tiles with a single write, the buffers grow if
+  /// there are more transform slots than fit into them
+  pub fn write<'a>(
+    &mut self,
+    ressource_manager: &RessourceManager,
+    queue: &wgpu::Queue,
+    view: &View,
+    tiles: impl Iterator<Item = &'a Tile>,
+  ) {
+    let slot_count = ressource_manager.get_transform_slot_count();
+    if slot_count == 0 {
+      return;
+    }
+
+    if slot_count > self.capacity {
+      self.capacity = slot_count.next_power_of_two();
+      (
+        self.transforms_buffer,
+        self.tile_index_buffer,
+        self.bind_group,
+      ) = create_transforms_buffers(ressource_manager, self.capacity, self.stride);
+    }
+
+    self.data.clear();
+    self.data.resize((slot_count * self.stride) as usize, 0);
+    for tile in tiles {
+      let offset = (tile.transform_slot.get_offset() * self.stride) as usize;
+      let transforms = get_transforms(view.get_view_matrix(), tile.extent);
+      self.data[offset..offset + size_of::<TileUniform>()]
+        .copy_from_slice(bytemuck::bytes_of(&transforms));
+    }
+
+    queue.write_buffer(&self.transforms_buffer, 0, &self.data);
+  }
+
+  /// bind the transforms of all tiles, downlevel devices bind them per tile with
+  /// `set_tile`
+  pub fn set<'frame>(&'frame self, render_pass: &mut wgpu::RenderPass<'frame>) {
+    if !self.downlevel {
+      render_pass.set_bind_group(BindGroupScope::Model as u32, Some(&self.bind_group), &[]);
+    }
+  }
+
+  /// bind the transform of the tile and, because downlevel devices may not support a
+  /// base vertex, its vertices starting at the vertex slot. the tile is drawn with a
+  /// base vertex of 0 afterwards
+  pub fn set_tile<'frame>(
+    &'frame self,
+    render_pass: &mut wgpu::RenderPass<'frame>,
+    arenas: &'frame Arenas,
+    tile: &Tile,
+  ) {
+    render_pass.set_bind_group(
+      BindGroupScope::Model as u32,
+      Some(&self.bind_group),
+      &[(tile.transform_slot.get_offset() * self.stride) as u32],
+    );
+
+    let arena_type = match tile.bucket_type {
+      BucketType::Fill => ArenaType::FillVertices,
+      BucketType::Line => ArenaType::LineVertices,
+      BucketType::Point | BucketType::Raster => return,
+    };
+    if let Some(vertex_slot) = tile.vertex_slot.as_ref() {
+      let offset = vertex_slot.get_offset();
+      render_pass.set_vertex_buffer(
+        0,
+        arenas
+          .get(arena_type)
+          .get_buffer()
+          .slice(offset..offset + vertex_slot.get_size()),
+      );
+    }
+  }
+
+  /// bind the arenas of the bucket type, every tile of the bucket type can be drawn
+  /// with its draw args afterwards
+  pub fn set_vertex_buffers<'frame>(
+    &'frame self,
+    render_pass: &mut wgpu::RenderPass<'frame>,
+    arenas: &'frame Arenas,
+    bucket_type: BucketType,
+  ) {
+    let (vertices, indices) = match bucket_type {
+      BucketType::Fill => (
+        arenas.get(ArenaType::FillVertices).get_buffer(),
+        arenas.get(ArenaType::FillIndices).get_buffer(),
+      ),
+      BucketType::Line => (
+        arenas.get(ArenaType::LineVertices).get_buffer(),
+        arenas.get(ArenaType::LineIndices).get_buffer(),
+      ),
+      BucketType::Point => {
+        render_pass.set_vertex_buffer(0, self.point_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(
+          1,
+          arenas.get(ArenaType::PointInstances).get_buffer().slice(..),
+        );
+        render_pass.set_index_buffer(self.point_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        return;
+      }
+      BucketType::Raster => (&self.raster_vertex_buffer, &self.raster_index_buffer),
+    };
+    render_pass.set_vertex_buffer(0, vertices.slice(..));
+    render_pass.set_vertex_buffer(1, self.tile_index_buffer.slice(..));
+    render_pass.set_index_buffer(indices.slice(..), wgpu::IndexFormat::Uint32);
+  }
+}
+
+/// the uniform binding of downlevel devices only covers a single transform
+fn create_transforms_buffers(
+  ressource_manager: &RessourceManager,
+  capacity: u64,
+  stride: u64,
+) -> (wgpu::Buffer, wgpu::Buffer, wgpu::BindGroup) {
+  let (usage, size) = if ressource_manager.downlevel {
+    (
+      wgpu::BufferUsages::UNIFORM,
+      wgpu::BufferSize::new(size_of::<TileUniform>() as u64),
+    )
+  } else {
+    (wgpu::BufferUsages::STORAGE, None)
+  };
+  let transforms_buffer = ressource_manager.create_buffer(&wgpu::BufferDescriptor {
+    label: None,
+    size: capacity * stride,
+    usage: usage | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false,
+  });
+  let tile_indices: Vec<u32> = (0..capacity as u32).collect();
+  let tile_index_buffer = ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    label: None,
+    contents: bytemuck::cast_slice(&tile_indices),
+    usage: wgpu::BufferUsages::VERTEX,
+  });
+  let bind_group = ressource_manager.create_bind_group(
+    &BindGroupScope::Model,
+    &[wgpu::BindGroupEntry {
+      binding: 0,
+      resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+        buffer: &transforms_buffer,
+        offset: 0,
+        size,
+      }),
+    }],
+  );
+  (transforms_buffer, tile_index_buffer, bind_group)
+}
+
 pub trait Bucket<F, const T: BucketType>
 where
   Self: Sized,
 {
   fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self;
 
-  fn add_features(&mut self, features: &mut Vec<Feature>, ressource_manager: &RessourceManager);
+  /// copy the cpu buffers into the arenas of the bucket type
+  fn upload(&mut self, ressource_manager: &RessourceManager) -> Result<(), Error>;
 }
 
 impl TileManager {
@@ -174,8 +710,12 @@ impl TileManager {
           binding: 0,
           visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
           ty: wgpu::BindingType::Buffer {
-            ty: wgpu::BufferBindingType::Uniform,
-            has_dynamic_offset: false,
+            ty: if ressource_manager.downlevel {
+              wgpu::BufferBindingType::Uniform
+            } else {
+              wgpu::BufferBindingType::Storage { read_only: true }
+            },
+            has_dynamic_offset: ressource_manager.downlevel,
             min_binding_size: Some(
               NonZeroU64::new(size_of::<TileUniform>().try_into().unwrap()).unwrap(),
             ),
@@ -188,3 +728,161 @@ impl TileManager {
     Self
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use geo_types::{
+    Geometry, MultiLineString, MultiPoint, MultiPolygon, line_string, point, polygon,
+  };
+
+  use super::*;
+
+  fn get_ids(features: Vec<Feature>, position: (f32, f32), tolerance: f32) -> Vec<u64> {
+    let extent = [0.0, 0.0, TILE_SIZE, TILE_SIZE];
+    let (bucket_type, (vertices, indices, ranges)) = get_buffers(&features, extent).unwrap();
+    get_features_at(
+      bucket_type,
+      (&vertices, &indices),
+      &ranges,
+      glam::Vec2::new(position.0, position.1),
+      tolerance,
+    )
+    .iter()
+    .map(|feature| feature.id.unwrap())
+    .collect()
+  }
+
+  fn feature(id: u64, geometry: Geometry<f32>) -> Feature {
+    Feature {
+      geometry,
+      id: Some(id),
+      properties: None,
+    }
+  }
+
+  /// indices and the vertex and index ranges of the features, checks that every vertex
+  /// gets the index of its feature
+  fn get_ranges(features: &[Feature]) -> (Vec<u32>, Vec<[Range<u32>; 2]>) {
+    let extent = [0.0, 0.0, TILE_SIZE, TILE_SIZE];
+    let (_, (vertices, indices, ranges)) = get_buffers(features, extent).unwrap();
+    let vertex_features = get_vertex_features(&ranges, vertices.len() / DIMENSIONS);
+    assert_eq!(
+      vertex_features.len(),
+      ranges.last().unwrap().vertices.end as usize
+    );
+    for (i, range) in ranges.iter().enumerate() {
+      assert!(
+        vertex_features[range.vertices.start as usize..range.vertices.end as usize]
+          .iter()
+          .all(|feature| *feature == i as u32)
+      );
+    }
+    let ranges = ranges
+      .into_iter()
+      .map(|range| [range.vertices, range.indices])
+      .collect();
+    (indices, ranges)
+  }
+
+  #[test]
+  fn feature_ranges() {
+    let square = |offset: f32| {
+      polygon![
+        (x: offset, y: 0.0),
+        (x: offset + 10.0, y: 0.0),
+        (x: offset + 10.0, y: 10.0),
+        (x: offset, y: 10.0),
+      ]
+    };
+    let fills = [
+      feature(1, MultiPolygon::new(vec![square(0.0), square(20.0)]).into()),
+      feature(2, square(40.0).into()),
+    ];
+    let (_, ranges) = get_ranges(&fills);
+    assert_eq!(ranges, [[0..8, 0..12], [8..12, 12..18]]);
+
+    // every linestring ends with a repeated index, so no edge connects it to the next
+    let lines = [
+      feature(
+        3,
+        MultiLineString::new(vec![
+          line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0)],
+          line_string![(x: 0.0, y: 10.0), (x: 10.0, y: 10.0)],
+        ])
+        .into(),
+      ),
+      feature(
+        4,
+        line_string![(x: 0.0, y: 20.0), (x: 10.0, y: 20.0), (x: 20.0, y: 20.0)].into(),
+      ),
+    ];
+    let (indices, ranges) = get_ranges(&lines);
+    assert_eq!(indices, [0, 1, 1, 2, 3, 3, 4, 5, 6, 6]);
+    assert_eq!(ranges, [[0..4, 0..6], [4..7, 6..10]]);
+
+    let points = [
+      feature(
+        5,
+        MultiPoint::new(vec![point!(x: 0.0, y: 0.0), point!(x: 10.0, y: 10.0)]).into(),
+      ),
+      feature(6, point!(x: 5.0, y: 5.0).into()),
+    ];
+    let (_, ranges) = get_ranges(&points);
+    assert_eq!(ranges, [[0..2, 0..0], [2..3, 0..0]]);
+  }
+
+  #[test]
+  fn hit_test_features() {
+    let fills = || {
+      vec![
+        feature(
+          1,
+          polygon![(x: 0.0, y: 0.0), (x: 100.0, y: 0.0), (x: 100.0, y: 100.0), (x: 0.0, y: 100.0)]
+            .into(),
+        ),
+        feature(
+          2,
+          polygon![(x: 50.0, y: 50.0), (x: 150.0, y: 50.0), (x: 150.0, y: 150.0), (x: 50.0, y: 150.0)]
+            .into(),
+        ),
+      ]
+    };
+    assert_eq!(get_ids(fills(), (75.0, 75.0), 0.0), [2, 1]);
+    assert_eq!(get_ids(fills(), (25.0, 25.0), 0.0), [1]);
+    assert!(get_ids(fills(), (160.0, 75.0), 0.0).is_empty());
+    assert_eq!(get_ids(fills(), (160.0, 75.0), 20.0), [2]);
+
+    // no segment between the linestrings of a multi linestring
+    let lines = || {
+      vec![
+        feature(
+          3,
+          MultiLineString::new(vec![
+            line_string![(x: 0.0, y: 0.0), (x: 100.0, y: 0.0)],
+            line_string![(x: 0.0, y: 100.0), (x: 100.0, y: 100.0)],
+          ])
+          .into(),
+        ),
+        feature(
+          4,
+          line_string![(x: 0.0, y: 200.0), (x: 100.0, y: 200.0)].into(),
+        ),
+      ]
+    };
+    assert!(get_ids(lines(), (50.0, 50.0), 10.0).is_empty());
+    assert_eq!(get_ids(lines(), (50.0, 98.0), 5.0), [3]);
+    assert_eq!(get_ids(lines(), (50.0, 203.0), 5.0), [4]);
+
+    let points = || {
+      vec![
+        feature(
+          5,
+          MultiPoint::new(vec![point!(x: 10.0, y: 10.0), point!(x: 90.0, y: 90.0)]).into(),
+        ),
+        feature(6, point!(x: 50.0, y: 50.0).into()),
+      ]
+    };
+    assert_eq!(get_ids(points(), (88.0, 90.0), 3.0), [5]);
+    assert_eq!(get_ids(points(), (50.0, 50.0), 3.0), [6]);
+  }
+}
@@ -2,89 +2,83 @@ use geo_types::Geometry::{MultiPoint, Point};
 use log::info;
 use mvt_reader::feature::Feature;
 
-use crate::ressource::{BindGroupScope, RessourceManager, material::MaterialType};
+use crate::{
+  error::Error,
+  ressource::{RessourceManager, arena::ArenaType, material::MaterialType},
+};
 
-use super::{Bucket, BucketType, Tile, TileUniform};
+use super::{Bucket, BucketType, Buffers, FeatureRange, PointInstance, Tile};
 
 const DIMENSIONS: usize = 2;
 
-const RECT_VERTEX_BUFFER: [f32; 8] = [-0.5, -0.5, 0.5, -0.5, 0.5, 0.5, -0.5, 0.5];
-const RECT_INDICES_BUFFER: [u32; 6] = [0, 1, 2, 2, 3, 0];
+/// positions of the points of the features, runs on the parser thread
+pub fn get_buffers(features: &[Feature]) -> Buffers {
+  let mut all_vertices = vec![];
+  let mut ranges = vec![];
 
-impl<F> Bucket<F, { BucketType::Point }> for Tile {
-  fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self {
-    let tile_uniform = TileUniform::default();
-    let tile_uniform_buffer =
-      ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&[tile_uniform]),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-      });
-    let bind_group =
-      ressource_manager.create_bind_group(&BindGroupScope::Model, &[wgpu::BindGroupEntry {
-        binding: 0,
-        resource: tile_uniform_buffer.as_entire_binding(),
-      }]);
-
-    let vertex_wgpu_buffer = Some(ressource_manager.create_buffer_init(
-      &wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&RECT_VERTEX_BUFFER),
-        usage: wgpu::BufferUsages::VERTEX,
-      },
-    ));
-
-    let index_wgpu_buffer = Some(ressource_manager.create_buffer_init(
-      &wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&RECT_INDICES_BUFFER),
-        usage: wgpu::BufferUsages::INDEX,
-      },
+  for feature in features.iter() {
+    let start = all_vertices.len();
+    match feature.get_geometry() {
+      Point(point) => {
+        all_vertices.push(point.x());
+        all_vertices.push(point.y());
+      }
+      MultiPoint(multi_point) => {
+        all_vertices.reserve(multi_point.0.len() * DIMENSIONS);
+        for point in multi_point.iter() {
+          all_vertices.push(point.x());
+          all_vertices.push(point.y());
+        }
+      }
+      _ => {
+        info!("Geometry type currently not supported");
+      }
+    }
+    ranges.extend(FeatureRange::new(
+      feature,
+      (start, 0),
+      (all_vertices.len(), 0),
     ));
+  }
+  (all_vertices, vec![], ranges)
+}
 
+impl<F> Bucket<F, { BucketType::Point }> for Tile {
+  fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self {
     Self {
       material: ressource_manager.get_material(MaterialType::Point),
-      bind_group,
-      tile_uniform_buffer,
-      vertex_wgpu_buffer,
+      transform_slot: ressource_manager.allocate_transform_slot(),
+      vertex_slot: None,
       vertex_buffer: Vec::with_capacity(0),
-      index_wgpu_buffer,
+      index_slot: None,
       index_buffer: Vec::with_capacity(0),
-      instance_wgpu_buffer: None,
       extent,
+      source_layer: String::new(),
+      features: Vec::new(),
+      image: None,
+      texture_bind_group: None,
       bucket_type: BucketType::Point,
     }
   }
 
-  fn add_features(&mut self, features: &mut Vec<Feature>, ressource_manager: &RessourceManager) {
-    for feature in features.iter() {
-      match feature.get_geometry() {
-        Point(point) => {
-          let mut vertices = Vec::with_capacity(DIMENSIONS);
-          vertices.push(point.x());
-          vertices.push(point.y());
-          self.vertex_buffer.append(&mut vertices);
-        }
-        MultiPoint(multi_point) => {
-          let mut vertices = Vec::with_capacity(multi_point.0.len() * DIMENSIONS);
-          for point in multi_point.iter() {
-            vertices.push(point.x());
-            vertices.push(point.y());
-          }
-          self.vertex_buffer.append(&mut vertices);
-        }
-        _ => {
-          info!("Geometry type currently not supported");
-        }
-      }
+  /// points are drawn as instances of a quad, the instance slot is the vertex slot
+  fn upload(&mut self, ressource_manager: &RessourceManager) -> Result<(), Error> {
+    if self.vertex_buffer.is_empty() {
+      return Ok(());
     }
-
-    self.instance_wgpu_buffer = Some(ressource_manager.create_buffer_init(
-      &wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(&self.vertex_buffer),
-        usage: wgpu::BufferUsages::VERTEX,
-      },
-    ));
+    let tile = self.transform_slot.get_offset() as u32;
+    let instances: Vec<PointInstance> = self
+      .vertex_buffer
+      .chunks_exact(DIMENSIONS)
+      .zip(self.get_vertex_features())
+      .map(|(position, feature)| PointInstance {
+        position: [position[0], position[1]],
+        tile,
+        feature,
+      })
+      .collect();
+    self.vertex_slot =
+      Some(ressource_manager.upload(ArenaType::PointInstances, bytemuck::cast_slice(&instances))?);
+    Ok(())
   }
 }
@@ -0,0 +1,143 @@
+use crate::{
+  error::Error,
+  ressource::{RessourceManager, material::MaterialType},
+};
+
+use super::{Bucket, BucketType, Tile};
+
+/// cells of the grid in each direction, so the tile follows the curvature of the globe
+const GRID_SIZE: u32 = 16;
+
+pub const GRID_INDEX_COUNT: u32 = GRID_SIZE * GRID_SIZE * 6;
+
+/// bytes of one pixel, rgba with 8 bits per channel
+const PIXEL_SIZE: usize = 4;
+
+/// decoded pixels of a raster tile, rows from top to bottom
+pub struct RasterImage {
+  pixels: Vec<u8>,
+
+  width: u32,
+
+  height: u32,
+}
+
+impl RasterImage {
+  /// pixels need to be rgba and not premultiplied, width and height at most the maximum
+  /// texture size of the device
+  pub fn new(pixels: Vec<u8>, width: u32, height: u32, max_size: u32) -> Result<Self, String> {
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+      return Err(format!(
+        "image size {width}x{height} not within 1x1 and {max_size}x{max_size}"
+      ));
+    }
+    let expected = width as usize * height as usize * PIXEL_SIZE;
+    if pixels.len() != expected {
+      return Err(format!(
+        "{expected} bytes of rgba pixels expected, got {}",
+        pixels.len()
+      ));
+    }
+    Ok(Self {
+      pixels,
+      width,
+      height,
+    })
+  }
+}
+
+/// vertices of a grid from 0 to 1 in both directions and the indices of its triangles,
+/// scaled to the tile in the shader
+pub fn get_grid() -> (Vec<f32>, Vec<u32>) {
+  let mut vertices = Vec::with_capacity(((GRID_SIZE + 1) * (GRID_SIZE + 1) * 2) as usize);
+  for y in 0..=GRID_SIZE {
+    for x in 0..=GRID_SIZE {
+      vertices.push(x as f32 / GRID_SIZE as f32);
+      vertices.push(y as f32 / GRID_SIZE as f32);
+    }
+  }
+
+  let mut indices = Vec::with_capacity(GRID_INDEX_COUNT as usize);
+  for y in 0..GRID_SIZE {
+    for x in 0..GRID_SIZE {
+      let top_left = y * (GRID_SIZE + 1) + x;
+      let bottom_left = top_left + GRID_SIZE + 1;
+      indices.extend_from_slice(&[
+        top_left,
+        bottom_left,
+        top_left + 1,
+        top_left + 1,
+        bottom_left,
+        bottom_left + 1,
+      ]);
+    }
+  }
+  (vertices, indices)
+}
+
+impl<F> Bucket<F, { BucketType::Raster }> for Tile {
+  fn new(ressource_manager: &RessourceManager, extent: [f32; 4]) -> Self {
+    Self {
+      material: ressource_manager.get_material(MaterialType::Raster),
+      transform_slot: ressource_manager.allocate_transform_slot(),
+      vertex_slot: None,
+      vertex_buffer: Vec::with_capacity(0),
+      index_slot: None,
+      index_buffer: Vec::with_capacity(0),
+      extent,
+      source_layer: String::new(),
+      features: Vec::new(),
+      image: None,
+      texture_bind_group: None,
+      bucket_type: BucketType::Raster,
+    }
+  }
+
+  /// the pixels are kept, so the texture can be created again after a device loss
+  fn upload(&mut self, ressource_manager: &RessourceManager) -> Result<(), Error> {
+    let Some(image) = self.image.as_ref() else {
+      return Ok(());
+    };
+    let texture = ressource_manager.create_texture_with_data(
+      &wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+          width: image.width,
+          height: image.height,
+          depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+      },
+      &image.pixels,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    self.texture_bind_group = Some(ressource_manager.create_raster_bind_group(&view));
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn grid_and_image_size() {
+    let (vertices, indices) = get_grid();
+    let vertex_count = vertices.len() as u32 / 2;
+    assert_eq!(vertex_count, (GRID_SIZE + 1) * (GRID_SIZE + 1));
+    assert_eq!(indices.len() as u32, GRID_INDEX_COUNT);
+    assert!(indices.iter().all(|i| *i < vertex_count));
+    assert_eq!(vertices[..4], [0.0, 0.0, 1.0 / GRID_SIZE as f32, 0.0]);
+    assert_eq!(vertices[vertices.len() - 2..], [1.0, 1.0]);
+
+    assert!(RasterImage::new(vec![0; 2 * 3 * 4], 2, 3, 256).is_ok());
+    assert!(RasterImage::new(vec![0; 2 * 3 * 3], 2, 3, 256).is_err());
+    assert!(RasterImage::new(Vec::new(), 0, 0, 256).is_err());
+    assert!(RasterImage::new(vec![0; 512 * 4], 512, 1, 256).is_err());
+  }
+}
@@ -1,6 +1,21 @@
-use std::mem;
+use std::{
+  f32::consts::{FRAC_PI_2, PI},
+  mem,
+};
 
-use super::{BindGroupScope, RessourceManager};
+use super::{
+  BindGroupScope, RessourceManager,
+  projection::{EARTH_RADIUS, Projection},
+};
+
+/// vertical field of view of the perspective camera
+const FIELD_OF_VIEW: f32 = 0.643_501_1;
+
+/// maximum camera pitch, higher values would make the horizon visible
+const MAX_PITCH: f32 = 60.0 * PI / 180.0;
+
+/// zoom range in which the globe fades into the flat web mercator map
+const GLOBE_TRANSITION_ZOOM: (f32, f32) = (5.0, 6.0);
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
@@ -12,7 +27,32 @@ struct ViewBuffer {
 
   height: u32,
 
-  _pad: [u32; 2],
+  /// msaa sample count, needed for clipping per sample
+  sample_count: u32,
+
+  /// device pixels per css pixel, style sizes are given in css pixels
+  pixel_ratio: f32,
+
+  /// camera position in world-space
+  eye: glam::Vec4,
+
+  /// center x, center y, radius and transition factor of the globe
+  globe: [f32; 4],
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Camera {
+  /// center of the view in map coordinates
+  pub center: glam::Vec2,
+
+  /// zoom level of the tile grid of the projection
+  pub zoom: f32,
+
+  /// rotation in radians, same direction as the OpenLayers view rotation
+  pub bearing: f32,
+
+  /// tilt in radians, 0 is looking straight down
+  pub pitch: f32,
 }
 
 pub struct View {
@@ -30,6 +70,15 @@ pub struct View {
   /// half height of surface
   half_height: f32,
 
+  /// camera the view matrix is derived from, none if the matrix was set directly
+  camera: Option<Camera>,
+
+  /// project the map onto a globe at low zoom levels
+  globe: bool,
+
+  /// coordinate reference system of the camera and tile extents
+  projection: Projection,
+
   view_buffer: ViewBuffer,
 
   view_matrix_buffer: wgpu::Buffer,
@@ -41,7 +90,10 @@ impl View {
       view_matrix: glam::Mat4::IDENTITY,
       width,
       height,
-      _pad: [0, 0],
+      sample_count: ressource_manager.sample_count,
+      pixel_ratio: 1.0,
+      eye: glam::Vec4::ZERO,
+      globe: [0.0; 4],
     };
     let view_matrix_buffer =
       ressource_manager.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -56,7 +108,7 @@ impl View {
         label: None,
         entries: &[wgpu::BindGroupLayoutEntry {
           binding: 0,
-          visibility: wgpu::ShaderStages::VERTEX,
+          visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
           ty: wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Uniform,
             has_dynamic_offset: false,
@@ -79,11 +131,22 @@ impl View {
       height,
       half_width: width as f32 * 0.5,
       half_height: height as f32 * 0.5,
+      camera: None,
+      globe: false,
+      projection: Projection::default(),
       view_buffer: view_matrix,
       view_matrix_buffer,
     }
   }
 
+  /// recreate the gpu ressources on a new device, camera and projection are kept
+  pub fn recreate(&mut self, ressource_manager: &mut RessourceManager) {
+    let view = View::new((self.width, self.height), ressource_manager);
+    self.bind_group = view.bind_group;
+    self.view_matrix_buffer = view.view_matrix_buffer;
+    self.view_buffer.sample_count = view.view_buffer.sample_count;
+  }
+
   pub fn set<'frame>(
     &'frame self,
     render_pass: &mut wgpu::RenderPass<'frame>,
@@ -105,10 +168,67 @@ impl View {
     self.half_height = height as f32 * 0.5;
     self.view_buffer.width = width;
     self.view_buffer.height = height;
+    self.update_camera();
+  }
+
+  pub fn set_pixel_ratio(&mut self, pixel_ratio: f32) {
+    self.view_buffer.pixel_ratio = pixel_ratio;
+    self.update_camera();
   }
 
   pub fn set_view_matrix(&mut self, view_matrix: glam::Mat4) {
+    self.camera = None;
     self.view_buffer.view_matrix = view_matrix;
+    self.view_buffer.eye = glam::Vec4::ZERO;
+    self.view_buffer.globe = [0.0; 4];
+  }
+
+  pub fn set_camera(&mut self, camera: Camera) {
+    self.camera = Some(Camera {
+      pitch: camera.pitch.clamp(0.0, MAX_PITCH),
+      ..camera
+    });
+    self.update_camera();
+  }
+
+  pub fn set_projection(&mut self, projection: Projection) {
+    self.projection = projection;
+    self.update_camera();
+  }
+
+  /// globe projection needs a camera and web mercator, it has no effect otherwise
+  pub fn set_globe(&mut self, globe: bool) {
+    self.globe = globe;
+    self.update_camera();
+  }
+
+  pub fn is_globe_visible(&self) -> bool {
+    self.view_buffer.globe[3] > 0.0
+  }
+
+  fn update_camera(&mut self) {
+    let Some(camera) = self.camera else {
+      return;
+    };
+
+    let radius = get_globe_radius(camera.center);
+    let transition = if self.globe && self.projection.is_mercator() {
+      get_globe_transition(camera.zoom)
+    } else {
+      0.0
+    };
+    // zoom levels refer to css pixels, the surface size is given in device pixels
+    let resolution = self.projection.get_resolution(camera.zoom) / self.view_buffer.pixel_ratio;
+    let (view_matrix, eye) = get_camera_matrix(
+      &camera,
+      resolution,
+      self.get_half_size(),
+      radius * transition,
+    );
+
+    self.view_buffer.view_matrix = view_matrix;
+    self.view_buffer.eye = eye.extend(1.0);
+    self.view_buffer.globe = [camera.center.x, camera.center.y, radius, transition];
   }
 
   pub fn get_view_matrix(&self) -> glam::Mat4 {
@@ -122,4 +242,206 @@ impl View {
   pub fn get_half_size(&self) -> (f32, f32) {
     (self.half_width, self.half_height)
   }
+
+  pub fn get_pixel_ratio(&self) -> f32 {
+    self.view_buffer.pixel_ratio
+  }
+
+  /// position on the map plane under the pixel in device pixels from the top left
+  /// corner, none while the globe is visible
+  pub fn get_map_position(&self, pixel: glam::Vec2) -> Option<glam::Vec2> {
+    if self.is_globe_visible() {
+      return None;
+    }
+    let inverse = self.get_view_matrix().inverse();
+    let ndc = glam::Vec2::new(
+      pixel.x / self.half_width - 1.0,
+      1.0 - pixel.y / self.half_height,
+    );
+    Some(unproject_to_map_plane(&inverse, ndc))
+  }
+
+  /// visible area of the map plane as quad in map coordinates, counter-clockwise
+  /// starting at the bottom left corner of the screen
+  pub fn get_footprint(&self) -> [glam::Vec2; 4] {
+    let inverse = self.get_view_matrix().inverse();
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+      .map(|(x, y)| unproject_to_map_plane(&inverse, glam::Vec2::new(x, y)))
+  }
+
+  /// bounding box of the footprint as [min_x, min_y, max_x, max_y], the whole world
+  /// while the globe is visible
+  pub fn get_visible_extent(&self) -> [f32; 4] {
+    if self.is_globe_visible() {
+      return self.projection.get_extent();
+    }
+
+    let footprint = self.get_footprint();
+    let min = footprint.iter().fold(glam::Vec2::MAX, |min, p| min.min(*p));
+    let max = footprint.iter().fold(glam::Vec2::MIN, |max, p| max.max(*p));
+    [min.x, min.y, max.x, max.y]
+  }
+
+  /// separating axis test between the footprint and the given extent
+  pub fn intersects(&self, extent: [f32; 4]) -> bool {
+    if self.is_globe_visible() {
+      return true; // back side of the globe is discarded in the fragment shader
+    }
+
+    let visible_extent = self.get_visible_extent();
+    if extent[0] > visible_extent[2]
+      || extent[1] > visible_extent[3]
+      || extent[2] < visible_extent[0]
+      || extent[3] < visible_extent[1]
+    {
+      return false;
+    }
+
+    let footprint = self.get_footprint();
+    let corners = [
+      glam::Vec2::new(extent[0], extent[1]),
+      glam::Vec2::new(extent[2], extent[1]),
+      glam::Vec2::new(extent[2], extent[3]),
+      glam::Vec2::new(extent[0], extent[3]),
+    ];
+
+    // winding flips if the view matrix mirrors an axis
+    let winding = (footprint[1] - footprint[0])
+      .perp_dot(footprint[2] - footprint[0])
+      .signum();
+
+    for i in 0..footprint.len() {
+      let start = footprint[i];
+      let edge = footprint[(i + 1) % footprint.len()] - start;
+      if corners
+        .iter()
+        .all(|corner| edge.perp_dot(*corner - start) * winding < 0.0)
+      {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+/// perspective projection with a depth range of 0..1
+#[rustfmt::skip]
+fn get_projection_matrix(field_of_view: f32, aspect_ratio: f32, near: f32, far: f32) -> glam::Mat4 {
+  let f = 1.0 / (field_of_view * 0.5).tan();
+  let r = far / (near - far);
+  glam::Mat4::from_cols_array(&[
+    f / aspect_ratio, 0.0, 0.0, 0.0,
+    0.0, f, 0.0, 0.0,
+    0.0, 0.0, r, -1.0,
+    0.0, 0.0, r * near, 0.0,
+  ])
+}
+
+/// radius of a sphere touching the map plane at the center with the same scale as web mercator
+fn get_globe_radius(center: glam::Vec2) -> f32 {
+  EARTH_RADIUS * (center.y / EARTH_RADIUS).cosh()
+}
+
+fn get_globe_transition(zoom: f32) -> f32 {
+  let (start, end) = GLOBE_TRANSITION_ZOOM;
+  let t = ((zoom - start) / (end - start)).clamp(0.0, 1.0);
+  1.0 - t * t * (3.0 - 2.0 * t) // smoothstep
+}
+
+/// camera is placed in pixel space so that the map plane at the center keeps the
+/// same scale as an orthographic projection, independent of the pitch. returns the
+/// view projection matrix and the camera position in world-space
+fn get_camera_matrix(
+  camera: &Camera,
+  resolution: f32,
+  (half_width, half_height): (f32, f32),
+  globe_depth: f32,
+) -> (glam::Mat4, glam::Vec3) {
+  let half_field_of_view = FIELD_OF_VIEW * 0.5;
+  let distance = half_height / half_field_of_view.tan();
+
+  // distance to the farthest visible point on the map plane
+  let ground_angle = FRAC_PI_2 + camera.pitch;
+  let top_half_surface_distance =
+    half_field_of_view.sin() * distance / (PI - ground_angle - half_field_of_view).sin();
+  let far = (camera.pitch.sin() * top_half_surface_distance + distance)
+    .max(distance + globe_depth / resolution)
+    * 1.01;
+  let near = distance * 0.01;
+
+  let projection_matrix = get_projection_matrix(FIELD_OF_VIEW, half_width / half_height, near, far);
+  let camera_matrix = glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, -distance))
+    * glam::Mat4::from_rotation_x(-camera.pitch)
+    * glam::Mat4::from_rotation_z(-camera.bearing)
+    * glam::Mat4::from_scale(glam::Vec3::splat(1.0 / resolution))
+    * glam::Mat4::from_translation(-camera.center.extend(0.0));
+  let eye = camera_matrix.inverse().transform_point3(glam::Vec3::ZERO);
+
+  (projection_matrix * camera_matrix, eye)
+}
+
+/// intersection of the view ray through the ndc position with the map plane (z = 0)
+fn unproject_to_map_plane(inverse: &glam::Mat4, ndc: glam::Vec2) -> glam::Vec2 {
+  let near = inverse.project_point3(ndc.extend(0.0));
+  let far = inverse.project_point3(ndc.extend(1.0));
+  let t = if near.z == far.z {
+    0.0
+  } else {
+    (near.z / (near.z - far.z)).clamp(0.0, 1.0)
+  };
+  near.lerp(far, t).truncate()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn globe_transition() {
+    assert_eq!(get_globe_transition(0.0), 1.0);
+    assert_eq!(get_globe_transition(GLOBE_TRANSITION_ZOOM.0), 1.0);
+    assert_eq!(get_globe_transition(5.5), 0.5);
+    assert_eq!(get_globe_transition(GLOBE_TRANSITION_ZOOM.1), 0.0);
+    assert_eq!(get_globe_transition(20.0), 0.0);
+  }
+
+  #[test]
+  fn camera_without_pitch_matches_orthographic_scale() {
+    let camera = Camera {
+      center: glam::Vec2::new(1000.0, 2000.0),
+      zoom: 4.0,
+      bearing: 0.0,
+      pitch: 0.0,
+    };
+    let resolution = Projection::default().get_resolution(camera.zoom);
+    let (matrix, _) = get_camera_matrix(&camera, resolution, (256.0, 256.0), 0.0);
+
+    let center = matrix.project_point3(glam::Vec3::new(1000.0, 2000.0, 0.0));
+    assert!(center.truncate().length() < 1e-4);
+
+    let right = matrix.project_point3(glam::Vec3::new(1000.0 + 256.0 * resolution, 2000.0, 0.0));
+    assert!((right.x - 1.0).abs() < 1e-3);
+    assert!(right.y.abs() < 1e-3);
+  }
+
+  #[test]
+  fn camera_with_pitch_unprojects_to_trapezoid() {
+    let camera = Camera {
+      center: glam::Vec2::ZERO,
+      zoom: 10.0,
+      bearing: 0.0,
+      pitch: MAX_PITCH,
+    };
+    let resolution = Projection::default().get_resolution(camera.zoom);
+    let (matrix, _) = get_camera_matrix(&camera, resolution, (256.0, 256.0), 0.0);
+    let inverse = matrix.inverse();
+
+    let center = unproject_to_map_plane(&inverse, glam::Vec2::ZERO);
+    assert!(center.length() < 1e-2 * resolution);
+
+    let bottom = unproject_to_map_plane(&inverse, glam::Vec2::new(1.0, -1.0));
+    let top = unproject_to_map_plane(&inverse, glam::Vec2::new(1.0, 1.0));
+    assert!(top.x > bottom.x);
+    assert!(top.y > -bottom.y);
+  }
 }
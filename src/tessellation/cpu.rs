@@ -0,0 +1,60 @@
+use super::OutputVertex;
+
+/// same output as `line.wgsl`, for adapters without compute shaders. out of bounds
+/// reads are clamped like on the gpu and skipped edges stay zero. without vertices all
+/// edges are skipped
+pub fn tessellate(
+  vertices: &[f32],
+  indices: &[u32],
+  features: &[u32],
+) -> (Vec<OutputVertex>, Vec<u32>) {
+  let mut line_vertices = vec![OutputVertex::default(); indices.len() * 4];
+  let mut line_indices = vec![0; indices.len() * 6];
+  if vertices.len() < 2 || features.is_empty() {
+    return (line_vertices, line_indices);
+  }
+
+  let last_vertex = (vertices.len() / 2).saturating_sub(1);
+  let get_vertex = |i: u32| {
+    let i = (i as usize).min(last_vertex);
+    glam::Vec2::new(vertices[i * 2], vertices[i * 2 + 1])
+  };
+  let get_feature = |i: u32| features[(i as usize).min(features.len() - 1)];
+
+  for x in 0..indices.len() {
+    let i1 = indices[x];
+    let i2 = indices[(x + 1).min(indices.len() - 1)];
+
+    if i1 == i2 || (x > 0 && indices[x - 1] == i1) {
+      // separate linestring from the next one
+      continue;
+    }
+
+    let v1 = get_vertex(i1);
+    let v2 = get_vertex(i2);
+
+    let d = v2 - v1;
+    let n1 = normalize(glam::Vec2::new(-d.y, d.x));
+    let n2 = normalize(glam::Vec2::new(d.y, -d.x));
+
+    let ii1 = x as u32 * 4;
+    let (ii2, ii3, ii4) = (ii1 + 1, ii1 + 2, ii1 + 3);
+
+    let (f1, f2) = (get_feature(i1), get_feature(i2));
+
+    line_vertices[ii1 as usize] = OutputVertex::new(v1, n1, f1);
+    line_vertices[ii2 as usize] = OutputVertex::new(v1, n2, f1);
+    line_vertices[ii3 as usize] = OutputVertex::new(v2, n1, f2);
+    line_vertices[ii4 as usize] = OutputVertex::new(v2, n2, f2);
+
+    let offset = x * 6;
+    line_indices[offset..offset + 6].copy_from_slice(&[ii1, ii2, ii3, ii3, ii2, ii4]);
+  }
+
+  (line_vertices, line_indices)
+}
+
+/// division like the wgsl builtin, instead of the multiplication by the reciprocal of glam
+fn normalize(v: glam::Vec2) -> glam::Vec2 {
+  v / v.length()
+}
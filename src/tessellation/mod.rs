@@ -1,26 +1,55 @@
 use wgpu::util::DeviceExt;
 
+use crate::error::Error;
+
+mod cpu;
+
 static WORK_GROUP_MAX_X: f32 = 256.0;
 
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
+#[derive(Debug, Default, Copy, Clone, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
 struct OutputVertex {
   position: [f32; 2],
   normal: [f32; 2],
+
+  /// index in the features of the tile
+  feature: u32,
+
+  /// struct size of the shader
+  _padding: u32,
+}
+
+impl OutputVertex {
+  fn new(position: glam::Vec2, normal: glam::Vec2, feature: u32) -> Self {
+    Self {
+      position: position.to_array(),
+      normal: normal.to_array(),
+      feature,
+      _padding: 0,
+    }
+  }
 }
 
 pub struct LineTessellation {
   /// wgpu device and queue pair
   device_queue: (wgpu::Device, wgpu::Queue),
 
-  /// wgpu pipeline
-  pipeline: wgpu::ComputePipeline,
-
-  /// bind group layout
-  bind_group_layout: wgpu::BindGroupLayout,
+  /// wgpu pipeline and its bind group layout, none if lines are tessellated on the cpu
+  compute: Option<(wgpu::ComputePipeline, wgpu::BindGroupLayout)>,
 }
 
 impl LineTessellation {
+  /// storage buffers bound by the compute shader
+  pub const STORAGE_BUFFERS: u32 = 5;
+
+  /// for adapters without compute shaders, e.g. WebGL2 and older mobile devices
+  pub fn new_on_cpu(device_queue: (wgpu::Device, wgpu::Queue)) -> Self {
+    Self {
+      device_queue,
+      compute: None,
+    }
+  }
+
   pub fn new((device, queue): (wgpu::Device, wgpu::Queue)) -> Self {
     let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
       label: None,
@@ -72,6 +101,16 @@ impl LineTessellation {
           },
           count: None,
         },
+        wgpu::BindGroupLayoutEntry {
+          binding: 4,
+          visibility: wgpu::ShaderStages::COMPUTE,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
       ],
     });
 
@@ -92,19 +131,16 @@ impl LineTessellation {
 
     Self {
       device_queue: (device, queue),
-      pipeline,
-      bind_group_layout,
+      compute: Some((pipeline, bind_group_layout)),
     }
   }
 
-  fn create_buffers(&self, vertices: &[f32], indices: &[u32]) -> [wgpu::Buffer; 4] {
+  fn create_input_buffers(
+    &self,
+    (vertices, indices, features): (&[f32], &[u32], &[u32]),
+  ) -> [wgpu::Buffer; 3] {
     let (device, _) = &self.device_queue;
 
-    // generate 4 vertices for each edge and 6 indices for each edge
-    let line_vertices_buffer_size =
-      (std::mem::size_of::<OutputVertex>() * (indices.len()) * 4) as u64;
-    let line_indices_buffer_size = (std::mem::size_of_val(indices) * 6) as u64;
-
     [
       device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: None,
@@ -116,6 +152,20 @@ impl LineTessellation {
         contents: bytemuck::cast_slice(indices),
         usage: wgpu::BufferUsages::STORAGE, // for the compute shader
       }),
+      device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(features),
+        usage: wgpu::BufferUsages::STORAGE, // for the compute shader
+      }),
+    ]
+  }
+
+  #[cfg(test)]
+  fn create_output_buffers(&self, indices: &[u32]) -> [wgpu::Buffer; 2] {
+    let (device, _) = &self.device_queue;
+    let (line_vertices_buffer_size, line_indices_buffer_size) = get_output_sizes(indices.len());
+
+    [
       device.create_buffer(&wgpu::BufferDescriptor {
         label: None,
         usage: wgpu::BufferUsages::VERTEX // reuse as vertex buffer later
@@ -137,16 +187,16 @@ impl LineTessellation {
 
   fn create_bind_group(
     &self,
-    vertices_buffer: &wgpu::Buffer,
-    indices_buffer: &wgpu::Buffer,
-    line_vertices_buffer: &wgpu::Buffer,
-    line_indices_buffer: &wgpu::Buffer,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    [vertices_buffer, indices_buffer, features_buffer]: &[wgpu::Buffer; 3],
+    line_vertices: wgpu::BufferBinding,
+    line_indices: wgpu::BufferBinding,
   ) -> wgpu::BindGroup {
     let (device, _) = &self.device_queue;
 
     device.create_bind_group(&wgpu::BindGroupDescriptor {
       label: None,
-      layout: &self.bind_group_layout,
+      layout: bind_group_layout,
       entries: &[
         wgpu::BindGroupEntry {
           binding: 0,
@@ -158,44 +208,106 @@ impl LineTessellation {
         },
         wgpu::BindGroupEntry {
           binding: 2,
-          resource: line_vertices_buffer.as_entire_binding(),
+          resource: wgpu::BindingResource::Buffer(line_vertices),
         },
         wgpu::BindGroupEntry {
           binding: 3,
-          resource: line_indices_buffer.as_entire_binding(),
+          resource: wgpu::BindingResource::Buffer(line_indices),
+        },
+        wgpu::BindGroupEntry {
+          binding: 4,
+          resource: features_buffer.as_entire_binding(),
         },
       ],
     })
   }
 
   // TODO: maybe later a variant with VERTEX and INDEX buffer as input params
+  /// tessellate into new buffers, so the output can be read back on its own
+  #[cfg(test)]
   pub async fn tessellate(
     &self,
-    (vertices, indices): (&[f32], &[u32]),
-  ) -> (wgpu::Buffer, wgpu::Buffer) {
+    (vertices, indices, features): (&[f32], &[u32], &[u32]),
+  ) -> Result<(wgpu::Buffer, wgpu::Buffer), Error> {
+    let [line_vertices_buffer, line_indices_buffer] = self.create_output_buffers(indices);
+
+    self.tessellate_into(
+      (vertices, indices, features),
+      (
+        line_vertices_buffer.as_entire_buffer_binding(),
+        line_indices_buffer.as_entire_buffer_binding(),
+      ),
+    )?;
+
+    Ok((line_vertices_buffer, line_indices_buffer))
+  }
+
+  /// write the tessellation into ranges of existing buffers, e.g. slots of an arena.
+  /// the ranges need the sizes of `get_output_sizes` and offsets aligned for storage
+  /// buffer bindings. output indices start at zero for the first output vertex. output
+  /// vertices get the feature index of their input vertex
+  pub fn tessellate_into(
+    &self,
+    (vertices, indices, features): (&[f32], &[u32], &[u32]),
+    (line_vertices, line_indices): (wgpu::BufferBinding, wgpu::BufferBinding),
+  ) -> Result<(), Error> {
     let (device, queue) = &self.device_queue;
 
-    #[rustfmt::skip]
-    let [
-      vertices_buffer,
-      indices_buffer,
-      line_vertices_buffer,
-      line_indices_buffer
-    ] = self.create_buffers(vertices, indices);
+    if indices.len() < 2 {
+      return Err(Error::Tessellation(format!(
+        "at least one edge needed, got {} indices",
+        indices.len()
+      )));
+    }
+    if vertices.len() < 2 {
+      return Err(Error::Tessellation("at least one vertex needed".to_owned()));
+    }
+    if features.len() != vertices.len() / 2 {
+      return Err(Error::Tessellation(format!(
+        "a feature index for each of the {} vertices needed, got {}",
+        vertices.len() / 2,
+        features.len()
+      )));
+    }
 
-    let bind_group = self.create_bind_group(
-      &vertices_buffer,
-      &indices_buffer,
-      &line_vertices_buffer,
-      &line_indices_buffer,
-    );
+    let Some((pipeline, bind_group_layout)) = &self.compute else {
+      // written with the next submission, like the compute pass
+      let (cpu_vertices, cpu_indices) = cpu::tessellate(vertices, indices, features);
+      queue.write_buffer(
+        line_vertices.buffer,
+        line_vertices.offset,
+        bytemuck::cast_slice(&cpu_vertices),
+      );
+      queue.write_buffer(
+        line_indices.buffer,
+        line_indices.offset,
+        bytemuck::cast_slice(&cpu_indices),
+      );
+      return Ok(());
+    };
+
+    let input_buffers = self.create_input_buffers((vertices, indices, features));
 
     let mut command_encoder =
       device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+    // edges between linestrings are skipped by the shader, the ranges may hold output
+    // of an earlier tessellation
+    for binding in [&line_vertices, &line_indices] {
+      command_encoder.clear_buffer(binding.buffer, binding.offset, binding.size.map(u64::from));
+    }
+
+    let bind_group = self.create_bind_group(
+      bind_group_layout,
+      &input_buffers,
+      line_vertices,
+      line_indices,
+    );
+
     {
       let mut pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
 
-      pass.set_pipeline(&self.pipeline);
+      pass.set_pipeline(pipeline);
       pass.set_bind_group(0, Some(&bind_group), &[]);
 
       let x = (indices.len() as f32 / WORK_GROUP_MAX_X).ceil() as u32;
@@ -204,10 +316,24 @@ impl LineTessellation {
 
     queue.submit(Some(command_encoder.finish()));
 
-    (line_vertices_buffer, line_indices_buffer)
+    Ok(())
   }
 }
 
+/// number of indices generated for a line with the given number of input indices,
+/// 6 for each edge
+pub fn get_line_index_count(index_count: usize) -> usize {
+  index_count * 6
+}
+
+/// sizes in bytes of the output vertices and indices, 4 vertices for each edge
+pub fn get_output_sizes(index_count: usize) -> (u64, u64) {
+  (
+    (std::mem::size_of::<OutputVertex>() * index_count * 4) as u64,
+    (std::mem::size_of::<u32>() * get_line_index_count(index_count)) as u64,
+  )
+}
+
 #[cfg(test)]
 #[cfg(not(target_arch = "wasm32"))]
 mod tests {
@@ -220,10 +346,12 @@ mod tests {
   struct Vertex {
     position: [f32; 2],
     normal: [f32; 2],
+    feature: u32,
+    _padding: u32,
   }
 
   async fn initialize_test() -> (wgpu::Device, wgpu::Queue) {
-    env_logger::init_from_env(
+    let _ = env_logger::try_init_from_env(
       env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
     );
 
@@ -307,6 +435,7 @@ mod tests {
 
     let vertices = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
     let indices = [0, 1, 2, 3, 0];
+    let features = [0; 4];
 
     let line_tessellation = Arc::new(LineTessellation::new((device.clone(), queue.clone())));
 
@@ -315,7 +444,8 @@ mod tests {
     let queue1 = queue.clone();
     let handle1 = std::thread::spawn(move || {
       let (vertices, indices) =
-        pollster::block_on(line_tessellation1.tessellate((&vertices, &indices)));
+        pollster::block_on(line_tessellation1.tessellate((&vertices, &indices, &features)))
+          .unwrap();
       {
         pollster::block_on(map_and_log_buffer(
           (device1.clone(), queue1.clone()),
@@ -352,7 +482,7 @@ mod tests {
     });
     let handle2 = std::thread::spawn(move || {
       let (vertices, indices) =
-        pollster::block_on(line_tessellation.tessellate((&vertices, &indices)));
+        pollster::block_on(line_tessellation.tessellate((&vertices, &indices, &features))).unwrap();
       {
         pollster::block_on(map_and_log_buffer(
           (device.clone(), queue.clone()),
@@ -372,4 +502,84 @@ mod tests {
     handle1.join().unwrap();
     handle2.join().unwrap();
   }
+
+  #[test]
+  fn tessellate_lines_on_cpu() {
+    let vertices = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+    let indices = [0, 1, 2, 3, 0];
+    let features = [3; 4];
+
+    let (line_vertices, line_indices) = cpu::tessellate(&vertices, &indices, &features);
+
+    assert_eq!(20, line_vertices.len());
+    assert_eq!(30, line_indices.len());
+    assert_eq!([0.0, 0.0], line_vertices[0].position);
+    assert_eq!([0.0, 1.0], line_vertices[0].normal);
+    assert_eq!([1.0, 0.0], line_vertices[2].position);
+    assert_eq!([0.0, -1.0], line_vertices[3].normal);
+    assert_eq!(3, line_vertices[3].feature);
+    assert_eq!([0, 1, 2, 2, 1, 3], line_indices[..6]);
+    assert_eq!([12, 13, 14, 14, 13, 15], line_indices[18..24]);
+    // last index has no edge
+    assert_eq!([0; 6], line_indices[24..]);
+  }
+
+  #[test]
+  fn cpu_matches_gpu() {
+    let (device, queue) = pollster::block_on(initialize_test());
+
+    // two linestrings, separated by a repeated index
+    let vertices = [0.0, 0.0, 3.0, 4.0, 10.0, -2.0, 5.0, 5.0, 6.0, 7.0];
+    let indices = [0, 1, 2, 2, 3, 4, 4];
+    let features = [0, 0, 0, 1, 1];
+
+    let line_tessellation = LineTessellation::new((device.clone(), queue.clone()));
+    let (gpu_vertices, gpu_indices) =
+      pollster::block_on(line_tessellation.tessellate((&vertices, &indices, &features))).unwrap();
+    let (cpu_vertices, cpu_indices) = cpu::tessellate(&vertices, &indices, &features);
+    assert!(pollster::block_on(line_tessellation.tessellate((&[], &[0, 1], &[]))).is_err());
+
+    let read_vertices = std::cell::RefCell::new(Vec::new());
+    pollster::block_on(map_and_log_buffer(
+      (device.clone(), queue.clone()),
+      &gpu_vertices,
+      gpu_vertices.size(),
+      |bytes| {
+        read_vertices
+          .borrow_mut()
+          .extend_from_slice(bytemuck::cast_slice::<u8, OutputVertex>(bytes))
+      },
+    ));
+    let read_indices = std::cell::RefCell::new(Vec::new());
+    pollster::block_on(map_and_log_buffer(
+      (device, queue),
+      &gpu_indices,
+      gpu_indices.size(),
+      |bytes| {
+        read_indices
+          .borrow_mut()
+          .extend_from_slice(bytemuck::cast_slice::<u8, u32>(bytes))
+      },
+    ));
+
+    assert_eq!(cpu_indices, *read_indices.borrow());
+    assert_eq!(cpu_vertices.len(), read_vertices.borrow().len());
+    for (cpu, gpu) in cpu_vertices.iter().zip(read_vertices.borrow().iter()) {
+      assert_eq!(cpu.position, gpu.position);
+      assert_eq!(cpu.feature, gpu.feature);
+      // normalize may use an inverse square root on the gpu
+      let normal = glam::Vec2::from(cpu.normal);
+      assert!(
+        normal.abs_diff_eq(glam::Vec2::from(gpu.normal), 1e-6),
+        "{cpu:?} {gpu:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn empty_vertices() {
+    let (cpu_vertices, cpu_indices) = cpu::tessellate(&[], &[0, 1], &[]);
+    assert_eq!(cpu_vertices.len(), 8);
+    assert!(cpu_indices.iter().all(|index| *index == 0));
+  }
 }
@@ -1,6 +1,7 @@
 struct OutputVertex {
   position: vec2<f32>,
   normal: vec2<f32>,
+  feature: u32,
 }
 
 @group(0) @binding(0)
@@ -15,6 +16,10 @@ var<storage, read_write> line_vertices : array<OutputVertex>;
 @group(0) @binding(3)
 var<storage, read_write> line_indices : array<u32>;
 
+// index in the features of the tile for every input vertex
+@group(0) @binding(4)
+var<storage, read> features : array<u32>;
+
 @compute @workgroup_size(256, 1)
 fn main(@builtin(global_invocation_id) global_id : vec3<u32>) {
   if (global_id.x >= arrayLength(&indices)) {
@@ -30,6 +35,8 @@ fn main(@builtin(global_invocation_id) global_id : vec3<u32>) {
 
   let v1 = vertices[i1];
   let v2 = vertices[i2];
+  let f1 = features[i1];
+  let f2 = features[i2];
 
   let dx = v2.x - v1.x;
   let dy = v2.y - v1.y;
@@ -41,10 +48,10 @@ fn main(@builtin(global_invocation_id) global_id : vec3<u32>) {
   let ii3 = ii1 + 2u;
   let ii4 = ii1 + 3u;
 
-  line_vertices[ii1] = OutputVertex(v1, n1);
-  line_vertices[ii2] = OutputVertex(v1, n2);
-  line_vertices[ii3] = OutputVertex(v2, n1);
-  line_vertices[ii4] = OutputVertex(v2, n2);
+  line_vertices[ii1] = OutputVertex(v1, n1, f1);
+  line_vertices[ii2] = OutputVertex(v1, n2, f1);
+  line_vertices[ii3] = OutputVertex(v2, n1, f2);
+  line_vertices[ii4] = OutputVertex(v2, n2, f2);
 
   let offset = global_id.x * 6u;
   line_indices[offset + 0u] = ii1;
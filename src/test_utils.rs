@@ -0,0 +1,24 @@
+//! encoders for building test inputs of the decoders
+
+/// unsigned LEB128 varint like in protobuf, MLT and PMTiles directories
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+  while value >= 0x80 {
+    bytes.push((value as u8 & 0x7f) | 0x80);
+    value >>= 7;
+  }
+  bytes.push(value as u8);
+}
+
+/// gzip member with an optional file name in the header, the crc32 is left 0
+pub fn gzip(bytes: &[u8], name: Option<&str>) -> Vec<u8> {
+  let flags = if name.is_some() { 1 << 3 } else { 0 };
+  let mut gzip = vec![0x1f, 0x8b, 8, flags, 0, 0, 0, 0, 0, 255];
+  if let Some(name) = name {
+    gzip.extend_from_slice(name.as_bytes());
+    gzip.push(0);
+  }
+  gzip.extend(miniz_oxide::deflate::compress_to_vec(bytes, 6));
+  gzip.extend([0; 4]);
+  gzip.extend((bytes.len() as u32).to_le_bytes());
+  gzip
+}
@@ -4,12 +4,21 @@
 
 mod utils;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
 use wasm_bindgen_test::*;
 
 use utils::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+const OSM_PBF_HASH: [u8; 32] = [
+  155, 210, 99, 215, 34, 140, 167, 36, 126, 218, 111, 44, 241, 6, 10, 205, 9, 179, 75, 237, 159,
+  35, 148, 131, 139, 45, 212, 131, 162, 190, 87, 48,
+];
+
 #[wasm_bindgen_test]
 async fn osm_pbf() {
   initialize();
@@ -17,18 +26,20 @@ async fn osm_pbf() {
   // arrange
   let canvas_ref = CANVAS.borrow();
   let canvas = canvas_ref.as_ref().unwrap();
-  wgpu_layers::wasm::start_with_canvas(canvas).await;
+  // reference hashes are rendered without msaa
+  wgpu_layers::wasm::start_with_canvas(canvas, Some(1))
+    .await
+    .unwrap();
   wgpu_layers::add_pbf_tile_data(
     include_bytes!("pbf/osm_4_8_5.pbf").to_vec(),
     vec![4, 8, 5],
     vec![0.0, 5009377.085697312, 2_504_688.5, 7_514_065.5],
   )
-  .await;
+  .await
+  .unwrap();
 
   // act
-  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1]);
-  timeout(500).await; // wait for compute shader
-  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1]);
+  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1], 1.0).unwrap();
   timeout(1500).await; // wait to render
 
   // assert
@@ -36,13 +47,43 @@ async fn osm_pbf() {
   let image = pdqhash::image::load_from_memory(&image_data[..]).unwrap();
   let (hash, _) = pdqhash::generate_pdq_full_size(&image);
 
-  assert_eq!(
-    [
-      155, 210, 99, 215, 34, 140, 167, 36, 126, 218, 111, 44, 241, 6, 10, 205, 9, 179, 75, 237,
-      159, 35, 148, 131, 139, 45, 212, 131, 162, 190, 87, 48
-    ],
-    hash
-  );
+  assert_eq!(OSM_PBF_HASH, hash);
+}
+
+#[wasm_bindgen_test]
+async fn osm_pbf_msaa() {
+  initialize();
+
+  // arrange
+  let canvas_ref = CANVAS.borrow();
+  let canvas = canvas_ref.as_ref().unwrap();
+  wgpu_layers::wasm::start_with_canvas(canvas, Some(4))
+    .await
+    .unwrap();
+  wgpu_layers::add_pbf_tile_data(
+    include_bytes!("pbf/osm_4_8_5.pbf").to_vec(),
+    vec![4, 8, 5],
+    vec![0.0, 5009377.085697312, 2_504_688.5, 7_514_065.5],
+  )
+  .await
+  .unwrap();
+
+  // act
+  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1], 1.0).unwrap();
+  timeout(1500).await; // wait to render
+
+  // assert
+  let image_data = get_canvas_image_data(canvas).await;
+  let image = pdqhash::image::load_from_memory(&image_data[..]).unwrap();
+  let (hash, _) = pdqhash::generate_pdq_full_size(&image);
+
+  // only the anti-aliased edges differ from the reference rendered without msaa
+  let distance: u32 = OSM_PBF_HASH
+    .iter()
+    .zip(hash)
+    .map(|(a, b)| (a ^ b).count_ones())
+    .sum();
+  assert!(distance <= 16, "hash distance {distance}");
 }
 
 #[wasm_bindgen_test]
@@ -52,10 +93,13 @@ async fn empty() {
   // arrange
   let canvas_ref = CANVAS.borrow();
   let canvas = canvas_ref.as_ref().unwrap();
-  wgpu_layers::wasm::start_with_canvas(canvas).await;
+  // reference hashes are rendered without msaa
+  wgpu_layers::wasm::start_with_canvas(canvas, Some(1))
+    .await
+    .unwrap();
 
   // act
-  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1]);
+  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1], 1.0).unwrap();
   timeout(1500).await; // wait to render
 
   // assert
@@ -71,3 +115,67 @@ async fn empty() {
     hash
   );
 }
+
+#[wasm_bindgen_test]
+async fn tile_ready() {
+  initialize();
+
+  // arrange
+  let canvas_ref = CANVAS.borrow();
+  let canvas = canvas_ref.as_ref().unwrap();
+  wgpu_layers::wasm::start_with_canvas(canvas, Some(1))
+    .await
+    .unwrap();
+  let ready_tiles = Rc::new(RefCell::new(Vec::new()));
+  let ready_tiles_cb = ready_tiles.clone();
+  let callback = Closure::<dyn Fn(String, Vec<u32>)>::new(move |source, tile_coord| {
+    ready_tiles_cb.borrow_mut().push((source, tile_coord));
+  });
+  let callback: &js_sys::Function = callback.as_ref().unchecked_ref();
+  wgpu_layers::wasm::set_on_tile_ready(Some(callback.clone())).unwrap();
+
+  // act
+  wgpu_layers::add_pbf_tile_data(
+    include_bytes!("pbf/osm_4_8_5.pbf").to_vec(),
+    vec![4, 8, 5],
+    vec![0.0, 5009377.085697312, 2_504_688.5, 7_514_065.5],
+  )
+  .await
+  .unwrap();
+
+  // assert
+  assert_eq!(
+    vec![("default".to_owned(), vec![4, 8, 5])],
+    *ready_tiles.borrow()
+  );
+}
+
+#[wasm_bindgen_test]
+async fn raster_tile() {
+  initialize();
+
+  // arrange
+  let canvas_ref = CANVAS.borrow();
+  let canvas = canvas_ref.as_ref().unwrap();
+  wgpu_layers::wasm::start_with_canvas(canvas, Some(1))
+    .await
+    .unwrap();
+  let extent = vec![0.0, 5009377.085697312, 2_504_688.5, 7_514_065.5];
+
+  // act
+  let result = wgpu_layers::add_raster_tile_data(
+    vec![255; 256 * 256 * 4],
+    256,
+    256,
+    vec![4, 8, 5],
+    extent.clone(),
+  )
+  .await;
+  let invalid_result =
+    wgpu_layers::add_raster_tile_data(vec![255; 3], 256, 256, vec![4, 8, 5], extent).await;
+
+  // assert
+  result.unwrap();
+  assert!(invalid_result.is_err());
+  wgpu_layers::render(get_view_matrix(), vec![CANVAS_SIZE.0, CANVAS_SIZE.1], 1.0).unwrap();
+}